@@ -0,0 +1,71 @@
+// Lexer throughput on generated sources of growing size. Run with
+// `cargo bench --bench lexer`; time per byte should stay flat as the input
+// grows if lexing is linear.
+#[allow(dead_code)]
+#[path = "../src/lexer/mod.rs"]
+mod lexer;
+
+// The lexer only needs the diagnostic codes
+#[allow(dead_code)]
+#[path = "../src/diagnostics/codes.rs"]
+mod diagnostics;
+
+use std::time::Instant;
+
+// One chunk of representative Solu: declarations, comments, literals,
+// operators and a non-ASCII string.
+const CHUNK: &str = "struct Entity is
+	pos_x: i32
+	pos_y: i32
+	sprite: char
+	map: *Map
+end
+
+-- moves the entity by (xd, yd)
+Entity::move(xd: i32, yd: i32) do
+	this.pos_x += xd
+	this.pos_y += yd
+end
+
+main(): i32 do
+	mut total: i64 = 0x1F + 0b101 * 3.25
+	for i in 0..1000 do
+		total = total << 1 | (i % 7 == 0 ? 1 : 0)
+	end
+	name: String = \"héllo wörld\"
+	c: char = '\\u0061'
+	return [i32] total
+end
+";
+
+fn generate(size: usize) -> String {
+    let mut src = String::with_capacity(size + CHUNK.len());
+    while src.len() < size {
+        src.push_str(CHUNK);
+    }
+    src
+}
+
+fn main() {
+    println!(
+        "{:>10} {:>10} {:>12} {:>10}",
+        "bytes", "tokens", "time", "ns/byte"
+    );
+    for kb in [64, 128, 256, 512, 1024] {
+        let src = generate(kb * 1024);
+        let start = Instant::now();
+        let mut lex = lexer::Lexer::new(&src);
+        let tokens = match lex.parse() {
+            Ok(t) => t,
+            Err(errors) => panic!("{}: {}", errors[0].span, errors[0].msg),
+        };
+        let elapsed = start.elapsed();
+        println!(
+            "{:>10} {:>10} {:>12?} {:>10.2}",
+            src.len(),
+            tokens.len(),
+            elapsed,
+            elapsed.as_nanos() as f64 / src.len() as f64
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use crate::parser::Primitive;
+use std::fmt;
+
+// The DVM1 instruction set, mirroring vm/src/vm/bytecode.rs. An opcode's
+// discriminant is the word written to the code section and indexes the VM's
+// DISPATCH_TABLE, keep the order in sync with the VM.
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    // Loads imm -> r1
+    LoadI32,
+    LoadI64,
+    LoadF32,
+    LoadF64,
+    // Moves r1 -> r2
+    Mov,
+    // Adds r1 + r2
+    AddI32,
+    AddI64,
+    AddF32,
+    AddF64,
+    // Subtracts r1 - r2
+    SubI32,
+    SubI64,
+    SubF32,
+    SubF64,
+    // Multiply r1 * r2
+    MulI32,
+    MulI64,
+    MulF32,
+    MulF64,
+    // Divide r1 / r2
+    DivI32,
+    DivI64,
+    DivF32,
+    DivF64,
+    // Converts r2 -> r1. 8 and 16 bit integers live in I32 registers, the
+    // Trunc opcodes wrap r2 to their width and sign or zero extend it back.
+    // Float to integer conversions saturate, NaN becomes 0.
+    I32ToI64,
+    U32ToI64,
+    I64ToI32,
+    I32ToF32,
+    I32ToF64,
+    I64ToF32,
+    I64ToF64,
+    U64ToF32,
+    U64ToF64,
+    F32ToI32,
+    F32ToI64,
+    F64ToI32,
+    F64ToI64,
+    F32ToU64,
+    F64ToU64,
+    F32ToF64,
+    F64ToF32,
+    TruncI8,
+    TruncU8,
+    TruncI16,
+    TruncU16,
+    // Remainder r2 % r3, unsigned division and remainder
+    ModI32,
+    ModI64,
+    DivU32,
+    DivU64,
+    ModU32,
+    ModU64,
+    // Negates r2 -> r1
+    NegI32,
+    NegI64,
+    NegF32,
+    NegF64,
+    // Bitwise r2 op r3, shifts by r3 modulo the width of the register
+    And,
+    Or,
+    Xor,
+    ShlI32,
+    ShlI64,
+    ShrI32,
+    ShrI64,
+    ShrU32,
+    ShrU64,
+    // Compares r2 with r3, r1 becomes an I32 1 or 0
+    Eq,
+    EqF32,
+    EqF64,
+    LtI32,
+    LtI64,
+    LtU32,
+    LtU64,
+    LtF32,
+    LtF64,
+    LeI32,
+    LeI64,
+    LeU32,
+    LeU64,
+    LeF32,
+    LeF64,
+    // 1 if r2 is zero, 0 otherwise
+    Not,
+    // Conditional Jumps
+    Equal,
+    GreaterThan,
+    LessThan,
+    // Calls function r1, copying its arguments from r3.. into its first
+    // locals. Its local 0 is copied into r2 when it returns, unless r2 is
+    // NO_RETURN.
+    Call,
+    // Calls function r1 in place of the current one, copying its arguments
+    // from r2.. into the first locals of this frame. It returns to where
+    // the current function would have.
+    TailCall,
+    // Move Instruction Pointer by r1 words, counted from the word after
+    // the opcode
+    Jmp,
+    // Move Instruction Pointer by r1 if r2 == 0
+    Jmpif,
+    // Move Instruction Pointer by r1 if r2 != 0
+    Jmpnif,
+    // Print (Debugging)
+    Print,
+    // Pointer into Arena
+    AddI32Ptr,
+    AddI64Ptr,
+    // Reads the memory at r2 + imm into r1, narrow integers are sign or zero
+    // extended. Addresses are Ptr registers, reading through nil traps.
+    ReadI8,
+    ReadU8,
+    ReadI16,
+    ReadU16,
+    ReadI32,
+    ReadI64,
+    ReadF32,
+    ReadF64,
+    ReadPtr,
+    // Writes the low bytes of r2 to the memory at r1 + imm
+    Write8,
+    Write16,
+    Write32,
+    Write64,
+    // Copies imm bytes from r2 to r1
+    MemCopy,
+    // r1 = address imm in the current frame's memory
+    FrameAddr,
+    // r1 = r2 + imm
+    Offset,
+    // r1 = r2 + r3 * imm1, trapping unless r3 < imm2. An imm2 of u64::MAX
+    // leaves the index unchecked.
+    Index,
+    // return value from frame
+    Ret,
+    // Halt the VM
+    Halt,
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    I32,
+    I64,
+    F32,
+    F64,
+    Ptr,
+}
+
+pub const FRAME_LOCALS_SIZE: usize = 300;
+// `Call` destination of a function that returns nothing
+pub const NO_RETURN: u64 = u64::MAX;
+// `Index` length that leaves the index unchecked
+pub const UNCHECKED: u64 = u64::MAX;
+
+impl ValueType {
+    // Register type Solu values of type `p` are held in
+    pub fn of(p: Primitive) -> Self {
+        match p {
+            Primitive::F32 => ValueType::F32,
+            Primitive::F64 => ValueType::F64,
+            Primitive::I64 | Primitive::U64 => ValueType::I64,
+            _ => ValueType::I32,
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ValueType::I32 => "i32",
+            ValueType::I64 => "i64",
+            ValueType::F32 => "f32",
+            ValueType::F64 => "f64",
+            ValueType::Ptr => "ptr",
+        };
+        write!(f, "{}", name)
+    }
+}
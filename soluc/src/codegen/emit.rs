@@ -0,0 +1,500 @@
+use super::bytecode::*;
+use super::image::{FunctionEntry, Image};
+use super::regalloc::allocate;
+use crate::diagnostics::{self, Diagnostic};
+use crate::ir::{BlockId, Function, Inst, Program, VReg};
+use crate::parser::{BinaryOp, Primitive, UnaryOp};
+use crate::sema::Value;
+
+type Reg = u32;
+
+// Turns the IR into bytecode. Virtual registers are allocated to frame
+// locals by `allocate`, arguments arrive in the first locals and the return
+// value leaves in local 0.
+pub fn generate(program: &Program) -> (Image, Vec<Diagnostic>) {
+    let mut g = Gen {
+        image: Image::default(),
+        diags: vec![],
+        alloc: vec![],
+        regs: 0,
+        scratch: 0,
+        fixups: vec![],
+    };
+    g.image.entry = program.entry as u32;
+    for func in program.functions.iter() {
+        g.function(func);
+    }
+    (g.image, g.diags)
+}
+
+struct Gen {
+    image: Image,
+    diags: Vec<Diagnostic>,
+
+    // The function being compiled
+    alloc: Vec<Reg>, // Local of each virtual register
+    regs: Reg,       // Locals holding virtual registers
+    scratch: Reg,    // Registers used after those for temporaries
+    // Jump offsets to patch, by the block they jump to
+    fixups: Vec<(usize, BlockId)>,
+}
+
+// ---------------------------------------
+// Functions
+// ---------------------------------------
+impl Gen {
+    fn function(&mut self, func: &Function) {
+        let entry = self.image.code.len() as u64;
+        let alloc = allocate(func);
+        self.alloc = alloc.regs;
+        self.regs = alloc.count.max(func.params as Reg);
+        self.scratch = 0;
+        self.fixups.clear();
+
+        // Blocks are laid out in order, a jump to the next one is left out
+        let mut starts = vec![];
+        for (b, block) in func.blocks.iter().enumerate() {
+            starts.push(self.image.code.len());
+            for inst in block.insts.iter() {
+                self.inst(func, inst, BlockId(b + 1));
+            }
+        }
+        for (at, target) in std::mem::take(&mut self.fixups) {
+            self.patch_to(at, starts[target.0]);
+        }
+
+        let reg_count = (self.regs + self.scratch).max(func.ret.is_some() as Reg);
+        if reg_count as usize > FRAME_LOCALS_SIZE {
+            self.diags.push(
+                Diagnostic::error(
+                    diagnostics::TOO_MANY_REGISTERS,
+                    func.span,
+                    format!("`{}` needs {} registers", func.name, reg_count),
+                )
+                .with_label("too many values are live at the same time")
+                .with_note(format!("a VM frame has {} registers", FRAME_LOCALS_SIZE))
+                .with_help("split it into smaller functions"),
+            );
+        }
+        self.image.functions.push(FunctionEntry {
+            name: func.name.clone(),
+            entry,
+            reg_count: reg_count.min(u16::MAX as Reg) as u16,
+            memory: func.memory,
+            arg_types: func.regs[..func.params].to_vec(),
+            ret_type: func.ret,
+            flags: 0,
+        });
+    }
+
+    fn reg(&self, v: VReg) -> Reg {
+        self.alloc[v.0 as usize]
+    }
+
+    // Temporary `i` of the current instruction
+    fn scratch(&mut self, i: Reg) -> Reg {
+        self.scratch = self.scratch.max(i + 1);
+        self.regs + i
+    }
+}
+
+// ---------------------------------------
+// Emission
+// ---------------------------------------
+impl Gen {
+    fn emit(&mut self, op: Opcode, operands: &[u64]) {
+        self.image.code.push(op as u64);
+        self.image.code.extend_from_slice(operands);
+    }
+
+    fn op(&mut self, op: Opcode, regs: &[Reg]) {
+        self.image.code.push(op as u64);
+        self.image.code.extend(regs.iter().map(|r| *r as u64));
+    }
+
+    fn mov(&mut self, dst: Reg, src: Reg) {
+        if dst != src {
+            self.op(Opcode::Mov, &[dst, src]);
+        }
+    }
+
+    // Jumps to the start of `target`, patched once the function is laid out
+    fn jump(&mut self, op: Opcode, target: BlockId, cond: Option<Reg>) {
+        self.image.code.push(op as u64);
+        self.fixups.push((self.image.code.len(), target));
+        self.image.code.push(0);
+        if let Some(r) = cond {
+            self.image.code.push(r as u64);
+        }
+    }
+
+    // Offsets count from the word after the opcode, the offset itself
+    fn patch_to(&mut self, at: usize, target: usize) {
+        self.image.code[at] = (target as i64 - at as i64) as u64;
+    }
+
+    fn load_int(&mut self, ty: ValueType, n: i128, dst: Reg) {
+        match ty {
+            ValueType::F32 | ValueType::F64 => self.load_float(ty, n as f64, dst),
+            ValueType::I64 | ValueType::Ptr => self.emit(Opcode::LoadI64, &[dst as u64, n as u64]),
+            // Kept sign extended like the VM's own I32 results
+            ValueType::I32 => self.emit(Opcode::LoadI32, &[dst as u64, n as i32 as u64]),
+        }
+    }
+
+    fn load_float(&mut self, ty: ValueType, x: f64, dst: Reg) {
+        if ty == ValueType::F32 {
+            self.emit(Opcode::LoadF32, &[dst as u64, (x as f32).to_bits() as u64]);
+        } else {
+            self.emit(Opcode::LoadF64, &[dst as u64, x.to_bits()]);
+        }
+    }
+
+    fn load_value(&mut self, v: &Value, ty: ValueType, dst: Reg) {
+        match v {
+            Value::Int(n, _) | Value::Variant(_, _, n) => self.load_int(ty, *n, dst),
+            Value::Float(x, _) => self.load_float(ty, *x, dst),
+            Value::Bool(b) => self.load_int(ty, *b as i128, dst),
+            Value::Char(c) => self.load_int(ty, *c as i128, dst),
+            // Strings never reach here, they aren't scalars
+            Value::Str(_) => {}
+        }
+    }
+
+    // 8 and 16 bit integers live in I32 registers, results that may have
+    // left their range are wrapped back into it
+    fn wrap(&mut self, p: Primitive, dst: Reg, src: Reg) -> bool {
+        let op = match p {
+            Primitive::I8 => Opcode::TruncI8,
+            Primitive::U8 => Opcode::TruncU8,
+            Primitive::I16 => Opcode::TruncI16,
+            Primitive::U16 => Opcode::TruncU16,
+            _ => return false,
+        };
+        self.op(op, &[dst, src]);
+        true
+    }
+}
+
+// Opcode for a value of type `p`, from one per register class: signed 32
+// bit, unsigned 32 bit, signed 64, unsigned 64, f32 and f64
+fn pick(p: Primitive, ops: [Opcode; 6]) -> Opcode {
+    ops[match p {
+        Primitive::F32 => 4,
+        Primitive::F64 => 5,
+        Primitive::I64 => 2,
+        Primitive::U64 => 3,
+        Primitive::I8 | Primitive::I16 | Primitive::I32 => 0,
+        _ => 1,
+    }]
+}
+
+// Opcode reading a value of type `p` into its register
+fn read(p: Primitive) -> Opcode {
+    match p {
+        Primitive::I8 => Opcode::ReadI8,
+        Primitive::U8 | Primitive::Bool => Opcode::ReadU8,
+        Primitive::I16 => Opcode::ReadI16,
+        Primitive::U16 => Opcode::ReadU16,
+        Primitive::F32 => Opcode::ReadF32,
+        Primitive::F64 => Opcode::ReadF64,
+        Primitive::I64 | Primitive::U64 => Opcode::ReadI64,
+        // 32 bit values are kept sign extended either way
+        Primitive::I32 | Primitive::U32 | Primitive::Char => Opcode::ReadI32,
+    }
+}
+
+// ---------------------------------------
+// Instructions
+// ---------------------------------------
+impl Gen {
+    // `next` is the block laid out after this one
+    fn inst(&mut self, func: &Function, inst: &Inst, next: BlockId) {
+        match inst {
+            Inst::Const { dst, value } => {
+                let ty = func.regs[dst.0 as usize];
+                self.load_value(value, ty, self.reg(*dst));
+            }
+            Inst::Copy { dst, src } => self.mov(self.reg(*dst), self.reg(*src)),
+            Inst::Unary { op, ty, dst, src } => {
+                self.unary(*op, *ty, self.reg(*dst), self.reg(*src))
+            }
+            Inst::Binary {
+                op,
+                ty,
+                dst,
+                lhs,
+                rhs,
+            } => self.binary(*op, *ty, self.reg(*dst), self.reg(*lhs), self.reg(*rhs)),
+            Inst::Convert { from, to, dst, src } => {
+                self.convert(*from, *to, self.reg(*dst), self.reg(*src))
+            }
+            Inst::Frame { dst, offset } => {
+                self.emit(Opcode::FrameAddr, &[self.reg(*dst) as u64, *offset])
+            }
+            Inst::Offset { dst, base, offset } => self.emit(
+                Opcode::Offset,
+                &[self.reg(*dst) as u64, self.reg(*base) as u64, *offset],
+            ),
+            Inst::Index {
+                dst,
+                base,
+                index,
+                size,
+                len,
+            } => self.emit(
+                Opcode::Index,
+                &[
+                    self.reg(*dst) as u64,
+                    self.reg(*base) as u64,
+                    self.reg(*index) as u64,
+                    *size,
+                    len.unwrap_or(UNCHECKED),
+                ],
+            ),
+            Inst::Load {
+                ty,
+                dst,
+                addr,
+                offset,
+            } => {
+                let op = match func.regs[dst.0 as usize] {
+                    ValueType::Ptr => Opcode::ReadPtr,
+                    _ => read(*ty),
+                };
+                let operands = [self.reg(*dst) as u64, self.reg(*addr) as u64, *offset];
+                self.emit(op, &operands);
+            }
+            Inst::Store {
+                ty,
+                addr,
+                offset,
+                src,
+            } => {
+                let op = match ty.size() {
+                    1 => Opcode::Write8,
+                    2 => Opcode::Write16,
+                    4 => Opcode::Write32,
+                    _ => Opcode::Write64,
+                };
+                let operands = [self.reg(*addr) as u64, self.reg(*src) as u64, *offset];
+                self.emit(op, &operands);
+            }
+            Inst::MemCopy { to, from, size } => self.emit(
+                Opcode::MemCopy,
+                &[self.reg(*to) as u64, self.reg(*from) as u64, *size],
+            ),
+            Inst::Call { func, dst, args } => {
+                let base = self.args(args);
+                let dst = dst.map_or(NO_RETURN, |d| self.reg(d) as u64);
+                self.emit(Opcode::Call, &[*func as u64, dst, base as u64]);
+            }
+            Inst::TailCall { func, args } => {
+                let base = self.args(args);
+                self.emit(Opcode::TailCall, &[*func as u64, base as u64]);
+            }
+            Inst::Jump(target) => {
+                if *target != next {
+                    self.jump(Opcode::Jmp, *target, None);
+                }
+            }
+            Inst::Branch {
+                cond,
+                then,
+                otherwise,
+            } => {
+                let c = self.reg(*cond);
+                if *then == next {
+                    self.jump(Opcode::Jmpif, *otherwise, Some(c));
+                } else if *otherwise == next {
+                    self.jump(Opcode::Jmpnif, *then, Some(c));
+                } else {
+                    self.jump(Opcode::Jmpif, *otherwise, Some(c));
+                    self.jump(Opcode::Jmp, *then, None);
+                }
+            }
+            Inst::Return(value) => {
+                if let Some(v) = value {
+                    self.mov(0, self.reg(*v));
+                }
+                self.op(Opcode::Ret, &[]);
+            }
+            Inst::Unreachable => self.op(Opcode::Halt, &[]),
+        }
+    }
+
+    fn unary(&mut self, op: UnaryOp, p: Primitive, dst: Reg, src: Reg) {
+        match op {
+            UnaryOp::Neg => {
+                use Opcode::*;
+                self.op(
+                    pick(p, [NegI32, NegI32, NegI64, NegI64, NegF32, NegF64]),
+                    &[dst, src],
+                );
+                self.wrap(p, dst, dst);
+            }
+            UnaryOp::Not => self.op(Opcode::Not, &[dst, src]),
+            UnaryOp::BitNot => {
+                let ones = self.scratch(0);
+                self.load_int(ValueType::of(p), -1, ones);
+                self.op(Opcode::Xor, &[dst, src, ones]);
+                self.wrap(p, dst, dst);
+            }
+            // Rejected while lowering
+            UnaryOp::Deref => unreachable!(),
+        }
+    }
+
+    // `p` is the type of the operands, comparisons give an I32 1 or 0
+    fn binary(&mut self, op: BinaryOp, p: Primitive, dst: Reg, a: Reg, b: Reg) {
+        use Opcode::*;
+        let lt = [LtI32, LtU32, LtI64, LtU64, LtF32, LtF64];
+        let le = [LeI32, LeU32, LeI64, LeU64, LeF32, LeF64];
+        let (code, a, b) = match op {
+            BinaryOp::Add => (
+                pick(p, [AddI32, AddI32, AddI64, AddI64, AddF32, AddF64]),
+                a,
+                b,
+            ),
+            BinaryOp::Sub => (
+                pick(p, [SubI32, SubI32, SubI64, SubI64, SubF32, SubF64]),
+                a,
+                b,
+            ),
+            BinaryOp::Mul => (
+                pick(p, [MulI32, MulI32, MulI64, MulI64, MulF32, MulF64]),
+                a,
+                b,
+            ),
+            BinaryOp::Div => (
+                pick(p, [DivI32, DivU32, DivI64, DivU64, DivF32, DivF64]),
+                a,
+                b,
+            ),
+            // The type checker only allows these on integers
+            BinaryOp::Mod => (
+                pick(p, [ModI32, ModU32, ModI64, ModU64, ModI64, ModI64]),
+                a,
+                b,
+            ),
+            BinaryOp::Shl => (
+                pick(p, [ShlI32, ShlI32, ShlI64, ShlI64, ShlI64, ShlI64]),
+                a,
+                b,
+            ),
+            BinaryOp::Shr => (
+                pick(p, [ShrI32, ShrU32, ShrI64, ShrU64, ShrI64, ShrI64]),
+                a,
+                b,
+            ),
+            BinaryOp::BitAnd => (And, a, b),
+            BinaryOp::BitOr => (Or, a, b),
+            BinaryOp::BitXor => (Xor, a, b),
+            BinaryOp::Eq | BinaryOp::NotEq => (pick(p, [Eq, Eq, Eq, Eq, EqF32, EqF64]), a, b),
+            BinaryOp::Lt => (pick(p, lt), a, b),
+            BinaryOp::Gt => (pick(p, lt), b, a),
+            BinaryOp::LtEq => (pick(p, le), a, b),
+            BinaryOp::GtEq => (pick(p, le), b, a),
+            // Lowered to branches
+            BinaryOp::And | BinaryOp::Or => unreachable!(),
+        };
+        self.op(code, &[dst, a, b]);
+        match op {
+            BinaryOp::NotEq => self.op(Not, &[dst, dst]),
+            BinaryOp::Add
+            | BinaryOp::Sub
+            | BinaryOp::Mul
+            | BinaryOp::Div
+            | BinaryOp::Mod
+            | BinaryOp::Shl => {
+                self.wrap(p, dst, dst);
+            }
+            _ => {}
+        }
+    }
+
+    // Casts and implicit conversions between the register representations
+    // of two scalar types
+    fn convert(&mut self, f: Primitive, t: Primitive, dst: Reg, src: Reg) {
+        use Opcode::*;
+        use Primitive::{F32, F64};
+        let wide = |p: Primitive| p.size() == 8;
+        let float = |p: Primitive| matches!(p, F32 | F64);
+        match (float(f), float(t)) {
+            (false, false) if wide(t) => match (wide(f), f.is_signed()) {
+                (true, _) => self.mov(dst, src),
+                (false, true) => self.op(I32ToI64, &[dst, src]),
+                (false, false) => self.op(U32ToI64, &[dst, src]),
+            },
+            (false, false) => {
+                let src = if wide(f) {
+                    self.op(I64ToI32, &[dst, src]);
+                    dst
+                } else {
+                    src
+                };
+                if !self.wrap(t, dst, src) {
+                    self.mov(dst, src);
+                }
+            }
+            (false, true) => {
+                let ops = match (wide(f), f.is_signed()) {
+                    (false, true) => [I32ToF32, I32ToF64],
+                    (true, false) => [U64ToF32, U64ToF64],
+                    _ => [I64ToF32, I64ToF64],
+                };
+                // Unsigned 32 bit values go through I64 to stay positive
+                let src = if !wide(f) && !f.is_signed() {
+                    self.op(U32ToI64, &[dst, src]);
+                    dst
+                } else {
+                    src
+                };
+                self.op(ops[(t == F64) as usize], &[dst, src]);
+            }
+            (true, false) => {
+                let (to_i32, to_i64, to_u64) = match f {
+                    F32 => (F32ToI32, F32ToI64, F32ToU64),
+                    _ => (F64ToI32, F64ToI64, F64ToU64),
+                };
+                match (wide(t), t.is_signed()) {
+                    (true, true) => self.op(to_i64, &[dst, src]),
+                    (true, false) => self.op(to_u64, &[dst, src]),
+                    (false, true) => {
+                        self.op(to_i32, &[dst, src]);
+                        self.wrap(t, dst, dst);
+                    }
+                    (false, false) => {
+                        self.op(to_i64, &[dst, src]);
+                        self.op(I64ToI32, &[dst, dst]);
+                        self.wrap(t, dst, dst);
+                    }
+                }
+            }
+            (true, true) if f == t => self.mov(dst, src),
+            (true, true) if f == F32 => self.op(F32ToF64, &[dst, src]),
+            (true, true) => self.op(F64ToF32, &[dst, src]),
+        }
+    }
+
+    // `Call` and `TailCall` copy the arguments from consecutive registers
+    // into the callee's first locals, they're moved into scratch registers
+    // unless they already are
+    fn args(&mut self, args: &[VReg]) -> Reg {
+        let args: Vec<Reg> = args.iter().map(|a| self.reg(*a)).collect();
+        let consecutive = args.windows(2).all(|w| w[1] == w[0] + 1);
+        match args.first() {
+            // Nothing is copied, so any register will do
+            None => 0,
+            Some(&first) if consecutive => first,
+            Some(_) => {
+                let base = self.scratch(0);
+                for (i, &arg) in args.iter().enumerate() {
+                    let r = self.scratch(i as Reg);
+                    self.mov(r, arg);
+                }
+                base
+            }
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use super::bytecode::ValueType;
+
+pub const MAGIC: u32 = 0x44564D31; // DVM1
+pub const VERSION: u32 = 3; // Must match the VM, see docs/bytecode.md
+// Return type byte of a function that returns nothing
+pub const NO_RETURN_TYPE: u8 = 0xFF;
+
+#[derive(Debug, Clone)]
+pub struct FunctionEntry {
+    pub name: String, // Qualified path, not written to the file
+    pub entry: u64,
+    pub reg_count: u16,
+    pub memory: u64, // Bytes of memory for each call
+    pub arg_types: Vec<ValueType>,
+    pub ret_type: Option<ValueType>,
+    pub flags: u32,
+}
+
+// A compiled program, laid out as described in docs/bytecode.md
+#[derive(Debug, Clone, Default)]
+pub struct Image {
+    pub functions: Vec<FunctionEntry>,
+    pub entry: u32,
+    pub code: Vec<u64>,
+}
+
+impl Image {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![];
+        out.extend_from_slice(&MAGIC.to_le_bytes());
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&(self.functions.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.entry.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes()); // Const table count
+        out.extend_from_slice(&(self.code.len() as u64).to_le_bytes());
+
+        for f in self.functions.iter() {
+            out.extend_from_slice(&f.entry.to_le_bytes());
+            out.extend_from_slice(&f.reg_count.to_le_bytes());
+            out.extend_from_slice(&f.memory.to_le_bytes());
+            out.extend_from_slice(&(f.arg_types.len() as u16).to_le_bytes());
+            out.extend(f.arg_types.iter().map(|t| *t as u8));
+            out.push(f.ret_type.map_or(NO_RETURN_TYPE, |t| t as u8));
+            out.extend_from_slice(&f.flags.to_le_bytes());
+        }
+
+        for word in self.code.iter() {
+            out.extend_from_slice(&word.to_le_bytes());
+        }
+        out
+    }
+}
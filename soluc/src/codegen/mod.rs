@@ -0,0 +1,12 @@
+mod bytecode;
+mod emit;
+mod image;
+mod regalloc;
+
+#[cfg(test)]
+mod tests;
+
+pub use bytecode::*;
+pub use emit::*;
+pub use image::*;
+pub use regalloc::*;
@@ -0,0 +1,74 @@
+use crate::ir::{Function, Inst, live_out, transfer};
+use std::collections::HashSet;
+
+// Frame local each virtual register is kept in
+pub struct Allocation {
+    pub regs: Vec<u32>,
+    pub count: u32, // Locals used
+}
+
+// Graph coloring. Two virtual registers interfere when one is written while
+// the other is live, anything else may share a local. Parameters are fixed
+// to the locals `Call` copies the arguments into, and registers are colored
+// in order, lowest free local first. A local a register is copied to or
+// from, or local 0 for a returned value, is preferred when it's free so the
+// `Mov` goes away.
+pub fn allocate(func: &Function) -> Allocation {
+    let n = func.regs.len();
+    let mut adj: Vec<HashSet<usize>> = vec![HashSet::new(); n];
+    let mut hints: Vec<Vec<usize>> = vec![vec![]; n];
+    let mut returned = vec![false; n];
+    let live_out = live_out(func);
+
+    for (b, block) in func.blocks.iter().enumerate() {
+        let mut live = live_out[b].clone();
+        for inst in block.insts.iter().rev() {
+            // A copy's source can share its destination's local
+            let copied = match inst {
+                Inst::Copy { dst, src } => {
+                    hints[dst.0 as usize].push(src.0 as usize);
+                    hints[src.0 as usize].push(dst.0 as usize);
+                    Some(*src)
+                }
+                Inst::Return(Some(v)) => {
+                    returned[v.0 as usize] = true;
+                    None
+                }
+                _ => None,
+            };
+            if let Some(d) = inst.def() {
+                let (d, copied) = (d.0 as usize, copied.map(|c| c.0 as usize));
+                for l in live.iter() {
+                    if l != d && Some(l) != copied {
+                        adj[d].insert(l);
+                        adj[l].insert(d);
+                    }
+                }
+            }
+            transfer(inst, &mut live);
+        }
+    }
+    // Arguments are all written on entry
+    for (a, edges) in adj.iter_mut().enumerate().take(func.params) {
+        edges.extend((0..func.params).filter(|&b| b != a));
+    }
+
+    let mut color: Vec<Option<u32>> = (0..n)
+        .map(|r| (r < func.params).then_some(r as u32))
+        .collect();
+    for v in func.params..n {
+        let taken: HashSet<u32> = adj[v].iter().filter_map(|&r| color[r]).collect();
+        let preferred = hints[v]
+            .iter()
+            .filter_map(|&r| color[r])
+            .chain(returned[v].then_some(0))
+            .find(|c| !taken.contains(c));
+        color[v] = Some(preferred.unwrap_or_else(|| (0..).find(|c| !taken.contains(c)).unwrap()));
+    }
+
+    let regs: Vec<u32> = color.into_iter().map(|c| c.unwrap_or(0)).collect();
+    Allocation {
+        count: regs.iter().map(|r| r + 1).max().unwrap_or(0),
+        regs,
+    }
+}
@@ -0,0 +1,152 @@
+use super::*;
+use crate::ir::{Block, BlockId, Function, Inst, Program, VReg};
+use crate::lexer::Span;
+use crate::parser::{BinaryOp, Primitive};
+use crate::sema::Value;
+
+fn r(n: u32) -> VReg {
+    VReg(n)
+}
+
+fn konst(dst: u32) -> Inst {
+    Inst::Const {
+        dst: r(dst),
+        value: Value::Int(1, Primitive::I32),
+    }
+}
+
+fn add(dst: u32, lhs: u32, rhs: u32) -> Inst {
+    Inst::Binary {
+        op: BinaryOp::Add,
+        ty: Primitive::I32,
+        dst: r(dst),
+        lhs: r(lhs),
+        rhs: r(rhs),
+    }
+}
+
+fn ret(v: u32) -> Inst {
+    Inst::Return(Some(r(v)))
+}
+
+// `f` with `params` parameters and `regs` i32 registers
+fn func(params: usize, regs: usize, blocks: Vec<Vec<Inst>>) -> Function {
+    Function {
+        name: "f".to_string(),
+        params,
+        ret: Some(ValueType::I32),
+        regs: vec![ValueType::I32; regs],
+        memory: 0,
+        blocks: blocks.into_iter().map(|insts| Block { insts }).collect(),
+        span: Span {
+            start: 0,
+            end: 0,
+            line: 1,
+            col: 0,
+        },
+    }
+}
+
+// `n` constants that are all live at once, then added up
+fn pressure(n: u32) -> Function {
+    let mut insts: Vec<Inst> = (0..n).map(konst).collect();
+    let mut sum = 0;
+    for i in 1..n {
+        insts.push(add(n + i - 1, sum, i));
+        sum = n + i - 1;
+    }
+    insts.push(ret(sum));
+    func(0, (2 * n - 1) as usize, vec![insts])
+}
+
+#[test]
+fn live_registers_interfere() {
+    let a = allocate(&func(
+        0,
+        4,
+        vec![vec![konst(0), konst(1), add(2, 0, 1), add(3, 2, 0), ret(3)]],
+    ));
+    assert_ne!(a.regs[0], a.regs[1]);
+    // %0 is still read after %2 is written
+    assert_ne!(a.regs[0], a.regs[2]);
+    // %1 is dead by then, and the returned value prefers local 0
+    assert_eq!(a.regs[3], 0);
+    assert_eq!(a.count, 2);
+}
+
+#[test]
+fn disjoint_lifetimes_share() {
+    let a = allocate(&func(
+        0,
+        4,
+        vec![vec![
+            konst(0),
+            add(1, 0, 0),
+            add(2, 1, 1),
+            add(3, 2, 2),
+            ret(3),
+        ]],
+    ));
+    assert_eq!(a.regs, [0, 0, 0, 0]);
+    assert_eq!(a.count, 1);
+}
+
+#[test]
+fn params_and_copies() {
+    let a = allocate(&func(
+        2,
+        4,
+        vec![vec![
+            Inst::Copy {
+                dst: r(2),
+                src: r(1),
+            },
+            add(3, 0, 2),
+            ret(3),
+        ]],
+    ));
+    // Parameters stay where `Call` puts them, the copy goes away
+    assert_eq!(&a.regs[..3], [0, 1, 1]);
+    assert_eq!(a.count, 2);
+}
+
+#[test]
+fn live_across_blocks() {
+    // %0 is live through both arms, so neither %2 nor %3 can take its local
+    let a = allocate(&func(
+        1,
+        5,
+        vec![
+            vec![
+                konst(1),
+                Inst::Branch {
+                    cond: r(0),
+                    then: BlockId(1),
+                    otherwise: BlockId(2),
+                },
+            ],
+            vec![add(2, 1, 1), add(4, 2, 0), Inst::Jump(BlockId(3))],
+            vec![add(3, 1, 1), add(4, 3, 0), Inst::Jump(BlockId(3))],
+            vec![ret(4)],
+        ],
+    ));
+    assert_ne!(a.regs[2], a.regs[0]);
+    assert_ne!(a.regs[3], a.regs[0]);
+    assert_ne!(a.regs[1], a.regs[0]);
+}
+
+#[test]
+fn register_pressure() {
+    let program = |f| Program {
+        functions: vec![f],
+        entry: 0,
+    };
+    let a = allocate(&pressure(FRAME_LOCALS_SIZE as u32));
+    assert_eq!(a.count as usize, FRAME_LOCALS_SIZE);
+    let (_, diags) = generate(&program(pressure(FRAME_LOCALS_SIZE as u32)));
+    assert!(diags.is_empty(), "{:?}", diags);
+
+    let (_, diags) = generate(&program(pressure(FRAME_LOCALS_SIZE as u32 + 1)));
+    let diags: Vec<(&str, &str)> = diags.iter().map(|d| (d.code, d.msg.as_str())).collect();
+    assert_eq!(diags, [("E0903", "`f` needs 301 registers")]);
+}
@@ -0,0 +1,75 @@
+// Diagnostic codes. Codes are stable once released, new ones get appended
+// to their pass' range.
+//
+// E0001-E0099 lexer
+// E0100-E0199 parser
+// E0200-E0299 name resolution
+// E0300-E0399 type checking
+// E0400-E0499 mutability
+// E0500-E0599 control flow
+// E0600-E0699 definite assignment
+// E0700-E0799 constant evaluation
+// E0800-E0899 layout
+// E0900-E0999 code generation
+//
+// Warnings use the same ranges with a W prefix and can be silenced with
+// --allow=<code>.
+
+pub const LEX_ERROR: &str = "E0001";
+pub const UNTERMINATED_STRING: &str = "E0002";
+pub const INVALID_CHARACTER: &str = "E0003";
+pub const UNBALANCED_DELIMITER: &str = "E0004";
+
+pub const SYNTAX_ERROR: &str = "E0100";
+
+pub const UNDEFINED_NAME: &str = "E0200";
+pub const DUPLICATE_DEFINITION: &str = "E0201";
+pub const USE_BEFORE_DECLARATION: &str = "E0202";
+pub const UNDEFINED_TYPE: &str = "E0203";
+pub const NOT_A_VALUE: &str = "E0204";
+pub const UNKNOWN_RECEIVER: &str = "E0205";
+
+pub const MISMATCHED_TYPES: &str = "E0300";
+pub const INVALID_OPERAND: &str = "E0301";
+pub const WRONG_ARG_COUNT: &str = "E0302";
+pub const NOT_CALLABLE: &str = "E0303";
+pub const UNKNOWN_FIELD: &str = "E0304";
+pub const MISSING_FIELDS: &str = "E0305";
+pub const INVALID_CAST: &str = "E0306";
+pub const NOT_INDEXABLE: &str = "E0307";
+pub const MISSING_RETURN_VALUE: &str = "E0308";
+pub const LITERAL_OUT_OF_RANGE: &str = "E0309";
+pub const INVALID_ENUM_TYPE: &str = "E0310";
+pub const NON_EXHAUSTIVE_SWITCH: &str = "E0311";
+pub const UNKNOWN_METHOD: &str = "E0312";
+pub const RECEIVER_MISMATCH: &str = "E0313";
+pub const LOSSY_CONVERSION: &str = "W0300";
+
+pub const ASSIGN_TO_IMMUTABLE: &str = "E0400";
+pub const ASSIGN_THROUGH_REF: &str = "E0401";
+pub const MUTATING_CALL_ON_IMMUTABLE: &str = "E0402";
+pub const NOT_ASSIGNABLE: &str = "E0403";
+
+pub const MISSING_RETURN: &str = "E0500";
+pub const BREAK_OUTSIDE_LOOP: &str = "E0501";
+pub const CONTINUE_OUTSIDE_LOOP: &str = "E0502";
+pub const UNREACHABLE_CODE: &str = "W0500";
+
+pub const USE_OF_UNINITIALIZED: &str = "E0600";
+
+pub const NOT_CONSTANT: &str = "E0700";
+pub const CONST_OVERFLOW: &str = "E0701";
+pub const DIVISION_BY_ZERO: &str = "E0702";
+pub const CONST_CYCLE: &str = "E0703";
+pub const INVALID_ARRAY_LENGTH: &str = "E0704";
+pub const DUPLICATE_CASE: &str = "E0705";
+pub const DUPLICATE_DISCRIMINANT: &str = "E0706";
+pub const INVALID_DISCRIMINANT: &str = "E0707";
+
+pub const RECURSIVE_STRUCT: &str = "E0800";
+pub const TYPE_TOO_LARGE: &str = "E0801";
+
+pub const MISSING_MAIN: &str = "E0900";
+pub const INVALID_MAIN: &str = "E0901";
+pub const UNSUPPORTED_BY_BACKEND: &str = "E0902";
+pub const TOO_MANY_REGISTERS: &str = "E0903";
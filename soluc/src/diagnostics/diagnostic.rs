@@ -0,0 +1,112 @@
+use crate::lexer::{LexerError, Span};
+use crate::parser::ParserError;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Note,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Note => write!(f, "note"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+// Extra span pointed at by a diagnostic, e.g. "first defined here"
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub msg: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub msg: String,
+    pub span: Span,
+    pub label: Option<String>, // Text next to the primary carets
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &'static str, span: Span, msg: impl Into<String>) -> Self {
+        Self {
+            severity,
+            code,
+            msg: msg.into(),
+            span,
+            label: None,
+            labels: vec![],
+            notes: vec![],
+            help: None,
+        }
+    }
+
+    pub fn error(code: &'static str, span: Span, msg: impl Into<String>) -> Self {
+        Self::new(Severity::Error, code, span, msg)
+    }
+
+    pub fn warning(code: &'static str, span: Span, msg: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, code, span, msg)
+    }
+
+    pub fn note(code: &'static str, span: Span, msg: impl Into<String>) -> Self {
+        Self::new(Severity::Note, code, span, msg)
+    }
+
+    pub fn with_label(mut self, msg: impl Into<String>) -> Self {
+        self.label = Some(msg.into());
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, msg: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            msg: msg.into(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, msg: impl Into<String>) -> Self {
+        self.notes.push(msg.into());
+        self
+    }
+
+    pub fn with_help(mut self, msg: impl Into<String>) -> Self {
+        self.help = Some(msg.into());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl From<&LexerError> for Diagnostic {
+    fn from(e: &LexerError) -> Self {
+        let d = Diagnostic::error(e.code, e.span, e.msg.clone());
+        match e.code {
+            super::UNTERMINATED_STRING => d
+                .with_label("string starts here")
+                .with_help("add a closing `\"` before the end of the line"),
+            super::UNBALANCED_DELIMITER => d.with_label("no matching opening delimiter"),
+            _ => d,
+        }
+    }
+}
+
+impl From<&ParserError> for Diagnostic {
+    fn from(e: &ParserError) -> Self {
+        Diagnostic::error(super::SYNTAX_ERROR, e.span, e.msg.clone())
+    }
+}
@@ -0,0 +1,73 @@
+use super::{Diagnostic, Renderer, to_json};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    Human,
+    Json,
+}
+
+impl ErrorFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "human" => Some(ErrorFormat::Human),
+            "json" => Some(ErrorFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+// Writes diagnostics from every pass to stderr in the chosen format
+pub struct Emitter<'a> {
+    file: &'a str,
+    src: &'a str,
+    format: ErrorFormat,
+    renderer: Renderer<'a>,
+    // Warning codes not to report, "warnings" silences all of them
+    allow: Vec<String>,
+}
+
+impl<'a> Emitter<'a> {
+    pub fn new(file: &'a str, src: &'a str, format: ErrorFormat, color: bool) -> Self {
+        Self {
+            file,
+            src,
+            format,
+            renderer: Renderer::new(file, src, color),
+            allow: vec![],
+        }
+    }
+
+    pub fn allow(mut self, codes: Vec<String>) -> Self {
+        self.allow = codes;
+        self
+    }
+
+    // Errors can't be allowed
+    fn allowed(&self, d: &Diagnostic) -> bool {
+        !d.is_error() && self.allow.iter().any(|a| a == "warnings" || a == d.code)
+    }
+
+    pub fn emit(&self, diags: &[Diagnostic]) {
+        let diags: Vec<_> = diags.iter().filter(|d| !self.allowed(d)).collect();
+        match self.format {
+            ErrorFormat::Human => {
+                for d in diags.iter() {
+                    eprintln!("{}", self.renderer.render(d));
+                }
+                let errors = diags.iter().filter(|d| d.is_error()).count();
+                let warnings = diags.len() - errors;
+                if warnings > 0 {
+                    eprintln!("{} warning(s) emitted", warnings);
+                }
+                if errors > 0 {
+                    eprintln!("{} error(s) emitted", errors);
+                }
+            }
+            ErrorFormat::Json => {
+                for d in diags.iter() {
+                    eprintln!("{}", to_json(self.file, self.src, d));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use super::Diagnostic;
+use crate::lexer::Span;
+use std::fmt::Write;
+
+// One JSON object per diagnostic, on a single line. Keys are always emitted
+// in the same order so the output can be snapshot tested:
+//
+// {"file":"main.sl","severity":"error","code":"E0002",
+//  "message":"Unterminated string literal",
+//  "span":{"start":8,"end":12,"line":5,"column":9,"end_line":5,"end_column":13},
+//  "label":"string starts here","labels":[],"notes":[],"help":"..."}
+//
+// Lines and columns are 1-based, columns count chars, `end` is exclusive.
+pub fn to_json(file: &str, src: &str, d: &Diagnostic) -> String {
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "{{\"file\":{},\"severity\":{},\"code\":{},\"message\":{},\"span\":{},\"label\":{}",
+        string(file),
+        string(&d.severity.to_string()),
+        string(d.code),
+        string(&d.msg),
+        span(src, d.span),
+        d.label.as_deref().map_or("null".to_string(), string)
+    );
+    out.push_str(",\"labels\":[");
+    for (i, l) in d.labels.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let _ = write!(
+            out,
+            "{{\"span\":{},\"message\":{}}}",
+            span(src, l.span),
+            string(&l.msg)
+        );
+    }
+    out.push_str("],\"notes\":[");
+    for (i, n) in d.notes.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str(&string(n));
+    }
+    let _ = write!(
+        out,
+        "],\"help\":{}}}",
+        d.help.as_deref().map_or("null".to_string(), string)
+    );
+    out
+}
+
+fn span(src: &str, s: Span) -> String {
+    let start = s.start.min(src.len());
+    let end = s.end.clamp(start, src.len());
+    let (line, col) = line_col(src, start);
+    let (end_line, end_col) = line_col(src, end);
+    format!(
+        "{{\"start\":{},\"end\":{},\"line\":{},\"column\":{},\"end_line\":{},\"end_column\":{}}}",
+        s.start, s.end, line, col, end_line, end_col
+    )
+}
+
+// 1-based line and char column of a byte offset
+fn line_col(src: &str, offset: usize) -> (usize, usize) {
+    let before = &src[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        src[line_start..offset].chars().count() + 1,
+    )
+}
+
+fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,14 @@
+mod codes;
+mod diagnostic;
+mod emitter;
+mod json;
+mod render;
+
+#[cfg(test)]
+mod tests;
+
+pub use codes::*;
+pub use diagnostic::*;
+pub use emitter::*;
+pub use json::*;
+pub use render::*;
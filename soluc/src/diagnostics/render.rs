@@ -0,0 +1,198 @@
+use super::{Diagnostic, Severity};
+use crate::lexer::Span;
+use std::fmt::Write;
+use std::io::IsTerminal;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const CYAN: &str = "\x1b[1;36m";
+const BLUE: &str = "\x1b[1;34m";
+
+// Tabs are expanded so carets line up under the snippet
+const TAB: &str = "    ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "auto" => Some(ColorMode::Auto),
+            "always" => Some(ColorMode::Always),
+            "never" => Some(ColorMode::Never),
+            _ => None,
+        }
+    }
+
+    // Auto only colors when stderr is a terminal and NO_COLOR isn't set
+    pub fn enabled(&self) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => {
+                std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal()
+            }
+        }
+    }
+}
+
+// Renders diagnostics against one source file:
+//
+// error[E0002]: Unterminated string literal
+//  --> main.sl:5:9
+//   |
+// 5 | let s = "abc
+//   |         ^^^^ string starts here
+//   = help: add a closing `"` before the end of the line
+pub struct Renderer<'a> {
+    file: &'a str,
+    src: &'a str,
+    color: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(file: &'a str, src: &'a str, color: bool) -> Self {
+        Self { file, src, color }
+    }
+
+    pub fn render(&self, d: &Diagnostic) -> String {
+        let mut out = String::new();
+        let sev = match d.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+            Severity::Note => CYAN,
+        };
+
+        // Gutter wide enough for the largest line number shown
+        let width = std::iter::once(&d.span)
+            .chain(d.labels.iter().map(|l| &l.span))
+            .map(|s| s.line.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let pad = " ".repeat(width);
+
+        let _ = writeln!(
+            out,
+            "{}{}[{}]{}{}: {}{}",
+            self.paint(sev),
+            d.severity,
+            d.code,
+            self.paint(RESET),
+            self.paint(BOLD),
+            d.msg,
+            self.paint(RESET)
+        );
+        let (line, col) = self.position(d.span);
+        let _ = writeln!(
+            out,
+            "{}{}-->{} {}:{}:{}",
+            pad,
+            self.paint(BLUE),
+            self.paint(RESET),
+            self.file,
+            line,
+            col
+        );
+        self.gutter(&mut out, &pad);
+        self.snippet(&mut out, &pad, d.span, '^', sev, d.label.as_deref());
+        for l in d.labels.iter() {
+            self.gutter(&mut out, &pad);
+            self.snippet(&mut out, &pad, l.span, '-', BLUE, Some(&l.msg));
+        }
+        for n in d.notes.iter() {
+            let _ = writeln!(
+                out,
+                "{}{} ={} note: {}",
+                pad,
+                self.paint(BLUE),
+                self.paint(RESET),
+                n
+            );
+        }
+        if let Some(h) = &d.help {
+            let _ = writeln!(
+                out,
+                "{}{} ={} help: {}",
+                pad,
+                self.paint(BLUE),
+                self.paint(RESET),
+                h
+            );
+        }
+        out
+    }
+
+    // 1-based line and column (in chars) of the start of a span
+    pub fn position(&self, span: Span) -> (usize, usize) {
+        let start = span.start.min(self.src.len());
+        let line_start = self.src[..start].rfind('\n').map_or(0, |i| i + 1);
+        (span.line, self.src[line_start..start].chars().count() + 1)
+    }
+
+    fn snippet(
+        &self,
+        out: &mut String,
+        pad: &str,
+        span: Span,
+        mark: char,
+        color: &'static str,
+        label: Option<&str>,
+    ) {
+        let start = span.start.min(self.src.len());
+        let line_start = self.src[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = self.src[start..]
+            .find('\n')
+            .map_or(self.src.len(), |i| start + i);
+        let text = &self.src[line_start..line_end];
+
+        // Spans running past the line are underlined up to its end
+        let end = span.end.clamp(start, line_end);
+        let offset = width(&self.src[line_start..start]);
+        let len = width(&self.src[start..end]).max(1);
+
+        let _ = writeln!(
+            out,
+            "{}{:>w$} |{} {}",
+            self.paint(BLUE),
+            span.line,
+            self.paint(RESET),
+            text.replace('\t', TAB),
+            w = pad.len()
+        );
+        let _ = write!(
+            out,
+            "{}{} |{} {}{}{}",
+            pad,
+            self.paint(BLUE),
+            self.paint(RESET),
+            " ".repeat(offset),
+            self.paint(color),
+            mark.to_string().repeat(len)
+        );
+        if let Some(l) = label {
+            let _ = write!(out, " {}", l);
+        }
+        let _ = writeln!(out, "{}", self.paint(RESET));
+    }
+
+    fn gutter(&self, out: &mut String, pad: &str) {
+        let _ = writeln!(out, "{}{} |{}", pad, self.paint(BLUE), self.paint(RESET));
+    }
+
+    fn paint(&self, code: &'static str) -> &'static str {
+        if self.color { code } else { "" }
+    }
+}
+
+// Display width of a snippet of a single line
+fn width(s: &str) -> usize {
+    s.chars()
+        .map(|c| if c == '\t' { TAB.len() } else { 1 })
+        .sum()
+}
@@ -0,0 +1,123 @@
+use super::*;
+use crate::lexer::{Lexer, Span};
+use crate::parser::Parser;
+use crate::sema;
+
+// Diagnostics from the first pass that reports any, like the driver
+fn diagnose(src: &str) -> Vec<Diagnostic> {
+    let tokens = match Lexer::new(src).parse() {
+        Ok(t) => t,
+        Err(errors) => return errors.iter().map(Diagnostic::from).collect(),
+    };
+    let ast = match Parser::new(tokens).parse() {
+        Ok(a) => a,
+        Err(errors) => return errors.iter().map(Diagnostic::from).collect(),
+    };
+    let (symbols, diags) = sema::resolve(&ast);
+    if !diags.is_empty() {
+        return diags;
+    }
+    let (types, diags) = sema::check(&ast, &symbols);
+    if !diags.is_empty() {
+        return diags;
+    }
+    let diags = sema::check_mutability(&ast, &symbols, &types);
+    if !diags.is_empty() {
+        return diags;
+    }
+    let diags = sema::check_flow(&ast, &types);
+    if !diags.is_empty() {
+        return diags;
+    }
+    sema::check_init(&ast, &types)
+}
+
+fn json(src: &str) -> String {
+    let diags = diagnose(src);
+    assert_eq!(diags.len(), 1, "{:?}", diags);
+    to_json("main.sl", src, &diags[0])
+}
+
+#[test]
+fn lexer_error() {
+    assert_eq!(
+        json("main(): i32 do\n\ts: i32 = \"abc\n\treturn 0\nend\n"),
+        concat!(
+            r#"{"file":"main.sl","severity":"error","code":"E0002","#,
+            r#""message":"Unterminated string literal","#,
+            r#""span":{"start":25,"end":29,"line":2,"column":11,"end_line":2,"end_column":15},"#,
+            r#""label":"string starts here","labels":[],"notes":[],"#,
+            r#""help":"add a closing `\"` before the end of the line"}"#,
+        )
+    );
+}
+
+#[test]
+fn parser_error() {
+    assert_eq!(
+        json("main(): i32 do\n\treturn (1 +\nend\n"),
+        concat!(
+            r#"{"file":"main.sl","severity":"error","code":"E0100","#,
+            r#""message":"expected expression, got END","#,
+            r#""span":{"start":28,"end":31,"line":3,"column":1,"end_line":3,"end_column":4},"#,
+            r#""label":null,"labels":[],"notes":[],"help":null}"#,
+        )
+    );
+}
+
+#[test]
+fn semantic_error() {
+    let src = "struct P is\n\tx: i32\n\ty: i32\nend\nmain(): i32 do\n\tmut p: P\n\tp.x = 1\n\tq: P = p\n\treturn q.x\nend\n";
+    assert_eq!(
+        json(src),
+        concat!(
+            r#"{"file":"main.sl","severity":"error","code":"E0600","#,
+            r#""message":"`p` is used before it is assigned","#,
+            r#""span":{"start":74,"end":75,"line":8,"column":9,"end_line":8,"end_column":10},"#,
+            r#""label":"not assigned on every path to here","#,
+            r#""labels":[{"span":{"start":48,"end":56,"line":6,"column":2,"end_line":6,"end_column":10},"#,
+            r#""message":"declared here without a value"}],"#,
+            r#""notes":["fields not assigned yet: y"],"#,
+            r#""help":"assign it on every path before this, or give it an initial value"}"#,
+        )
+    );
+}
+
+#[test]
+fn warning() {
+    assert_eq!(
+        json("main(): i32 do\n\treturn 1\n\tx: i32 = 2\nend\n"),
+        concat!(
+            r#"{"file":"main.sl","severity":"warning","code":"W0500","#,
+            r#""message":"unreachable statement","#,
+            r#""span":{"start":26,"end":36,"line":3,"column":2,"end_line":3,"end_column":12},"#,
+            r#""label":"this is never run","#,
+            r#""labels":[{"span":{"start":16,"end":24,"line":2,"column":2,"end_line":2,"end_column":10},"#,
+            r#""message":"any code following this is unreachable"}],"notes":[],"help":null}"#,
+        )
+    );
+}
+
+// Columns count chars, not bytes, and strings are escaped
+#[test]
+fn escaping() {
+    let src = "é = \"a\\b\"\n";
+    let span = Span {
+        start: 5,
+        end: 11,
+        line: 1,
+        col: 4,
+    };
+    let d = Diagnostic::note("N0000", span, "quote \" backslash \\ tab \t bell \u{7}")
+        .with_label("line\nbreak\r")
+        .with_note("ünïcode stays as is");
+    assert_eq!(
+        to_json("dir\\main.sl", src, &d),
+        concat!(
+            r#"{"file":"dir\\main.sl","severity":"note","code":"N0000","#,
+            r#""message":"quote \" backslash \\ tab \t bell \u0007","#,
+            r#""span":{"start":5,"end":11,"line":1,"column":5,"end_line":2,"end_column":1},"#,
+            r#""label":"line\nbreak\r","labels":[],"notes":["ünïcode stays as is"],"help":null}"#,
+        )
+    );
+}
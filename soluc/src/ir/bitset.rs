@@ -0,0 +1,80 @@
+// A fixed size set of small integers, one bit each. Dataflow analyses keep
+// one per block, indexed by register or block number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    // An empty set that can hold 0..size
+    pub fn new(size: usize) -> Self {
+        Self {
+            words: vec![0; size.div_ceil(64)],
+        }
+    }
+
+    // Whether the set changed
+    pub fn insert(&mut self, i: usize) -> bool {
+        let (w, bit) = (i / 64, 1u64 << (i % 64));
+        let changed = self.words[w] & bit == 0;
+        self.words[w] |= bit;
+        changed
+    }
+
+    pub fn remove(&mut self, i: usize) -> bool {
+        let (w, bit) = (i / 64, 1u64 << (i % 64));
+        let changed = self.words[w] & bit != 0;
+        self.words[w] &= !bit;
+        changed
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    // Adds everything in `other`, true if anything was new
+    pub fn union_with(&mut self, other: &BitSet) -> bool {
+        let mut changed = false;
+        for (a, b) in self.words.iter_mut().zip(other.words.iter()) {
+            changed |= *b & !*a != 0;
+            *a |= *b;
+        }
+        changed
+    }
+
+    // Keeps only what's also in `other`, true if anything was dropped
+    pub fn intersect_with(&mut self, other: &BitSet) -> bool {
+        let mut changed = false;
+        for (a, b) in self.words.iter_mut().zip(other.words.iter()) {
+            changed |= *a & !*b != 0;
+            *a &= *b;
+        }
+        changed
+    }
+
+    // Replaces the contents with `other`'s without allocating
+    pub fn copy_from(&mut self, other: &BitSet) {
+        self.words.copy_from_slice(&other.words);
+    }
+
+    pub fn first(&self) -> Option<usize> {
+        self.words
+            .iter()
+            .position(|w| *w != 0)
+            .map(|w| w * 64 + self.words[w].trailing_zeros() as usize)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(w, &word)| {
+            let mut rest = word;
+            std::iter::from_fn(move || {
+                if rest == 0 {
+                    return None;
+                }
+                let bit = rest.trailing_zeros() as usize;
+                rest &= rest - 1;
+                Some(w * 64 + bit)
+            })
+        })
+    }
+}
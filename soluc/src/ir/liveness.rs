@@ -0,0 +1,66 @@
+use super::bitset::BitSet;
+use super::types::*;
+use super::worklist::Worklist;
+
+// Registers live at the end of each block: read on some path from there
+// before they're written again
+pub fn live_out(func: &Function) -> Vec<BitSet> {
+    analyze(func, false)
+}
+
+// Like live_out, but only reads by instructions that are needed count. An
+// instruction is needed when it has an effect or writes a live register, so
+// what a dead one reads, even around a loop, isn't kept alive by it.
+pub fn strongly_live_out(func: &Function) -> Vec<BitSet> {
+    analyze(func, true)
+}
+
+// Whether `inst` has to run given the registers live after it. Bounds
+// checks stay like calls do.
+pub fn needed(inst: &Inst, live: &BitSet) -> bool {
+    match inst.def() {
+        Some(d) => {
+            matches!(inst, Inst::Call { .. } | Inst::Index { len: Some(_), .. })
+                || live.contains(d.0 as usize)
+        }
+        None => true,
+    }
+}
+
+// Turns the registers live after `inst` into the ones live before it
+pub fn transfer(inst: &Inst, live: &mut BitSet) {
+    if let Some(d) = inst.def() {
+        live.remove(d.0 as usize);
+    }
+    for r in inst.uses() {
+        live.insert(r.0 as usize);
+    }
+}
+
+// Backwards over the blocks in postorder. A block's predecessors are only
+// visited again when what's live on entry to it grew.
+fn analyze(func: &Function, strong: bool) -> Vec<BitSet> {
+    let regs = func.regs.len();
+    let preds = func.preds();
+    let mut live_in = vec![BitSet::new(regs); func.blocks.len()];
+    let mut live_out = vec![BitSet::new(regs); func.blocks.len()];
+    let mut work = Worklist::backward(func);
+    let mut live = BitSet::new(regs);
+    while let Some(b) = work.pop() {
+        live.copy_from(&live_out[b.0]);
+        for inst in func.blocks[b.0].insts.iter().rev() {
+            if !strong || needed(inst, &live) {
+                transfer(inst, &mut live);
+            }
+        }
+        if !live_in[b.0].union_with(&live) {
+            continue;
+        }
+        for p in preds[b.0].iter() {
+            if live_out[p.0].union_with(&live) {
+                work.push(*p);
+            }
+        }
+    }
+    live_out
+}
@@ -0,0 +1,1113 @@
+use super::types::*;
+use crate::codegen::ValueType;
+use crate::diagnostics::{self, Diagnostic};
+use crate::lexer::Span;
+use crate::parser::{self, AssignOp, BinaryOp, Decl, Expr, ExprKind, Namespace, Primitive};
+use crate::parser::{Stmt, StmtKind, UnaryOp};
+use crate::sema::{
+    ConstEval, FnSig, GLOBAL, Layout, NsId, Res, Symbol, SymbolKind, SymbolTable, Ty, TypeInfo,
+    Value, WORD,
+};
+use std::collections::{HashMap, HashSet};
+
+// Lowers a checked program to IR. `main` is lowered first and every other
+// function the first time it's called, so only code reachable from `main`
+// has to be supported by the backend.
+pub fn lower(ast: &Namespace, table: &SymbolTable, info: &TypeInfo) -> (Program, Vec<Diagnostic>) {
+    let mut l = Lower {
+        table,
+        info,
+        eval: ConstEval::new(ast, table),
+        program: Program::default(),
+        diags: vec![],
+        reported: HashSet::new(),
+        fns: HashMap::new(),
+        order: vec![],
+        ns: GLOBAL,
+        scopes: vec![],
+        func: empty_function(ast.span),
+        cur: None,
+        layout: vec![],
+        loops: vec![],
+        sret: None,
+    };
+    l.collect(&ast.nodes, GLOBAL);
+
+    match l.fns.get("main") {
+        Some((f, ..)) if !f.params.is_empty() => l.diags.push(
+            Diagnostic::error(
+                diagnostics::INVALID_MAIN,
+                f.params[0].span,
+                "`main` can't take parameters",
+            )
+            .with_label("`main` is called without arguments"),
+        ),
+        Some(_) => {
+            l.program.entry = l.index("main");
+        }
+        None => l.diags.push(
+            Diagnostic::error(diagnostics::MISSING_MAIN, ast.span, "no `main` function")
+                .with_help("the program starts at `main(): i32 do ... end`"),
+        ),
+    }
+
+    let mut i = 0;
+    while i < l.order.len() {
+        let key = l.order[i].clone();
+        l.function(&key);
+        i += 1;
+    }
+    (l.program, l.diags)
+}
+
+fn empty_function(span: Span) -> Function {
+    Function {
+        name: String::new(),
+        params: 0,
+        ret: None,
+        regs: vec![],
+        memory: 0,
+        blocks: vec![],
+        span,
+    }
+}
+
+struct Lower<'a> {
+    table: &'a SymbolTable,
+    info: &'a TypeInfo,
+    eval: ConstEval<'a>,
+    program: Program,
+    diags: Vec<Diagnostic>,
+    // Spans already reported as unsupported
+    reported: HashSet<(usize, usize)>,
+    // Every function by its qualified path, methods as `Type::name`
+    fns: HashMap<String, (&'a parser::Function, NsId, FnSig)>,
+    // Functions in the order they were first called, which is their index
+    // in the program
+    order: Vec<String>,
+
+    // The function being lowered
+    ns: NsId,
+    scopes: Vec<HashMap<String, (VReg, Ty)>>,
+    func: Function,
+    // Block being filled, None right after a terminator until the next
+    // block starts
+    cur: Option<BlockId>,
+    // Blocks in the order they were started
+    layout: Vec<BlockId>,
+    // Where `continue` and `break` jump to
+    loops: Vec<(BlockId, BlockId)>,
+    // Address a value in memory is returned to, with its size
+    sret: Option<(VReg, u64)>,
+}
+
+// Where a value in memory is: `offset` bytes past the address in `base`
+#[derive(Clone, Copy)]
+struct Place {
+    base: VReg,
+    offset: u64,
+}
+
+// ---------------------------------------
+// Functions
+// ---------------------------------------
+impl<'a> Lower<'a> {
+    fn collect(&mut self, nodes: &'a [Decl], ns: NsId) {
+        for decl in nodes {
+            match decl {
+                Decl::Namespace(n) => {
+                    if let Some(SymbolKind::Namespace(id)) =
+                        self.table.scopes[ns].items.get(&n.name).map(|s| &s.kind)
+                    {
+                        self.collect(&n.nodes, *id);
+                    }
+                }
+                Decl::Function(f) => {
+                    let (key, sig) = match &f.receiver {
+                        Some(r) => match self.table.lookup(ns, r) {
+                            Some(sym) => (
+                                format!("{}::{}", sym.path, f.name),
+                                self.info
+                                    .methods
+                                    .get(&sym.path)
+                                    .and_then(|m| m.get(&f.name)),
+                            ),
+                            None => continue,
+                        },
+                        None => {
+                            let path = self.table.qualify(ns, &f.name);
+                            let sig = self
+                                .info
+                                .funcs
+                                .get(&path)
+                                .or_else(|| self.info.ctors.get(&path));
+                            (path, sig)
+                        }
+                    };
+                    if let Some(sig) = sig {
+                        self.fns.insert(key, (f, ns, sig.clone()));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Program index of `key`, queueing it to be lowered on first use
+    fn index(&mut self, key: &str) -> usize {
+        match self.order.iter().position(|k| k == key) {
+            Some(i) => i,
+            None => {
+                self.order.push(key.to_string());
+                self.order.len() - 1
+            }
+        }
+    }
+
+    fn function(&mut self, key: &str) {
+        let (f, ns, sig) = self.fns[key].clone();
+        self.ns = ns;
+        self.loops.clear();
+        self.layout.clear();
+        self.func = Function {
+            name: key.to_string(),
+            ..empty_function(f.span)
+        };
+
+        // Values in memory are returned through an address passed before
+        // the parameters, then comes `this` for methods
+        self.sret = None;
+        if self.in_memory(&sig.ret) {
+            let size = self.layout(&sig.ret).size;
+            self.sret = Some((self.func.new_reg(ValueType::Ptr), size));
+        }
+        let mut params = HashMap::new();
+        if let Some(this) = &sig.this {
+            let reg = self.func.new_reg(ValueType::Ptr);
+            params.insert("this".to_string(), (reg, this.clone()));
+        }
+        for (p, ty) in f.params.iter().zip(sig.params.iter()) {
+            let vt = self.value_type(ty, p.span);
+            params.insert(p.name.clone(), (self.func.new_reg(vt), ty.clone()));
+        }
+        self.func.params = self.func.regs.len();
+        self.scopes = vec![params];
+        self.func.ret = match &sig.ret {
+            Ty::Void => None,
+            ty if self.in_memory(ty) => None,
+            ty => Some(self.value_type(ty, f.ret.as_ref().map_or(f.span, |t| t.span))),
+        };
+
+        let entry = self.func.new_block();
+        self.start(entry);
+        self.block(&f.body);
+        // The flow check makes sure functions with a return type can't
+        // reach the end of their body
+        if self.cur.is_some() {
+            match sig.ret {
+                Ty::Void => self.terminate(Inst::Return(None)),
+                _ => self.terminate(Inst::Unreachable),
+            }
+        }
+        self.finish();
+    }
+
+    // Renumbers the blocks in the order they were started, so the dump and
+    // the backend see them in source order
+    fn finish(&mut self) {
+        let span = self.func.span;
+        let mut func = std::mem::replace(&mut self.func, empty_function(span));
+        func.reorder(&self.layout);
+        self.program.functions.push(func);
+    }
+
+    fn unsupported(&mut self, span: Span, what: impl Into<String>) {
+        if !self.reported.insert((span.start, span.end)) {
+            return;
+        }
+        self.diags.push(
+            Diagnostic::error(
+                diagnostics::UNSUPPORTED_BY_BACKEND,
+                span,
+                format!("{} can't be compiled to bytecode yet", what.into()),
+            )
+            .with_label("not supported by the code generator")
+            .with_note("strings need a heap, the VM only gives each call memory of its own"),
+        );
+    }
+
+    // Primitive a value is held as: enums as their underlying type,
+    // pointers as their address. None for values that don't fit a register.
+    fn scalar(&self, ty: &Ty) -> Option<Primitive> {
+        match ty {
+            Ty::Prim(p) => Some(*p),
+            Ty::Enum(path) => Some(self.info.enums.get(path).map_or(Primitive::I32, |e| e.repr)),
+            Ty::Pointer(_) | Ty::Ref(_) | Ty::Nil => Some(Primitive::U64),
+            _ => None,
+        }
+    }
+
+    // Structs and arrays are kept in memory and held as their address
+    fn in_memory(&self, ty: &Ty) -> bool {
+        matches!(ty, Ty::Struct(_) | Ty::Array(..))
+    }
+
+    fn layout(&self, ty: &Ty) -> Layout {
+        self.info.layout(ty).unwrap_or(WORD)
+    }
+
+    fn value_type(&mut self, ty: &Ty, span: Span) -> ValueType {
+        match ty {
+            Ty::Pointer(_) | Ty::Ref(_) | Ty::Nil | Ty::Struct(_) | Ty::Array(..) => ValueType::Ptr,
+            _ => match self.scalar(ty) {
+                Some(p) => ValueType::of(p),
+                None => {
+                    self.unsupported(span, format!("values of type `{}`", ty));
+                    ValueType::I32
+                }
+            },
+        }
+    }
+}
+
+// ---------------------------------------
+// Building blocks
+// ---------------------------------------
+impl Lower<'_> {
+    fn reg(&mut self, ty: ValueType) -> VReg {
+        self.func.new_reg(ty)
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        self.func.new_block()
+    }
+
+    // Code after a `return`, `break` or `continue` goes into a block of its
+    // own that nothing jumps to
+    fn push(&mut self, inst: Inst) {
+        let b = match self.cur {
+            Some(b) => b,
+            None => {
+                let b = self.new_block();
+                self.start(b);
+                b
+            }
+        };
+        self.func.blocks[b.0].insts.push(inst);
+    }
+
+    fn terminate(&mut self, inst: Inst) {
+        self.push(inst);
+        self.cur = None;
+    }
+
+    fn goto(&mut self, target: BlockId) {
+        if self.cur.is_some() {
+            self.terminate(Inst::Jump(target));
+        }
+    }
+
+    // Continues in `b`, falling into it from the current block
+    fn start(&mut self, b: BlockId) {
+        self.goto(b);
+        self.cur = Some(b);
+        self.layout.push(b);
+    }
+
+    // A literal `true` or `false` only jumps to the edge taken, like the
+    // flow checks treat it, so a register assigned on that edge alone is
+    // still defined after it
+    fn branch(&mut self, cond: &Expr, then: BlockId, otherwise: BlockId) {
+        if let ExprKind::Bool(b) = cond.kind {
+            self.terminate(Inst::Jump(if b { then } else { otherwise }));
+            return;
+        }
+        let cond = self.expr(cond);
+        self.terminate(Inst::Branch {
+            cond,
+            then,
+            otherwise,
+        });
+    }
+
+    fn constant(&mut self, value: Value, ty: ValueType) -> VReg {
+        let dst = self.reg(ty);
+        self.push(Inst::Const { dst, value });
+        dst
+    }
+
+    fn local(&self, name: &str) -> Option<(VReg, Ty)> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+    }
+
+    // Stand-in for a value that couldn't be lowered, after reporting it
+    fn dummy(&mut self) -> VReg {
+        self.constant(Value::Int(0, Primitive::I32), ValueType::I32)
+    }
+
+    // Address of new memory in the function's frame for a `ty`
+    fn slot(&mut self, ty: &Ty) -> VReg {
+        let layout = self.layout(ty);
+        let offset = self.func.memory.next_multiple_of(layout.align.max(1));
+        self.func.memory = offset + layout.size;
+        let dst = self.reg(ValueType::Ptr);
+        self.push(Inst::Frame { dst, offset });
+        dst
+    }
+
+    fn addr(&mut self, at: Place) -> VReg {
+        if at.offset == 0 {
+            return at.base;
+        }
+        let dst = self.reg(ValueType::Ptr);
+        self.push(Inst::Offset {
+            dst,
+            base: at.base,
+            offset: at.offset,
+        });
+        dst
+    }
+
+    // A `ty` read from memory, values in memory are left where they are
+    fn read(&mut self, from: Place, ty: &Ty, vt: ValueType) -> VReg {
+        let Some(p) = self.scalar(ty) else {
+            return self.addr(from);
+        };
+        let dst = self.reg(vt);
+        self.push(Inst::Load {
+            ty: p,
+            dst,
+            addr: from.base,
+            offset: from.offset,
+        });
+        dst
+    }
+
+    // Writes `src` to memory, values in memory are copied from its address
+    fn write(&mut self, to: Place, ty: &Ty, src: VReg) {
+        match self.scalar(ty) {
+            Some(p) => self.push(Inst::Store {
+                ty: p,
+                addr: to.base,
+                offset: to.offset,
+                src,
+            }),
+            None => {
+                let size = self.layout(ty).size;
+                let to = self.addr(to);
+                self.push(Inst::MemCopy {
+                    to,
+                    from: src,
+                    size,
+                });
+            }
+        }
+    }
+
+    // A copy of the value in memory at `from`
+    fn copy(&mut self, from: VReg, ty: &Ty) -> VReg {
+        let to = self.slot(ty);
+        let size = self.layout(ty).size;
+        self.push(Inst::MemCopy { to, from, size });
+        to
+    }
+}
+
+// Values built in memory of their own, which nothing else can see yet
+fn fresh(expr: &Expr) -> bool {
+    matches!(
+        expr.kind,
+        ExprKind::StructLit { .. } | ExprKind::Call { .. }
+    )
+}
+
+// Integer constant of type `p`, which may be a float type for literals
+fn int_value(n: i128, p: Primitive) -> Value {
+    match p {
+        Primitive::F32 | Primitive::F64 => Value::Float(n as f64, p),
+        _ => Value::Int(n, p),
+    }
+}
+
+// ---------------------------------------
+// Statements
+// ---------------------------------------
+impl Lower<'_> {
+    fn block(&mut self, block: &parser::Block) {
+        self.scopes.push(HashMap::new());
+        for stmt in block.stmts.iter() {
+            self.stmt(stmt);
+        }
+        self.scopes.pop();
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        let key = (stmt.span.start, stmt.span.end);
+        match &stmt.kind {
+            StmtKind::Var { name, value, .. } if self.in_memory(self.local_type(key)) => {
+                let ty = self.local_type(key).clone();
+                let reg = match value {
+                    Some(v) if fresh(v) => self.expr(v),
+                    Some(v) => {
+                        let src = self.expr(v);
+                        self.copy(src, &ty)
+                    }
+                    None => self.slot(&ty),
+                };
+                self.scopes
+                    .last_mut()
+                    .unwrap()
+                    .insert(name.clone(), (reg, ty));
+            }
+            StmtKind::Var { name, value, .. } => {
+                let ty = self.local_type(key).clone();
+                // A local that can't be lowered is reported once, not again
+                // for its value
+                let scalar = self.scalar(&ty).is_some();
+                let vt = self.value_type(&ty, stmt.span);
+                let reg = self.reg(vt);
+                if let Some(v) = value
+                    && scalar
+                {
+                    let src = self.expr(v);
+                    self.push(Inst::Copy { dst: reg, src });
+                }
+                self.scopes
+                    .last_mut()
+                    .unwrap()
+                    .insert(name.clone(), (reg, ty));
+            }
+            StmtKind::Assign { target, op, value } => self.assign(target, *op, value),
+            StmtKind::Expr(e) => match &e.kind {
+                ExprKind::Call { callee, args } if self.info.type_of(e) == Some(&Ty::Void) => {
+                    self.call(callee, args, None);
+                }
+                _ => {
+                    self.expr(e);
+                }
+            },
+            StmtKind::Return(value) => {
+                let v = value.as_ref().map(|v| self.expr(v));
+                match (v, self.sret) {
+                    (Some(from), Some((to, size))) => {
+                        self.push(Inst::MemCopy { to, from, size });
+                        self.terminate(Inst::Return(None));
+                    }
+                    _ => self.terminate(Inst::Return(v)),
+                }
+            }
+            StmtKind::Break => {
+                if let Some(&(_, exit)) = self.loops.last() {
+                    self.terminate(Inst::Jump(exit));
+                }
+            }
+            StmtKind::Continue => {
+                if let Some(&(next, _)) = self.loops.last() {
+                    self.terminate(Inst::Jump(next));
+                }
+            }
+            StmtKind::If {
+                branches,
+                else_body,
+            } => {
+                let end = self.new_block();
+                for (i, (cond, body)) in branches.iter().enumerate() {
+                    let then = self.new_block();
+                    let otherwise = if i + 1 == branches.len() && else_body.is_none() {
+                        end
+                    } else {
+                        self.new_block()
+                    };
+                    self.branch(cond, then, otherwise);
+                    self.start(then);
+                    self.block(body);
+                    self.goto(end);
+                    if otherwise != end {
+                        self.start(otherwise);
+                    }
+                }
+                if let Some(body) = else_body {
+                    self.block(body);
+                }
+                self.start(end);
+            }
+            StmtKind::While { cond, body } => {
+                let head = self.new_block();
+                let then = self.new_block();
+                let exit = self.new_block();
+                self.start(head);
+                self.branch(cond, then, exit);
+                self.start(then);
+                self.loops.push((head, exit));
+                self.block(body);
+                self.loops.pop();
+                self.goto(head);
+                self.start(exit);
+            }
+            StmtKind::For {
+                var,
+                start,
+                end,
+                body,
+            } => {
+                let ty = self.local_type(key).clone();
+                let Some(end) = end else {
+                    self.each(var, start, &ty, body);
+                    return;
+                };
+                let p = self.scalar(&ty).unwrap_or(Primitive::I32);
+                let vt = ValueType::of(p);
+                let i = self.reg(vt);
+                let first = self.expr(start);
+                self.push(Inst::Copy { dst: i, src: first });
+                // The bound is read once, the body may assign what it names
+                let last = self.reg(vt);
+                let bound = self.expr(end);
+                self.push(Inst::Copy {
+                    dst: last,
+                    src: bound,
+                });
+                self.count(i, last, p, body, |_| {
+                    HashMap::from([(var.clone(), (i, ty))])
+                });
+            }
+            StmtKind::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                let p = self
+                    .info
+                    .type_of(value)
+                    .and_then(|t| self.scalar(t))
+                    .unwrap_or(Primitive::I32);
+                let v = self.expr(value);
+                let end = self.new_block();
+                for case in cases.iter() {
+                    let c = self.expr(&case.value);
+                    let cond = self.reg(ValueType::I32);
+                    self.push(Inst::Binary {
+                        op: BinaryOp::Eq,
+                        ty: p,
+                        dst: cond,
+                        lhs: c,
+                        rhs: v,
+                    });
+                    let then = self.new_block();
+                    let otherwise = self.new_block();
+                    self.terminate(Inst::Branch {
+                        cond,
+                        then,
+                        otherwise,
+                    });
+                    self.start(then);
+                    self.block(&case.body);
+                    self.goto(end);
+                    self.start(otherwise);
+                }
+                match default {
+                    Some(body) => self.block(body),
+                    // Switches that handle every variant of an enum never
+                    // get here without a match
+                    None if self.info.exhaustive.contains(&key) => {
+                        self.terminate(Inst::Unreachable)
+                    }
+                    None => {}
+                }
+                self.start(end);
+            }
+        }
+    }
+
+    fn local_type(&self, key: (usize, usize)) -> &Ty {
+        self.info.locals.get(&key).unwrap_or(&Ty::Error)
+    }
+
+    // Counts `i` up to `last`, running `body` in the scope `enter` gives
+    // for each value
+    fn count(
+        &mut self,
+        i: VReg,
+        last: VReg,
+        p: Primitive,
+        body: &parser::Block,
+        enter: impl FnOnce(&mut Self) -> HashMap<String, (VReg, Ty)>,
+    ) {
+        let vt = ValueType::of(p);
+        let one = self.constant(int_value(1, p), vt);
+        let head = self.new_block();
+        let then = self.new_block();
+        let next = self.new_block();
+        let exit = self.new_block();
+        self.start(head);
+        let cond = self.reg(ValueType::I32);
+        self.push(Inst::Binary {
+            op: BinaryOp::Lt,
+            ty: p,
+            dst: cond,
+            lhs: i,
+            rhs: last,
+        });
+        self.terminate(Inst::Branch {
+            cond,
+            then,
+            otherwise: exit,
+        });
+        self.start(then);
+        self.loops.push((next, exit));
+        let scope = enter(self);
+        self.scopes.push(scope);
+        self.block(body);
+        self.scopes.pop();
+        self.loops.pop();
+        self.start(next);
+        self.push(Inst::Binary {
+            op: BinaryOp::Add,
+            ty: p,
+            dst: i,
+            lhs: i,
+            rhs: one,
+        });
+        self.goto(head);
+        self.start(exit);
+    }
+
+    // `for v in array` goes through the indexes, each element is read into
+    // `v` as the iteration starts
+    fn each(&mut self, var: &str, array: &Expr, ty: &Ty, body: &parser::Block) {
+        let len = match self.info.type_of(array) {
+            Some(Ty::Array(_, n)) => n.unwrap_or(0),
+            _ => 0,
+        };
+        let base = self.expr(array);
+        let i = self.constant(Value::Int(0, Primitive::I64), ValueType::I64);
+        let last = self.constant(Value::Int(len as i128, Primitive::I64), ValueType::I64);
+        let size = self.layout(ty).size;
+        self.count(i, last, Primitive::I64, body, |l| {
+            let dst = l.reg(ValueType::Ptr);
+            l.push(Inst::Index {
+                dst,
+                base,
+                index: i,
+                size,
+                len: None,
+            });
+            let vt = l.value_type(ty, array.span);
+            let mut v = l.read(
+                Place {
+                    base: dst,
+                    offset: 0,
+                },
+                ty,
+                vt,
+            );
+            if l.in_memory(ty) {
+                v = l.copy(v, ty);
+            }
+            HashMap::from([(var.to_string(), (v, ty.clone()))])
+        });
+    }
+
+    fn assign(&mut self, target: &Expr, op: AssignOp, value: &Expr) {
+        let vt = self.info.type_of(value).cloned().unwrap_or(Ty::Error);
+        if let ExprKind::Ident(name) = &target.kind
+            && let Some((reg, ty)) = self.local(name)
+            && !self.in_memory(&ty)
+        {
+            let src = self.expr(value);
+            match op.binary() {
+                None => self.push(Inst::Copy { dst: reg, src }),
+                Some(op) => self.arith(op, &ty, &vt, reg, reg, src),
+            }
+            return;
+        }
+        // Fields, elements, through pointers, or a whole value in memory
+        let ty = self.info.type_of(target).cloned().unwrap_or(Ty::Error);
+        let to = self.place(target);
+        let src = match op.binary() {
+            None => self.expr(value),
+            Some(op) => {
+                let rt = self.value_type(&ty, target.span);
+                let old = self.read(to, &ty, rt);
+                let rhs = self.expr(value);
+                let dst = self.reg(rt);
+                self.arith(op, &ty, &vt, dst, old, rhs);
+                dst
+            }
+        };
+        self.write(to, &ty, src);
+    }
+}
+
+// ---------------------------------------
+// Expressions
+// ---------------------------------------
+impl Lower<'_> {
+    // Register holding the value of `expr`, converted to the type it's
+    // stored as. Locals are read in place, anything else gets a new
+    // register. Values in memory are held as their address.
+    fn expr(&mut self, expr: &Expr) -> VReg {
+        let ty = self.info.type_of(expr).cloned().unwrap_or(Ty::Error);
+        let address = self.in_memory(&ty).then_some(Primitive::U64);
+        let Some(p) = self.scalar(&ty).or(address) else {
+            self.unsupported(expr.span, format!("values of type `{}`", ty));
+            return self.dummy();
+        };
+        let v = self.value(expr, &ty, p);
+        match self.info.coercions.get(&(expr.span.start, expr.span.end)) {
+            Some(to) => self.convert(&ty, &to.clone(), v),
+            None => v,
+        }
+    }
+
+    fn value(&mut self, expr: &Expr, ty: &Ty, p: Primitive) -> VReg {
+        let vt = self.value_type(ty, expr.span);
+        match &expr.kind {
+            ExprKind::Int(n) => self.constant(int_value(*n as i128, p), vt),
+            ExprKind::Float(x) => self.constant(Value::Float(*x, p), vt),
+            ExprKind::Bool(b) => self.constant(Value::Bool(*b), vt),
+            ExprKind::Char(c) => self.constant(Value::Char(*c), vt),
+            ExprKind::Nil => self.constant(Value::Int(0, Primitive::U64), vt),
+            ExprKind::Ident(name) => match self.local(name) {
+                Some((reg, _)) => reg,
+                None => self.known(expr, p, vt),
+            },
+            ExprKind::Path(_) | ExprKind::SizeOf(_) | ExprKind::AlignOf(_) => {
+                self.known(expr, p, vt)
+            }
+            ExprKind::Member { .. }
+            | ExprKind::Index { .. }
+            | ExprKind::Unary {
+                op: UnaryOp::Deref, ..
+            } => {
+                let from = self.place(expr);
+                self.read(from, ty, vt)
+            }
+            ExprKind::Unary { op, expr: inner } => {
+                let src = self.expr(inner);
+                let dst = self.reg(vt);
+                self.push(Inst::Unary {
+                    op: *op,
+                    ty: p,
+                    dst,
+                    src,
+                });
+                dst
+            }
+            ExprKind::Binary {
+                op: op @ (BinaryOp::And | BinaryOp::Or),
+                lhs,
+                rhs,
+            } => {
+                // The right side only runs when the left one doesn't decide
+                // the result
+                let dst = self.reg(vt);
+                let l = self.expr(lhs);
+                self.push(Inst::Copy { dst, src: l });
+                let right = self.new_block();
+                let end = self.new_block();
+                let (then, otherwise) = match op {
+                    BinaryOp::And => (right, end),
+                    _ => (end, right),
+                };
+                self.terminate(Inst::Branch {
+                    cond: dst,
+                    then,
+                    otherwise,
+                });
+                self.start(right);
+                let r = self.expr(rhs);
+                self.push(Inst::Copy { dst, src: r });
+                self.start(end);
+                dst
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                let lt = self.info.type_of(lhs).cloned().unwrap_or(Ty::Error);
+                let rt = self.info.type_of(rhs).cloned().unwrap_or(Ty::Error);
+                let lhs = self.expr(lhs);
+                let rhs = self.expr(rhs);
+                let dst = self.reg(vt);
+                self.arith(*op, &lt, &rt, dst, lhs, rhs);
+                dst
+            }
+            ExprKind::Ternary {
+                cond,
+                then,
+                otherwise,
+            } => {
+                let dst = self.reg(vt);
+                let cond = self.expr(cond);
+                let (a, b, end) = (self.new_block(), self.new_block(), self.new_block());
+                self.terminate(Inst::Branch {
+                    cond,
+                    then: a,
+                    otherwise: b,
+                });
+                self.start(a);
+                let src = self.expr(then);
+                self.push(Inst::Copy { dst, src });
+                self.goto(end);
+                self.start(b);
+                let src = self.expr(otherwise);
+                self.push(Inst::Copy { dst, src });
+                self.start(end);
+                dst
+            }
+            ExprKind::Cast { expr: inner, .. } => {
+                let from = self.info.type_of(inner).cloned().unwrap_or(Ty::Error);
+                let v = self.expr(inner);
+                self.convert(&from, ty, v)
+            }
+            ExprKind::Call { callee, args } => match self.call(callee, args, Some(vt)) {
+                Some(dst) => dst,
+                None => self.dummy(),
+            },
+            ExprKind::StructLit { fields, .. } => {
+                let dst = self.slot(ty);
+                let Ty::Struct(path) = ty else {
+                    return dst;
+                };
+                let decl = self.info.structs.get(path).cloned().unwrap_or_default();
+                for init in fields.iter() {
+                    let Some((_, fty)) = decl.iter().find(|(name, _)| *name == init.name) else {
+                        continue;
+                    };
+                    let offset = self.field_offset(path, &init.name);
+                    let src = self.expr(&init.value);
+                    self.write(Place { base: dst, offset }, fty, src);
+                }
+                dst
+            }
+            // Reported by `expr`
+            ExprKind::Str(_) => self.dummy(),
+        }
+    }
+
+    // Where the value of `expr` is in memory. Fields and elements are found
+    // from their struct or array, or through a pointer.
+    fn place(&mut self, expr: &Expr) -> Place {
+        match &expr.kind {
+            ExprKind::Member { base, field } => {
+                let (at, path) = match self.info.type_of(base) {
+                    Some(Ty::Struct(path)) => (self.place(base), path),
+                    Some(Ty::Pointer(t) | Ty::Ref(t)) if matches!(**t, Ty::Struct(_)) => {
+                        let Ty::Struct(path) = &**t else {
+                            unreachable!()
+                        };
+                        let base = self.expr(base);
+                        (Place { base, offset: 0 }, path)
+                    }
+                    _ => return self.dummy_place(),
+                };
+                let offset = self.field_offset(path, field);
+                Place {
+                    base: at.base,
+                    offset: at.offset + offset,
+                }
+            }
+            ExprKind::Index { base, index } => {
+                let (at, elem, len) = match self.info.type_of(base) {
+                    // Array indexes are checked against the length
+                    Some(Ty::Array(elem, len)) => (self.place(base), elem, *len),
+                    Some(Ty::Pointer(elem)) => {
+                        let base = self.expr(base);
+                        (Place { base, offset: 0 }, elem, None)
+                    }
+                    _ => return self.dummy_place(),
+                };
+                let size = self.layout(elem).size;
+                let it = self.info.type_of(index).cloned().unwrap_or(Ty::Error);
+                let i = self.expr(index);
+                let index = self.convert(&it, &Ty::Prim(Primitive::I64), i);
+                let dst = self.reg(ValueType::Ptr);
+                self.push(Inst::Index {
+                    dst,
+                    base: at.base,
+                    index,
+                    size,
+                    len,
+                });
+                Place {
+                    base: dst,
+                    offset: at.offset,
+                }
+            }
+            ExprKind::Unary {
+                op: UnaryOp::Deref,
+                expr: inner,
+            } => Place {
+                base: self.expr(inner),
+                offset: 0,
+            },
+            // Locals and other values in memory
+            _ => Place {
+                base: self.expr(expr),
+                offset: 0,
+            },
+        }
+    }
+
+    fn dummy_place(&mut self) -> Place {
+        Place {
+            base: self.dummy(),
+            offset: 0,
+        }
+    }
+
+    fn field_offset(&self, path: &str, field: &str) -> u64 {
+        self.info
+            .layouts
+            .get(path)
+            .and_then(|l| l.field(field))
+            .map_or(0, |f| f.offset)
+    }
+
+    // `lhs op rhs` into `dst`, for operands of type `lt` and `rt`. Adding an
+    // integer to a pointer moves it by that many of the values it points to.
+    fn arith(&mut self, op: BinaryOp, lt: &Ty, rt: &Ty, dst: VReg, lhs: VReg, rhs: VReg) {
+        if let (Ty::Pointer(elem), BinaryOp::Add | BinaryOp::Sub) = (lt, op) {
+            let size = self.layout(elem).size;
+            let mut index = self.convert(rt, &Ty::Prim(Primitive::I64), rhs);
+            if op == BinaryOp::Sub {
+                let neg = self.reg(ValueType::I64);
+                self.push(Inst::Unary {
+                    op: UnaryOp::Neg,
+                    ty: Primitive::I64,
+                    dst: neg,
+                    src: index,
+                });
+                index = neg;
+            }
+            self.push(Inst::Index {
+                dst,
+                base: lhs,
+                index,
+                size,
+                len: None,
+            });
+            return;
+        }
+        let ty = self.scalar(lt).unwrap_or(Primitive::I32);
+        self.push(Inst::Binary {
+            op,
+            ty,
+            dst,
+            lhs,
+            rhs,
+        });
+    }
+
+    // `const`s, enum variants and sizeof/alignof are known at compile time
+    fn known(&mut self, expr: &Expr, p: Primitive, vt: ValueType) -> VReg {
+        let value = match self.eval.eval(expr, self.ns, Some(p)) {
+            Some(Value::Variant(_, _, n)) => Value::Int(n, p),
+            Some(Value::Int(n, _)) => int_value(n, p),
+            Some(v) => v,
+            None => {
+                self.unsupported(expr.span, "this expression");
+                return self.dummy();
+            }
+        };
+        self.constant(value, vt)
+    }
+
+    // Casts and implicit conversions, a no-op between types held the same way
+    fn convert(&mut self, from: &Ty, to: &Ty, src: VReg) -> VReg {
+        let (Some(f), Some(t)) = (self.scalar(from), self.scalar(to)) else {
+            return src;
+        };
+        if f == t {
+            return src;
+        }
+        let dst = self.reg(ValueType::of(t));
+        self.push(Inst::Convert {
+            from: f,
+            to: t,
+            dst,
+            src,
+        });
+        dst
+    }
+
+    // Values in memory are returned to memory of the caller's, whose
+    // address is passed first
+    fn call(&mut self, callee: &Expr, args: &[Expr], ret: Option<ValueType>) -> Option<VReg> {
+        let Some(key) = self.callee(callee).filter(|k| self.fns.contains_key(k)) else {
+            self.unsupported(callee.span, "this call");
+            return None;
+        };
+        let sig = self.fns[&key].2.clone();
+        let func = self.index(&key);
+        let sret = self.in_memory(&sig.ret).then(|| self.slot(&sig.ret));
+        let mut values: Vec<VReg> = sret.into_iter().collect();
+        if let ExprKind::Member { base, .. } = &callee.kind {
+            values.push(self.this(base));
+        }
+        for (arg, ty) in args.iter().zip(sig.params.iter()) {
+            values.push(self.arg(arg, ty));
+        }
+        let dst = match sret {
+            Some(_) => None,
+            None => ret.map(|ty| self.reg(ty)),
+        };
+        self.push(Inst::Call {
+            func,
+            dst,
+            args: values,
+        });
+        sret.or(dst)
+    }
+
+    // `this` for a method called on `base`, the address of the value or
+    // the pointer to it
+    fn this(&mut self, base: &Expr) -> VReg {
+        match self.info.type_of(base) {
+            Some(Ty::Struct(_)) => {
+                let at = self.place(base);
+                self.addr(at)
+            }
+            _ => self.expr(base),
+        }
+    }
+
+    // Arguments in memory are passed as the address of a copy the callee
+    // can change, unless nothing else sees the value
+    fn arg(&mut self, arg: &Expr, ty: &Ty) -> VReg {
+        let v = self.expr(arg);
+        if self.in_memory(ty) && !fresh(arg) {
+            self.copy(v, ty)
+        } else {
+            v
+        }
+    }
+
+    // Functions, constructors and methods, by their key in `fns`
+    fn callee(&self, callee: &Expr) -> Option<String> {
+        let is_fn = |sym: &Symbol| matches!(sym.kind, SymbolKind::Function | SymbolKind::Struct);
+        match &callee.kind {
+            ExprKind::Ident(name) if self.local(name).is_none() => self
+                .table
+                .lookup(self.ns, name)
+                .filter(|sym| is_fn(sym))
+                .map(|sym| sym.path.clone()),
+            ExprKind::Path(path) => match self.table.lookup_path(self.ns, path) {
+                Ok(Res::Symbol(sym)) if is_fn(sym) => Some(sym.path.clone()),
+                Ok(Res::Method(sym, _)) => Some(format!("{}::{}", sym.path, path.last()?)),
+                _ => None,
+            },
+            ExprKind::Member { base, field } => match self.info.type_of(base)? {
+                Ty::Struct(path) => Some(format!("{}::{}", path, field)),
+                Ty::Pointer(t) | Ty::Ref(t) => match &**t {
+                    Ty::Struct(path) => Some(format!("{}::{}", path, field)),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,16 @@
+mod bitset;
+mod liveness;
+mod lower;
+mod types;
+mod verify;
+mod worklist;
+
+#[cfg(test)]
+mod tests;
+
+pub use bitset::*;
+pub use liveness::*;
+pub use lower::*;
+pub use types::*;
+pub use verify::*;
+pub use worklist::*;
@@ -0,0 +1,203 @@
+use super::*;
+use crate::codegen::ValueType;
+use crate::lexer::Span;
+use crate::parser::Primitive;
+use crate::sema::Value;
+
+fn r(n: u32) -> VReg {
+    VReg(n)
+}
+
+fn bb(n: usize) -> BlockId {
+    BlockId(n)
+}
+
+fn konst(dst: u32) -> Inst {
+    Inst::Const {
+        dst: r(dst),
+        value: Value::Int(1, Primitive::I32),
+    }
+}
+
+fn branch(cond: u32, then: usize, otherwise: usize) -> Inst {
+    Inst::Branch {
+        cond: r(cond),
+        then: bb(then),
+        otherwise: bb(otherwise),
+    }
+}
+
+fn ret(v: u32) -> Inst {
+    Inst::Return(Some(r(v)))
+}
+
+// `f` with `params` parameters and `regs` i32 registers
+fn func(params: usize, regs: usize, blocks: Vec<Vec<Inst>>) -> Function {
+    Function {
+        name: "f".to_string(),
+        params,
+        ret: Some(ValueType::I32),
+        regs: vec![ValueType::I32; regs],
+        memory: 0,
+        blocks: blocks.into_iter().map(|insts| Block { insts }).collect(),
+        span: Span {
+            start: 0,
+            end: 0,
+            line: 1,
+            col: 0,
+        },
+    }
+}
+
+fn errors(f: Function) -> Vec<String> {
+    let program = Program {
+        functions: vec![f],
+        entry: 0,
+    };
+    match verify(&program) {
+        Ok(()) => vec![],
+        Err(errors) => errors.iter().map(|e| e.to_string()).collect(),
+    }
+}
+
+// bb0 branches on the parameter to bb1 and bb2, which both go to bb3
+fn diamond(left: Vec<Inst>, right: Vec<Inst>, join: Vec<Inst>) -> Function {
+    let mut left = left;
+    left.push(Inst::Jump(bb(3)));
+    let mut right = right;
+    right.push(Inst::Jump(bb(3)));
+    func(1, 3, vec![vec![branch(0, 1, 2)], left, right, join])
+}
+
+#[test]
+fn defined_on_every_path() {
+    assert!(errors(diamond(vec![konst(1)], vec![konst(1)], vec![ret(1)])).is_empty());
+}
+
+#[test]
+fn defined_on_one_path() {
+    assert_eq!(
+        errors(diamond(vec![konst(1)], vec![konst(2)], vec![ret(1)])),
+        ["`f` bb3: %1 is used before it's defined"]
+    );
+}
+
+#[test]
+fn defined_in_a_loop_body() {
+    // bb1 is the loop header, bb2 the body
+    let read_in_header = func(
+        1,
+        2,
+        vec![
+            vec![Inst::Jump(bb(1))],
+            vec![
+                Inst::Copy {
+                    dst: r(0),
+                    src: r(1),
+                },
+                branch(0, 2, 3),
+            ],
+            vec![konst(1), Inst::Jump(bb(1))],
+            vec![ret(0)],
+        ],
+    );
+    assert_eq!(
+        errors(read_in_header),
+        ["`f` bb1: %1 is used before it's defined"]
+    );
+
+    let defined_before = func(
+        1,
+        2,
+        vec![
+            vec![konst(1), Inst::Jump(bb(1))],
+            vec![
+                Inst::Copy {
+                    dst: r(0),
+                    src: r(1),
+                },
+                branch(0, 2, 3),
+            ],
+            vec![konst(1), Inst::Jump(bb(1))],
+            vec![ret(0)],
+        ],
+    );
+    assert!(errors(defined_before).is_empty());
+}
+
+#[test]
+fn unreachable_blocks_are_skipped() {
+    let f = func(1, 3, vec![vec![ret(0)], vec![ret(2)]]);
+    assert!(errors(f).is_empty());
+}
+
+#[test]
+fn malformed_blocks() {
+    let f = func(
+        1,
+        2,
+        vec![
+            vec![konst(1)],
+            vec![ret(0), ret(0)],
+            vec![Inst::Jump(bb(9))],
+        ],
+    );
+    assert_eq!(
+        errors(f),
+        [
+            "`f` bb0: block doesn't end in a terminator",
+            "`f` bb1: terminator in the middle of the block at 0",
+            "`f` bb2: jump to missing block bb9",
+        ]
+    );
+}
+
+#[test]
+fn frame_addresses_in_memory() {
+    let frame = |offset| Inst::Frame { dst: r(0), offset };
+    let mut f = func(0, 1, vec![vec![frame(8), frame(24), ret(0)]]);
+    f.memory = 16;
+    assert_eq!(errors(f), ["`f` bb0: frame address 24 past the memory"]);
+}
+
+fn copy(dst: u32, src: u32) -> Inst {
+    Inst::Copy {
+        dst: r(dst),
+        src: r(src),
+    }
+}
+
+fn sets(live: Vec<BitSet>) -> Vec<Vec<usize>> {
+    live.iter().map(|s| s.iter().collect()).collect()
+}
+
+// bb1 is the loop header, bb2 the body. %1 and %2 are only copied into
+// each other around the loop.
+fn copy_loop() -> Function {
+    func(
+        1,
+        3,
+        vec![
+            vec![konst(1), Inst::Jump(bb(1))],
+            vec![copy(2, 1), branch(0, 2, 3)],
+            vec![copy(1, 2), Inst::Jump(bb(1))],
+            vec![ret(0)],
+        ],
+    )
+}
+
+#[test]
+fn live_around_a_loop() {
+    assert_eq!(
+        sets(live_out(&copy_loop())),
+        [vec![0, 1], vec![0, 2], vec![0, 1], vec![]]
+    );
+}
+
+#[test]
+fn dead_copies_are_not_strongly_live() {
+    assert_eq!(
+        sets(strongly_live_out(&copy_loop())),
+        [vec![0], vec![0], vec![0], vec![]]
+    );
+}
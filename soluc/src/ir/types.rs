@@ -0,0 +1,489 @@
+use crate::codegen::ValueType;
+use crate::lexer::Span;
+use crate::parser::{BinaryOp, Primitive, UnaryOp};
+use crate::sema::Value;
+use std::fmt;
+
+// A virtual register. A function has as many as it needs, each holding
+// values of one ValueType. They aren't SSA, locals are assigned as often as
+// the source assigns them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VReg(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub usize);
+
+#[derive(Debug, Clone)]
+pub enum Inst {
+    // Ints, bools, chars and enum discriminants, or a float
+    Const {
+        dst: VReg,
+        value: Value,
+    },
+    Copy {
+        dst: VReg,
+        src: VReg,
+    },
+    // `ty` is the type of the operands, comparisons give a bool
+    Unary {
+        op: UnaryOp,
+        ty: Primitive,
+        dst: VReg,
+        src: VReg,
+    },
+    Binary {
+        op: BinaryOp,
+        ty: Primitive,
+        dst: VReg,
+        lhs: VReg,
+        rhs: VReg,
+    },
+    Convert {
+        from: Primitive,
+        to: Primitive,
+        dst: VReg,
+        src: VReg,
+    },
+    // Address `offset` of the function's memory, which holds the values
+    // that don't fit a register
+    Frame {
+        dst: VReg,
+        offset: u64,
+    },
+    // Address `offset` bytes past `base`
+    Offset {
+        dst: VReg,
+        base: VReg,
+        offset: u64,
+    },
+    // Address of element `index` of the `size` byte elements at `base`,
+    // trapping unless `index` is below `len` when it's known
+    Index {
+        dst: VReg,
+        base: VReg,
+        index: VReg,
+        size: u64,
+        len: Option<u64>,
+    },
+    // Reads or writes a `ty` at `addr` + `offset`
+    Load {
+        ty: Primitive,
+        dst: VReg,
+        addr: VReg,
+        offset: u64,
+    },
+    Store {
+        ty: Primitive,
+        addr: VReg,
+        offset: u64,
+        src: VReg,
+    },
+    // Copies `size` bytes of memory
+    MemCopy {
+        to: VReg,
+        from: VReg,
+        size: u64,
+    },
+    // `func` indexes Program::functions
+    Call {
+        func: usize,
+        dst: Option<VReg>,
+        args: Vec<VReg>,
+    },
+
+    // Terminators, the last instruction of every block and nowhere else
+    Jump(BlockId),
+    Branch {
+        cond: VReg,
+        then: BlockId,
+        otherwise: BlockId,
+    },
+    Return(Option<VReg>),
+    // Calls `func` in place of the current function, which returns what it
+    // returns
+    TailCall {
+        func: usize,
+        args: Vec<VReg>,
+    },
+    // End of a block control never reaches, like the end of a function
+    // that returns on every path
+    Unreachable,
+}
+
+impl Inst {
+    pub fn is_terminator(&self) -> bool {
+        matches!(
+            self,
+            Inst::Jump(_)
+                | Inst::Branch { .. }
+                | Inst::Return(_)
+                | Inst::TailCall { .. }
+                | Inst::Unreachable
+        )
+    }
+
+    // Register written by the instruction
+    pub fn def(&self) -> Option<VReg> {
+        match self {
+            Inst::Const { dst, .. }
+            | Inst::Copy { dst, .. }
+            | Inst::Unary { dst, .. }
+            | Inst::Binary { dst, .. }
+            | Inst::Convert { dst, .. }
+            | Inst::Frame { dst, .. }
+            | Inst::Offset { dst, .. }
+            | Inst::Index { dst, .. }
+            | Inst::Load { dst, .. } => Some(*dst),
+            Inst::Call { dst, .. } => *dst,
+            _ => None,
+        }
+    }
+
+    // Registers read by the instruction
+    pub fn uses(&self) -> Vec<VReg> {
+        match self {
+            Inst::Copy { src, .. } | Inst::Unary { src, .. } | Inst::Convert { src, .. } => {
+                vec![*src]
+            }
+            Inst::Binary { lhs, rhs, .. } => vec![*lhs, *rhs],
+            Inst::Offset { base, .. } => vec![*base],
+            Inst::Index { base, index, .. } => vec![*base, *index],
+            Inst::Load { addr, .. } => vec![*addr],
+            Inst::Store { addr, src, .. } => vec![*addr, *src],
+            Inst::MemCopy { to, from, .. } => vec![*to, *from],
+            Inst::Call { args, .. } | Inst::TailCall { args, .. } => args.clone(),
+            Inst::Branch { cond, .. } => vec![*cond],
+            Inst::Return(Some(v)) => vec![*v],
+            _ => vec![],
+        }
+    }
+
+    pub fn succs(&self) -> Vec<BlockId> {
+        match self {
+            Inst::Jump(b) => vec![*b],
+            Inst::Branch {
+                then, otherwise, ..
+            } => vec![*then, *otherwise],
+            _ => vec![],
+        }
+    }
+
+    // Replaces each jump target `t` with `f(t)`
+    pub fn retarget(&mut self, mut f: impl FnMut(BlockId) -> BlockId) {
+        match self {
+            Inst::Jump(t) => *t = f(*t),
+            Inst::Branch {
+                then, otherwise, ..
+            } => {
+                *then = f(*then);
+                *otherwise = f(*otherwise);
+            }
+            _ => {}
+        }
+    }
+
+    // Replaces the register written with `f(r)`
+    pub fn rename_def(&mut self, f: impl FnOnce(VReg) -> VReg) {
+        match self {
+            Inst::Const { dst, .. }
+            | Inst::Copy { dst, .. }
+            | Inst::Unary { dst, .. }
+            | Inst::Binary { dst, .. }
+            | Inst::Convert { dst, .. }
+            | Inst::Frame { dst, .. }
+            | Inst::Offset { dst, .. }
+            | Inst::Index { dst, .. }
+            | Inst::Load { dst, .. }
+            | Inst::Call { dst: Some(dst), .. } => *dst = f(*dst),
+            _ => {}
+        }
+    }
+
+    // Replaces each register read with `f(r)`
+    pub fn rename_uses(&mut self, mut f: impl FnMut(VReg) -> VReg) {
+        match self {
+            Inst::Copy { src, .. } | Inst::Unary { src, .. } | Inst::Convert { src, .. } => {
+                *src = f(*src)
+            }
+            Inst::Binary { lhs, rhs, .. } => {
+                *lhs = f(*lhs);
+                *rhs = f(*rhs);
+            }
+            Inst::Offset { base, .. } => *base = f(*base),
+            Inst::Index { base, index, .. } => {
+                *base = f(*base);
+                *index = f(*index);
+            }
+            Inst::Load { addr, .. } => *addr = f(*addr),
+            Inst::Store { addr, src, .. } => {
+                *addr = f(*addr);
+                *src = f(*src);
+            }
+            Inst::MemCopy { to, from, .. } => {
+                *to = f(*to);
+                *from = f(*from);
+            }
+            Inst::Call { args, .. } | Inst::TailCall { args, .. } => {
+                args.iter_mut().for_each(|a| *a = f(*a))
+            }
+            Inst::Branch { cond, .. } => *cond = f(*cond),
+            Inst::Return(Some(v)) => *v = f(*v),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Block {
+    pub insts: Vec<Inst>,
+}
+
+// Parameters are the first registers, in order. bb0 is the entry block.
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    pub params: usize,
+    pub ret: Option<ValueType>,
+    pub regs: Vec<ValueType>, // Type of each register
+    pub memory: u64,          // Bytes of memory for `Frame` addresses
+    pub blocks: Vec<Block>,
+    pub span: Span,
+}
+
+impl Function {
+    pub fn new_reg(&mut self, ty: ValueType) -> VReg {
+        self.regs.push(ty);
+        VReg(self.regs.len() as u32 - 1)
+    }
+
+    pub fn new_block(&mut self) -> BlockId {
+        self.blocks.push(Block::default());
+        BlockId(self.blocks.len() - 1)
+    }
+
+    pub fn succs(&self, b: BlockId) -> Vec<BlockId> {
+        self.blocks[b.0]
+            .insts
+            .last()
+            .map_or(vec![], |inst| inst.succs())
+    }
+
+    // Keeps only the blocks in `order`, in that order, and renumbers the
+    // jumps. Blocks left out must not be jumped to by the ones kept.
+    pub fn reorder(&mut self, order: &[BlockId]) {
+        let mut number = vec![BlockId(usize::MAX); self.blocks.len()];
+        for (i, b) in order.iter().enumerate() {
+            number[b.0] = BlockId(i);
+        }
+        let mut blocks = std::mem::take(&mut self.blocks);
+        for b in order.iter() {
+            let mut block = std::mem::take(&mut blocks[b.0]);
+            if let Some(last) = block.insts.last_mut() {
+                last.retarget(|t| number[t.0]);
+            }
+            self.blocks.push(block);
+        }
+    }
+
+    // Blocks reachable from the entry, each after all of its successors
+    // except the ones it loops back to
+    pub fn postorder(&self) -> Vec<BlockId> {
+        let mut order = vec![];
+        if self.blocks.is_empty() {
+            return order;
+        }
+        let mut seen = vec![false; self.blocks.len()];
+        // Blocks being visited with the successors still to go
+        let mut stack = vec![(BlockId(0), self.succs(BlockId(0)))];
+        seen[0] = true;
+        while let Some((b, succs)) = stack.last_mut() {
+            match succs.pop() {
+                Some(s) if !seen[s.0] => {
+                    seen[s.0] = true;
+                    let next = self.succs(s);
+                    stack.push((s, next));
+                }
+                Some(_) => {}
+                None => {
+                    order.push(*b);
+                    stack.pop();
+                }
+            }
+        }
+        order
+    }
+
+    pub fn preds(&self) -> Vec<Vec<BlockId>> {
+        let mut preds = vec![vec![]; self.blocks.len()];
+        for b in 0..self.blocks.len() {
+            for s in self.succs(BlockId(b)) {
+                if let Some(p) = preds.get_mut(s.0) {
+                    p.push(BlockId(b));
+                }
+            }
+        }
+        preds
+    }
+}
+
+// Functions are in function table order, `entry` is `main`
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub functions: Vec<Function>,
+    pub entry: usize,
+}
+
+// ---------------------------------------
+// Textual dump, --emit=ir
+// ---------------------------------------
+
+impl fmt::Display for VReg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, func) in self.functions.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            let params: Vec<String> = (0..func.params)
+                .map(|p| format!("%{}: {}", p, func.regs[p]))
+                .collect();
+            write!(f, "{}({})", func.name, params.join(", "))?;
+            if let Some(ret) = func.ret {
+                write!(f, ": {}", ret)?;
+            }
+            if func.memory > 0 {
+                write!(f, " memory {}", func.memory)?;
+            }
+            writeln!(f)?;
+            for (b, block) in func.blocks.iter().enumerate() {
+                writeln!(f, "{}:", BlockId(b))?;
+                for inst in block.insts.iter() {
+                    write!(f, "    ")?;
+                    self.fmt_inst(f, func, inst)?;
+                    writeln!(f)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Program {
+    fn name(&self, func: usize) -> &str {
+        self.functions.get(func).map_or("?", |f| f.name.as_str())
+    }
+
+    fn fmt_inst(&self, f: &mut fmt::Formatter<'_>, func: &Function, inst: &Inst) -> fmt::Result {
+        if let Some(dst) = inst.def() {
+            write!(f, "{}: {} = ", dst, func.regs[dst.0 as usize])?;
+        }
+        let list = |regs: &[VReg]| {
+            regs.iter()
+                .map(|r| r.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match inst {
+            Inst::Const { value, .. } => write!(f, "const {}", value),
+            Inst::Copy { src, .. } => write!(f, "copy {}", src),
+            Inst::Unary { op, ty, src, .. } => write!(f, "{} {} {}", unary_name(*op), ty, src),
+            Inst::Binary {
+                op, ty, lhs, rhs, ..
+            } => write!(f, "{} {} {}, {}", binary_name(*op), ty, lhs, rhs),
+            Inst::Convert { from, to, src, .. } => {
+                write!(f, "convert {} -> {} {}", from, to, src)
+            }
+            Inst::Frame { offset, .. } => write!(f, "frame {}", offset),
+            Inst::Offset { base, offset, .. } => write!(f, "offset {}, {}", base, offset),
+            Inst::Index {
+                base,
+                index,
+                size,
+                len,
+                ..
+            } => {
+                write!(f, "index {}, {} * {}", base, index, size)?;
+                match len {
+                    Some(n) => write!(f, ", len {}", n),
+                    None => Ok(()),
+                }
+            }
+            Inst::Load {
+                ty, addr, offset, ..
+            } => write!(f, "load {} {}", ty, at(*addr, *offset)),
+            Inst::Store {
+                ty,
+                addr,
+                offset,
+                src,
+            } => write!(f, "store {} {}, {}", ty, at(*addr, *offset), src),
+            Inst::MemCopy { to, from, size } => write!(f, "memcopy {}, {}, {}", to, from, size),
+            Inst::Call {
+                func: callee, args, ..
+            } => write!(f, "call {}({})", self.name(*callee), list(args)),
+            Inst::TailCall { func: callee, args } => {
+                write!(f, "tailcall {}({})", self.name(*callee), list(args))
+            }
+            Inst::Jump(b) => write!(f, "jump {}", b),
+            Inst::Branch {
+                cond,
+                then,
+                otherwise,
+            } => write!(f, "branch {}, {}, {}", cond, then, otherwise),
+            Inst::Return(Some(v)) => write!(f, "ret {}", v),
+            Inst::Return(None) => write!(f, "ret"),
+            Inst::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
+
+// `[%a]` or `[%a + offset]`
+fn at(addr: VReg, offset: u64) -> String {
+    match offset {
+        0 => format!("[{}]", addr),
+        n => format!("[{} + {}]", addr, n),
+    }
+}
+
+fn unary_name(op: UnaryOp) -> &'static str {
+    match op {
+        UnaryOp::Neg => "neg",
+        UnaryOp::Not => "not",
+        UnaryOp::BitNot => "bitnot",
+        UnaryOp::Deref => "deref",
+    }
+}
+
+fn binary_name(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "add",
+        BinaryOp::Sub => "sub",
+        BinaryOp::Mul => "mul",
+        BinaryOp::Div => "div",
+        BinaryOp::Mod => "mod",
+        BinaryOp::Eq => "eq",
+        BinaryOp::NotEq => "ne",
+        BinaryOp::Lt => "lt",
+        BinaryOp::Gt => "gt",
+        BinaryOp::LtEq => "le",
+        BinaryOp::GtEq => "ge",
+        BinaryOp::Shl => "shl",
+        BinaryOp::Shr => "shr",
+        BinaryOp::BitAnd => "and",
+        BinaryOp::BitXor => "xor",
+        BinaryOp::BitOr => "or",
+        BinaryOp::And => "land",
+        BinaryOp::Or => "lor",
+    }
+}
@@ -0,0 +1,141 @@
+use super::bitset::BitSet;
+use super::types::*;
+use super::worklist::Worklist;
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub struct VerifyError {
+    pub function: String,
+    pub block: BlockId,
+    pub msg: String,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` {}: {}", self.function, self.block, self.msg)
+    }
+}
+
+// Checks what later passes rely on: every block ends in a terminator and has
+// no other, jumps and calls go somewhere that exists, frame addresses are in
+// the function's memory, and every register is defined on every path to
+// each of its uses. Failures are compiler bugs, not mistakes in the program.
+pub fn verify(program: &Program) -> Result<(), Vec<VerifyError>> {
+    let mut errors = vec![];
+    for func in program.functions.iter() {
+        verify_function(program, func, &mut errors);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn verify_function(program: &Program, func: &Function, errors: &mut Vec<VerifyError>) {
+    let mut error = |block: usize, msg: String| {
+        errors.push(VerifyError {
+            function: func.name.clone(),
+            block: BlockId(block),
+            msg,
+        })
+    };
+    if func.blocks.is_empty() {
+        error(0, "function has no blocks".to_string());
+        return;
+    }
+
+    let mut shape_ok = true;
+    for (b, block) in func.blocks.iter().enumerate() {
+        match block.insts.last() {
+            Some(last) if last.is_terminator() => {}
+            _ => {
+                error(b, "block doesn't end in a terminator".to_string());
+                shape_ok = false;
+            }
+        }
+        let n = block.insts.len();
+        for (i, inst) in block.insts.iter().enumerate() {
+            if inst.is_terminator() && i + 1 < n {
+                error(b, format!("terminator in the middle of the block at {}", i));
+            }
+            for s in inst.succs() {
+                if s.0 >= func.blocks.len() {
+                    error(b, format!("jump to missing block {}", s));
+                    shape_ok = false;
+                }
+            }
+            for r in inst.uses().into_iter().chain(inst.def()) {
+                if r.0 as usize >= func.regs.len() {
+                    error(b, format!("{} has no type", r));
+                    shape_ok = false;
+                }
+            }
+            if let Inst::Call { func: callee, .. } | Inst::TailCall { func: callee, .. } = inst
+                && *callee >= program.functions.len()
+            {
+                error(b, format!("call to missing function {}", callee));
+            }
+            if let Inst::Frame { offset, .. } = inst
+                && *offset > func.memory
+            {
+                error(b, format!("frame address {} past the memory", offset));
+            }
+        }
+    }
+    if !shape_ok {
+        return;
+    }
+
+    // Registers defined on every path into each block, only meaningful once
+    // the block is reached. Blocks that can't be reached aren't checked.
+    let regs = func.regs.len();
+    let n = func.blocks.len();
+    let mut defs = vec![BitSet::new(regs); n];
+    for (b, block) in func.blocks.iter().enumerate() {
+        for r in block.insts.iter().filter_map(|inst| inst.def()) {
+            defs[b].insert(r.0 as usize);
+        }
+    }
+    let mut entry = vec![BitSet::new(regs); n];
+    let mut reached = vec![false; n];
+    for p in 0..func.params {
+        entry[0].insert(p);
+    }
+    reached[0] = true;
+    let mut work = Worklist::forward(func);
+    let mut defined = BitSet::new(regs);
+    while let Some(b) = work.pop() {
+        defined.copy_from(&entry[b.0]);
+        defined.union_with(&defs[b.0]);
+        for s in func.succs(b) {
+            let changed = if reached[s.0] {
+                entry[s.0].intersect_with(&defined)
+            } else {
+                reached[s.0] = true;
+                entry[s.0].copy_from(&defined);
+                true
+            };
+            if changed {
+                work.push(s);
+            }
+        }
+    }
+
+    for (b, block) in func.blocks.iter().enumerate() {
+        if !reached[b] {
+            continue;
+        }
+        let defined = &mut entry[b];
+        for inst in block.insts.iter() {
+            for r in inst.uses() {
+                if !defined.contains(r.0 as usize) {
+                    error(b, format!("{} is used before it's defined", r));
+                }
+            }
+            if let Some(d) = inst.def() {
+                defined.insert(d.0 as usize);
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use super::bitset::BitSet;
+use super::types::{BlockId, Function};
+
+// Blocks a dataflow analysis still has to visit. Each is queued at most once
+// and they come out in a fixed order, so a block is usually only visited
+// after everything flowing into it: reverse postorder for forward analyses,
+// postorder for backward ones. Blocks the entry can't reach come last.
+pub struct Worklist {
+    order: Vec<BlockId>,
+    position: Vec<usize>,
+    queued: BitSet,
+}
+
+impl Worklist {
+    // Starts with only the entry block queued
+    pub fn forward(func: &Function) -> Self {
+        let mut order = func.postorder();
+        order.reverse();
+        let mut work = Self::new(func, order);
+        work.push(BlockId(0));
+        work
+    }
+
+    // Starts with every block queued
+    pub fn backward(func: &Function) -> Self {
+        let mut work = Self::new(func, func.postorder());
+        for b in 0..func.blocks.len() {
+            work.push(BlockId(b));
+        }
+        work
+    }
+
+    fn new(func: &Function, mut order: Vec<BlockId>) -> Self {
+        let n = func.blocks.len();
+        let mut position = vec![usize::MAX; n];
+        for (i, b) in order.iter().enumerate() {
+            position[b.0] = i;
+        }
+        for (b, p) in position.iter_mut().enumerate() {
+            if *p == usize::MAX {
+                *p = order.len();
+                order.push(BlockId(b));
+            }
+        }
+        Self {
+            order,
+            position,
+            queued: BitSet::new(n),
+        }
+    }
+
+    pub fn push(&mut self, b: BlockId) {
+        self.queued.insert(self.position[b.0]);
+    }
+
+    pub fn pop(&mut self) -> Option<BlockId> {
+        let i = self.queued.first()?;
+        self.queued.remove(i);
+        Some(self.order[i])
+    }
+}
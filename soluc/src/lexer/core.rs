@@ -1,10 +1,14 @@
 #![allow(unused)]
 use super::*;
+use crate::diagnostics;
 
 #[derive(Debug, Clone)]
 pub struct Lexer<'a> {
-    pub tokens: Vec<Token>,
-    pub cursor: usize,
+    pub tokens: Vec<SpannedToken>,
+    pub errors: Vec<LexerError>,
+    pub start: Span,   // where the token being lexed begins
+    pub cursor: usize, // Byte offset into src
+
     pub line: usize,
     pub line_start: usize,
     pub src: &'a str,
@@ -17,6 +21,13 @@ impl<'a> Lexer<'a> {
     pub fn new(src: &'a str) -> Self {
         Self {
             tokens: vec![],
+            errors: vec![],
+            start: Span {
+                start: 0,
+                end: 0,
+                line: 1,
+                col: 0,
+            },
             src,
             cursor: 0,
             line_start: 0,
@@ -27,18 +38,27 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    pub fn parse(&mut self) -> Result<Vec<Token>, LexerError> {
+    // Lexes the whole source. Lexical errors don't stop the lexer: the bad
+    // lexeme becomes a Special::Invalid token and lexing carries on, so every
+    // error in the file is reported at once.
+    pub fn parse(&mut self) -> Result<Vec<SpannedToken>, Vec<LexerError>> {
         loop {
             self.skip_whitespace();
-            match self.peek() {
-                '_' | 'a'..='z' | 'A'..='Z' => self.lex_indent(),
-                '0'..='9' => self.parse_number()?,
-                c if matches!(c, '(' | ')' | '[' | ']' | '{' | '}') => self.lex_groupings(c)?,
-                c if matches!(c, ':' | ',' | ';' | '\n') => self.lex_delim(c)?,
+            self.start = Span::new(self);
+            let count = self.tokens.len();
+            let res = match self.peek() {
+                '_' | 'a'..='z' | 'A'..='Z' => {
+                    self.lex_indent();
+                    Ok(())
+                }
+                '0'..='9' => self.parse_number().inspect_err(|_| self.skip_number_tail()),
+                c if matches!(c, '(' | ')' | '[' | ']' | '{' | '}') => self.lex_groupings(c),
+                c if matches!(c, ':' | ',' | ';' | '\n') => self.lex_delim(c),
                 c if matches!(c, '"') => self.lex_string_literal(c),
                 c if matches!(
                     c,
                     '.' | '&'
+                        | '?'
                         | '-'
                         | '>'
                         | '<'
@@ -53,31 +73,86 @@ impl<'a> Lexer<'a> {
                         | '!'
                 ) =>
                 {
-                    self.lex_op(c)?
+                    self.lex_op(c)
                 }
                 '\'' => {
                     self.advance();
-                    self.lex_char()?;
+                    self.lex_char().inspect_err(|_| self.skip_char_tail())
                 }
                 '\0' => {
-                    self.tokens.push(Token::Special(Special::Eof));
-                    return Ok(self.tokens.clone());
-                }
-                c => {
-                    // self.tokens
-                    //     .push(Token::Special(Special::Invalid(c.to_string())));
-                    // self.advance()
-                    return Err(LexerError::new(self, "Invalid token"));
+                    self.push(Token::Special(Special::Eof));
+                    if self.errors.is_empty() {
+                        return Ok(self.tokens.clone());
+                    }
+                    return Err(self.errors.clone());
                 }
+                c => Err(LexerError::new(self, format!("Invalid character '{}'", c))
+                    .with_code(diagnostics::INVALID_CHARACTER)),
+            };
+            if let Err(e) = res {
+                self.recover(e, count);
             }
+            self.close_spans(count);
+        }
+    }
+
+    // Replaces whatever the failed lexeme pushed with one Invalid token
+    // covering it and records the error
+    fn recover(&mut self, mut err: LexerError, count: usize) {
+        if self.cursor == self.start.start {
+            self.advance();
+        }
+        self.tokens.truncate(count);
+        let text = self.src[self.start.start..self.cursor].to_string();
+        self.push(Token::Special(Special::Invalid(text)));
+        err.span.end = err.span.end.max(self.cursor);
+        self.errors.push(err);
+    }
+
+    // Consumes the rest of a malformed number (`0x1g2`, `1.2.3`)
+    fn skip_number_tail(&mut self) {
+        while self.peek().is_ascii_alphanumeric()
+            || self.peek() == '_'
+            || (self.peek() == '.' && self.next() != '.')
+        {
+            self.advance();
+        }
+    }
+
+    // Consumes up to and including the closing quote of a bad char literal,
+    // stopping at the end of the line if there isn't one
+    fn skip_char_tail(&mut self) {
+        while !matches!(self.peek(), '\'' | '\n' | '\0') {
+            self.advance();
+        }
+        self.match_char('\'');
+    }
+
+    fn push(&mut self, token: Token) {
+        self.tokens.push(SpannedToken {
+            token,
+            span: self.start,
+        });
+    }
+
+    // Tokens are pushed before their last characters are consumed, so their
+    // spans are closed off at the cursor once the lexeme is finished
+    fn close_spans(&mut self, count: usize) {
+        let end = self.cursor;
+        for t in self.tokens[count..].iter_mut() {
+            t.span.end = end;
         }
     }
 
-    fn lex_string_literal(&mut self, c: char) {
+    fn lex_string_literal(&mut self, c: char) -> Result<(), LexerError> {
         self.advance();
         let mut str_literal = String::new();
         while self.peek() != '"' {
             match self.peek() {
+                '\n' | '\0' => {
+                    return Err(LexerError::new(self, "Unterminated string literal")
+                        .with_code(diagnostics::UNTERMINATED_STRING));
+                }
                 '\\' => {
                     self.advance();
                     match self.peek() {
@@ -111,7 +186,8 @@ impl<'a> Lexer<'a> {
             }
         }
         self.advance();
-        self.tokens.push(Token::Str(str_literal));
+        self.push(Token::Str(str_literal));
+        Ok(())
     }
 
     fn lex_indent(&mut self) {
@@ -122,213 +198,226 @@ impl<'a> Lexer<'a> {
             self.advance();
         }
         match indent.as_str() {
-            "if" => self.tokens.push(Token::Keyword(Keyword::If)),
-            "then" => self.tokens.push(Token::Keyword(Keyword::Then)),
-            "else" => self.tokens.push(Token::Keyword(Keyword::Else)),
-            "elif" => self.tokens.push(Token::Keyword(Keyword::Elif)),
-            "while" => self.tokens.push(Token::Keyword(Keyword::While)),
-            "for" => self.tokens.push(Token::Keyword(Keyword::For)),
-            "in" => self.tokens.push(Token::Keyword(Keyword::In)),
-            "do" => self.tokens.push(Token::Keyword(Keyword::Do)),
-            "with" => self.tokens.push(Token::Keyword(Keyword::With)),
-            "is" => self.tokens.push(Token::Keyword(Keyword::Is)),
-            "end" => self.tokens.push(Token::Keyword(Keyword::End)),
-            "return" => self.tokens.push(Token::Keyword(Keyword::Return)),
-            "break" => self.tokens.push(Token::Keyword(Keyword::Break)),
-            "continue" => self.tokens.push(Token::Keyword(Keyword::Continue)),
-            "switch" => self.tokens.push(Token::Keyword(Keyword::Switch)),
-            "mut" => self.tokens.push(Token::Keyword(Keyword::Mut)),
-            "struct" => self.tokens.push(Token::Keyword(Keyword::Struct)),
-            "enum" => self.tokens.push(Token::Keyword(Keyword::Enum)),
-            "const" => self.tokens.push(Token::Keyword(Keyword::Const)),
-            "type" => self.tokens.push(Token::Keyword(Keyword::Type)),
-            "arena" => self.tokens.push(Token::Keyword(Keyword::Arena)),
-            "defer" => self.tokens.push(Token::Keyword(Keyword::Defer)),
-            "new" => self.tokens.push(Token::Keyword(Keyword::New)),
-
-            "true" => self.tokens.push(Token::Keyword(Keyword::True)),
-            "false" => self.tokens.push(Token::Keyword(Keyword::False)),
-            "nil" => self.tokens.push(Token::Keyword(Keyword::Nil)),
-            "i8" => self.tokens.push(Token::Keyword(Keyword::TypeI8)),
-            "i16" => self.tokens.push(Token::Keyword(Keyword::TypeI16)),
-            "i32" => self.tokens.push(Token::Keyword(Keyword::TypeI32)),
-            "i64" => self.tokens.push(Token::Keyword(Keyword::TypeI64)),
-            "u8" => self.tokens.push(Token::Keyword(Keyword::TypeU8)),
-            "u16" => self.tokens.push(Token::Keyword(Keyword::TypeU16)),
-            "u32" => self.tokens.push(Token::Keyword(Keyword::TypeU32)),
-            "u64" => self.tokens.push(Token::Keyword(Keyword::TypeU64)),
-            "f32" => self.tokens.push(Token::Keyword(Keyword::TypeF32)),
-            "f64" => self.tokens.push(Token::Keyword(Keyword::TypeF64)),
-            "char" => self.tokens.push(Token::Keyword(Keyword::TypeChar)),
-            "bool" => self.tokens.push(Token::Keyword(Keyword::TypeBool)),
-
-            _ => self.tokens.push(Token::Identifier(indent)),
+            "if" => self.push(Token::Keyword(Keyword::If)),
+            "then" => self.push(Token::Keyword(Keyword::Then)),
+            "else" => self.push(Token::Keyword(Keyword::Else)),
+            "elif" => self.push(Token::Keyword(Keyword::Elif)),
+            "while" => self.push(Token::Keyword(Keyword::While)),
+            "for" => self.push(Token::Keyword(Keyword::For)),
+            "in" => self.push(Token::Keyword(Keyword::In)),
+            "do" => self.push(Token::Keyword(Keyword::Do)),
+            "with" => self.push(Token::Keyword(Keyword::With)),
+            "is" => self.push(Token::Keyword(Keyword::Is)),
+            "end" => self.push(Token::Keyword(Keyword::End)),
+            "return" => self.push(Token::Keyword(Keyword::Return)),
+            "break" => self.push(Token::Keyword(Keyword::Break)),
+            "continue" => self.push(Token::Keyword(Keyword::Continue)),
+            "switch" => self.push(Token::Keyword(Keyword::Switch)),
+            "case" => self.push(Token::Keyword(Keyword::Case)),
+            "default" => self.push(Token::Keyword(Keyword::Default)),
+            "mut" => self.push(Token::Keyword(Keyword::Mut)),
+            "struct" => self.push(Token::Keyword(Keyword::Struct)),
+            "enum" => self.push(Token::Keyword(Keyword::Enum)),
+            "const" => self.push(Token::Keyword(Keyword::Const)),
+            "namespace" => self.push(Token::Keyword(Keyword::Namespace)),
+            "type" => self.push(Token::Keyword(Keyword::Type)),
+            "arena" => self.push(Token::Keyword(Keyword::Arena)),
+            "defer" => self.push(Token::Keyword(Keyword::Defer)),
+            "new" => self.push(Token::Keyword(Keyword::New)),
+            "sizeof" => self.push(Token::Keyword(Keyword::Sizeof)),
+            "alignof" => self.push(Token::Keyword(Keyword::Alignof)),
+
+            "true" => self.push(Token::Keyword(Keyword::True)),
+            "false" => self.push(Token::Keyword(Keyword::False)),
+            "nil" => self.push(Token::Keyword(Keyword::Nil)),
+            "i8" => self.push(Token::Keyword(Keyword::TypeI8)),
+            "i16" => self.push(Token::Keyword(Keyword::TypeI16)),
+            "i32" => self.push(Token::Keyword(Keyword::TypeI32)),
+            "i64" => self.push(Token::Keyword(Keyword::TypeI64)),
+            "u8" => self.push(Token::Keyword(Keyword::TypeU8)),
+            "u16" => self.push(Token::Keyword(Keyword::TypeU16)),
+            "u32" => self.push(Token::Keyword(Keyword::TypeU32)),
+            "u64" => self.push(Token::Keyword(Keyword::TypeU64)),
+            "f32" => self.push(Token::Keyword(Keyword::TypeF32)),
+            "f64" => self.push(Token::Keyword(Keyword::TypeF64)),
+            "char" => self.push(Token::Keyword(Keyword::TypeChar)),
+            "bool" => self.push(Token::Keyword(Keyword::TypeBool)),
+
+            _ => self.push(Token::Identifier(indent)),
         }
     }
     fn lex_op(&mut self, c: char) -> Result<(), LexerError> {
         match c {
             '?' => {
-                self.tokens.push(Token::Operator(Operator::Terinary));
+                self.push(Token::Operator(Operator::Terinary));
                 self.advance();
             }
             '+' => {
                 self.advance();
                 match self.peek() {
                     '=' => {
-                        self.tokens.push(Token::Operator(Operator::PlusEq));
+                        self.push(Token::Operator(Operator::PlusEq));
                         self.advance();
                     }
-                    _ => self.tokens.push(Token::Operator(Operator::Plus)),
+                    _ => self.push(Token::Operator(Operator::Plus)),
                 }
             }
             '-' => {
                 self.advance();
                 match self.peek() {
                     '=' => {
-                        self.tokens.push(Token::Operator(Operator::SubEq));
+                        self.push(Token::Operator(Operator::SubEq));
                         self.advance();
                     }
                     '>' => {
-                        self.tokens.push(Token::Operator(Operator::Arrow));
+                        self.push(Token::Operator(Operator::Arrow));
                         self.advance();
                     }
-                    _ => self.tokens.push(Token::Operator(Operator::Minus)),
+                    _ => self.push(Token::Operator(Operator::Minus)),
                 }
             }
             '*' => {
                 self.advance();
                 match self.peek() {
                     '=' => {
-                        self.tokens.push(Token::Operator(Operator::StarEq));
+                        self.push(Token::Operator(Operator::StarEq));
                         self.advance();
                     }
-                    _ => self.tokens.push(Token::Operator(Operator::Star)),
+                    _ => self.push(Token::Operator(Operator::Star)),
                 }
             }
             '/' => {
                 self.advance();
                 match self.peek() {
                     '=' => {
-                        self.tokens.push(Token::Operator(Operator::DivEq));
+                        self.push(Token::Operator(Operator::DivEq));
                         self.advance();
                     }
-                    _ => self.tokens.push(Token::Operator(Operator::Div)),
+                    _ => self.push(Token::Operator(Operator::Div)),
                 }
             }
             '=' => {
                 self.advance();
                 match self.peek() {
                     '=' => {
-                        self.tokens.push(Token::Operator(Operator::EqEq));
+                        self.push(Token::Operator(Operator::EqEq));
                         self.advance();
                     }
                     '>' => {
-                        self.tokens.push(Token::Operator(Operator::FatArrow));
+                        self.push(Token::Operator(Operator::FatArrow));
                         self.advance();
                     }
-                    _ => self.tokens.push(Token::Operator(Operator::Eq)),
+                    _ => self.push(Token::Operator(Operator::Eq)),
                 }
             }
             '>' => {
                 self.advance();
                 match self.peek() {
                     '=' => {
-                        self.tokens.push(Token::Operator(Operator::LtEq));
+                        self.push(Token::Operator(Operator::GtEq));
                         self.advance();
                     }
                     '>' => {
-                        self.tokens.push(Token::Operator(Operator::ShiftR));
+                        self.push(Token::Operator(Operator::ShiftR));
                         self.advance();
                     }
-                    _ => self.tokens.push(Token::Operator(Operator::Gt)),
+                    _ => self.push(Token::Operator(Operator::Gt)),
                 }
             }
             '<' => {
                 self.advance();
                 match self.peek() {
                     '=' => {
-                        self.tokens.push(Token::Operator(Operator::LtEq));
+                        self.push(Token::Operator(Operator::LtEq));
                         self.advance();
                     }
                     '<' => {
-                        self.tokens.push(Token::Operator(Operator::ShiftL));
+                        self.push(Token::Operator(Operator::ShiftL));
                         self.advance();
                     }
                     '-' => {
-                        self.tokens.push(Token::Operator(Operator::ArrowRev));
+                        self.push(Token::Operator(Operator::ArrowRev));
                         self.advance();
                     }
-                    _ => self.tokens.push(Token::Operator(Operator::Lt)),
+                    _ => self.push(Token::Operator(Operator::Lt)),
                 }
             }
             '.' => {
                 self.advance();
                 match self.peek() {
                     '.' => {
-                        self.tokens.push(Token::Operator(Operator::Range));
+                        self.push(Token::Operator(Operator::Range));
                         self.advance();
                     }
-                    _ => self.tokens.push(Token::Operator(Operator::MemberAccessor)),
+                    _ => self.push(Token::Operator(Operator::MemberAccessor)),
                 }
             }
             '|' => {
                 self.advance();
                 match self.peek() {
                     '=' => {
-                        self.tokens.push(Token::Operator(Operator::PipeEq));
+                        self.push(Token::Operator(Operator::PipeEq));
                         self.advance();
                     }
-                    _ => self.tokens.push(Token::Operator(Operator::Pipe)),
+                    '|' => {
+                        self.push(Token::Operator(Operator::LOr));
+                        self.advance();
+                    }
+                    _ => self.push(Token::Operator(Operator::Pipe)),
                 }
             }
             '^' => {
                 self.advance();
                 match self.peek() {
                     '=' => {
-                        self.tokens.push(Token::Operator(Operator::CaretEq));
+                        self.push(Token::Operator(Operator::CaretEq));
                         self.advance();
                     }
-                    _ => self.tokens.push(Token::Operator(Operator::Caret)),
+                    _ => self.push(Token::Operator(Operator::Caret)),
                 }
             }
             '~' => {
                 self.advance();
                 match self.peek() {
                     '=' => {
-                        self.tokens.push(Token::Operator(Operator::TildeEq));
+                        self.push(Token::Operator(Operator::TildeEq));
                         self.advance();
                     }
-                    _ => self.tokens.push(Token::Operator(Operator::Tilde)),
+                    _ => self.push(Token::Operator(Operator::Tilde)),
                 }
             }
             '!' => {
                 self.advance();
                 match self.peek() {
                     '=' => {
-                        self.tokens.push(Token::Operator(Operator::NotEq));
+                        self.push(Token::Operator(Operator::NotEq));
                         self.advance();
                     }
-                    _ => self.tokens.push(Token::Operator(Operator::Not)),
+                    _ => self.push(Token::Operator(Operator::Not)),
                 }
             }
             '%' => {
                 self.advance();
                 match self.peek() {
                     '=' => {
-                        self.tokens.push(Token::Operator(Operator::ModEq));
+                        self.push(Token::Operator(Operator::ModEq));
                         self.advance();
                     }
-                    _ => self.tokens.push(Token::Operator(Operator::Mod)),
+                    _ => self.push(Token::Operator(Operator::Mod)),
                 }
             }
             '&' => {
                 self.advance();
                 match self.peek() {
                     '&' => {
-                        self.tokens.push(Token::Operator(Operator::LAnd));
+                        self.push(Token::Operator(Operator::LAnd));
+                        self.advance();
+                    }
+                    '=' => {
+                        self.push(Token::Operator(Operator::AmpEq));
                         self.advance();
                     }
-                    _ => self.tokens.push(Token::Operator(Operator::Amp)),
+                    _ => self.push(Token::Operator(Operator::Amp)),
                 }
             }
             _ => unreachable!(),
@@ -338,27 +427,18 @@ impl<'a> Lexer<'a> {
 
     fn lex_delim(&mut self, c: char) -> Result<(), LexerError> {
         match c {
-            ':' => self.tokens.push(Token::Delim(Delimeter::Colon)),
-            ',' => self.tokens.push(Token::Delim(Delimeter::Comma)),
-            ';' => self.tokens.push(Token::Delim(Delimeter::Term)),
+            ':' => self.push(Token::Delim(Delimeter::Colon)),
+            ',' => self.push(Token::Delim(Delimeter::Comma)),
+            ';' => self.push(Token::Delim(Delimeter::Term)),
             '\n' => {
-                println!(
-                    "Checking Newline Creds:\n\tbrace depth: ({},{},{})\n\ttoken count: {}\n\tlast token: {:?}",
-                    self.paren_depth,
-                    self.bracket_depth,
-                    self.brace_depth,
-                    self.tokens.len(),
-                    self.tokens.last().unwrap_or(&Token::Special(Special::Eof))
-                );
                 self.advance();
                 self.skip_whitespace();
                 if !self.in_braces()
-                    && self.tokens.len() > 0
-                    && self.tokens.last().unwrap_or(&Token::Special(Special::Eof))
-                        != &Token::Delim(Delimeter::Term)
+                    && !self.tokens.is_empty()
+                    && self.tokens.last().map(|t| &t.token) != Some(&Token::Delim(Delimeter::Term))
                     && self.peek() != '.'
                 {
-                    self.tokens.push(Token::Delim(Delimeter::Term));
+                    self.push(Token::Delim(Delimeter::Term));
                 }
             }
             _ => unreachable!(),
@@ -373,36 +453,39 @@ impl<'a> Lexer<'a> {
         match c {
             '(' => {
                 self.paren_depth = self.paren_depth.wrapping_add(1);
-                self.tokens.push(Token::Delim(Delimeter::Lparen));
+                self.push(Token::Delim(Delimeter::Lparen));
             }
             ')' => {
-                if self.paren_depth <= 0 {
-                    return Err(LexerError::new(self, "Unexpected Rparan"));
+                if self.paren_depth == 0 {
+                    return Err(LexerError::new(self, "Unexpected Rparan")
+                        .with_code(diagnostics::UNBALANCED_DELIMITER));
                 }
                 self.paren_depth = self.paren_depth.wrapping_sub(1);
-                self.tokens.push(Token::Delim(Delimeter::Rparen));
+                self.push(Token::Delim(Delimeter::Rparen));
             }
             '[' => {
                 self.bracket_depth = self.bracket_depth.wrapping_add(1);
-                self.tokens.push(Token::Delim(Delimeter::Lbracket));
+                self.push(Token::Delim(Delimeter::Lbracket));
             }
             ']' => {
-                if self.bracket_depth <= 0 {
-                    return Err(LexerError::new(self, "Unexpected Rbracket"));
+                if self.bracket_depth == 0 {
+                    return Err(LexerError::new(self, "Unexpected Rbracket")
+                        .with_code(diagnostics::UNBALANCED_DELIMITER));
                 }
                 self.bracket_depth = self.bracket_depth.wrapping_sub(1);
-                self.tokens.push(Token::Delim(Delimeter::Rbracket));
+                self.push(Token::Delim(Delimeter::Rbracket));
             }
             '{' => {
                 self.brace_depth = self.brace_depth.wrapping_add(1);
-                self.tokens.push(Token::Delim(Delimeter::Lbrace));
+                self.push(Token::Delim(Delimeter::Lbrace));
             }
             '}' => {
-                if self.brace_depth <= 0 {
-                    return Err(LexerError::new(self, "Unexpected Rbrace"));
+                if self.brace_depth == 0 {
+                    return Err(LexerError::new(self, "Unexpected Rbrace")
+                        .with_code(diagnostics::UNBALANCED_DELIMITER));
                 }
                 self.brace_depth = self.brace_depth.wrapping_sub(1);
-                self.tokens.push(Token::Delim(Delimeter::Rbrace));
+                self.push(Token::Delim(Delimeter::Rbrace));
             }
             _ => unreachable!(),
         }
@@ -410,155 +493,102 @@ impl<'a> Lexer<'a> {
         Ok(())
     }
 
-    fn float_check(&mut self, t: &mut NumLiteralType) -> Result<bool, LexerError> {
-        if self.peek() == '.' {
-            match t {
-                NumLiteralType::Int => *t = NumLiteralType::Float,
-                _ => {
-                    return Err(LexerError::new(self, "Malformed Float"));
-                }
+    // Decimal integers and floats, or an integer in the base its 0x, 0b or 0o
+    // prefix picks
+    fn parse_number(&mut self) -> Result<(), LexerError> {
+        if self.peek() == '0'
+            && let Some((radix, name)) = match self.next() {
+                'x' => Some((16, "Hex")),
+                'b' => Some((2, "Binary")),
+                'o' => Some((8, "Octal")),
+                _ => None,
             }
-            return Ok(true);
+        {
+            return self.parse_prefixed(radix, name);
         }
-        Ok(false)
-    }
-
-    fn hex_check(&mut self, t: &mut NumLiteralType, ctr: usize) -> Result<bool, LexerError> {
-        if self.peek() == 'x' {
-            match t {
-                NumLiteralType::Int if ctr == 1 => *t = NumLiteralType::Hex,
-                _ => {
-                    return Err(LexerError::new(
-                        self,
-                        format!("Malformed Hex: {} ({})", self.peek(), ctr),
-                    ));
+        let start = self.cursor;
+        let mut float = false;
+        loop {
+            match self.peek() {
+                c if c.is_ascii_alphanumeric() => {}
+                // `1..10` is a range, not a float
+                '.' if self.next() != '.' => {
+                    if float {
+                        return Err(LexerError::new(self, "Malformed Float"));
+                    }
+                    float = true;
                 }
+                _ => break,
             }
-            return Ok(true);
+            self.advance();
         }
-        Ok(false)
-    }
-
-    fn bin_check(&mut self, t: &mut NumLiteralType, ctr: usize) -> Result<bool, LexerError> {
-        if self.peek() == 'b' {
-            match t {
-                NumLiteralType::Int if ctr == 1 => *t = NumLiteralType::Binary,
-                _ => {
-                    return Err(LexerError::new(self, "Malformed Binary"));
-                }
+        let t = &self.src[start..self.cursor];
+        let num = if float {
+            t.parse::<f64>()
+                .map(NumLiteral::Float)
+                .map_err(|e| format!("Failed to parse float literal: {}", e))
+        } else {
+            t.parse::<u64>()
+                .map(NumLiteral::Int)
+                .map_err(|e| format!("Failed to parse int literal: {}", e))
+        };
+        match num {
+            Ok(num) => {
+                self.push(Token::Num(num));
+                Ok(())
             }
-            return Ok(true);
+            Err(msg) => Err(LexerError::new(self, msg)),
         }
-        Ok(false)
-    }
-
-    fn number_type_check(
-        &mut self,
-        t: &mut NumLiteralType,
-        ctr: usize,
-    ) -> Result<bool, LexerError> {
-        Ok(self.float_check(t)? || self.hex_check(t, ctr)? || self.bin_check(t, ctr)?)
     }
 
-    fn parse_number(&mut self) -> Result<(), LexerError> {
-        let mut type_state: NumLiteralType = NumLiteralType::Int;
-        let mut ctr = 1;
-        let mut t = String::from(self.peek());
+    // Every letter and digit after the prefix has to be a digit of the base
+    fn parse_prefixed(&mut self, radix: u32, name: &str) -> Result<(), LexerError> {
         self.advance();
-        let mut c = self.peek();
-        while matches!(
-            c,
-            '.' | 'x' | 'a'..='f' | 'A'..='F'
-        ) || self.is_digit()
-        {
-            self.number_type_check(&mut type_state, ctr)?;
-            t.push(c);
-            ctr += 1;
+        self.advance();
+        let start = self.cursor;
+        while self.peek().is_ascii_alphanumeric() {
             self.advance();
-            c = self.peek();
         }
-        match type_state {
-            NumLiteralType::Int => {
-                self.tokens
-                    .push(Token::Num(NumLiteral::Int(match t.parse::<u64>() {
-                        Ok(n) => n,
-                        Err(e) => {
-                            return Err(LexerError::new(
-                                self,
-                                format!("Failed to parse int literal: {}", e),
-                            ));
-                        }
-                    })))
-            }
-            NumLiteralType::Float => {
-                self.tokens
-                    .push(Token::Num(NumLiteral::Float(match t.parse::<f64>() {
-                        Ok(f) => f,
-                        Err(e) => {
-                            return Err(LexerError::new(
-                                self,
-                                format!("Failed to parse float literal: {}", e),
-                            ));
-                        }
-                    })))
-            }
-            NumLiteralType::Hex => self.tokens.push(Token::Num(NumLiteral::Int(
-                match u64::from_str_radix(&t[2..], 16) {
-                    Ok(n) => n,
-                    Err(_) => {
-                        return Err(LexerError::new(
-                            self,
-                            format!("Malformed Hex Value: {}", &t[2..]),
-                        ));
-                    }
-                },
-            ))),
-            NumLiteralType::Binary => self.tokens.push(Token::Num(NumLiteral::Int(
-                match u64::from_str_radix(&t[2..], 2) {
-                    Ok(n) => n,
-                    Err(_) => {
-                        return Err(LexerError::new(self, "Malformed Binary Value"));
-                    }
-                },
-            ))),
-        };
-        Ok(())
+        match u64::from_str_radix(&self.src[start..self.cursor], radix) {
+            Ok(n) => {
+                self.push(Token::Num(NumLiteral::Int(n)));
+                Ok(())
+            }
+            Err(_) => Err(LexerError::new(
+                self,
+                format!(
+                    "Malformed {} Value: {}",
+                    name,
+                    &self.src[self.start.start..self.cursor]
+                ),
+            )),
+        }
     }
 
-    fn peek_offset(&self, offset: isize) -> char {
-        self.src
-            .chars()
-            .nth((self.cursor as isize + offset) as usize)
-            .unwrap()
+    // All cursor helpers slice from the current byte offset, so each look is
+    // O(1) no matter how far into the file the lexer is.
+    fn peek_offset(&self, offset: usize) -> char {
+        self.src[self.cursor..].chars().nth(offset).unwrap_or('\0')
     }
 
     fn prev(&self) -> char {
-        if self.cursor > self.src.len() || self.cursor == 0 {
-            '\0' // end or start of file
-        } else {
-            self.src.chars().nth(self.cursor - 1).unwrap()
-        }
+        self.src[..self.cursor].chars().next_back().unwrap_or('\0')
     }
 
     fn peek(&self) -> char {
-        if self.cursor >= self.src.len() {
-            '\0' // End of file
-        } else {
-            self.src.chars().nth(self.cursor).unwrap()
-        }
+        self.src[self.cursor..].chars().next().unwrap_or('\0')
     }
 
     fn next(&self) -> char {
-        if (self.cursor + 1) >= self.src.len() {
-            '\0'
-        } else {
-            self.src.chars().nth(self.cursor + 1).unwrap()
-        }
+        self.peek_offset(1)
     }
 
     fn advance(&mut self) {
         let c = self.peek();
-        self.cursor += 1;
+        if c == '\0' && self.cursor >= self.src.len() {
+            return;
+        }
+        self.cursor += c.len_utf8();
         if c == '\n' {
             self.line += 1;
             self.line_start = self.cursor;
@@ -566,28 +596,25 @@ impl<'a> Lexer<'a> {
     }
 
     fn skip_whitespace(&mut self) {
-        match self.peek() {
-            '\r' | ' ' | '\t' => {
-                self.advance();
-                self.skip_whitespace();
-            }
-            '-' if self.peek_offset(1) == '-' => {
-                while self.peek() != '\n' {
-                    self.advance();
+        loop {
+            match self.peek() {
+                '\r' | ' ' | '\t' => self.advance(),
+                '-' if self.next() == '-' => {
+                    while !matches!(self.peek(), '\n' | '\0') {
+                        self.advance();
+                    }
                 }
+                _ => return,
             }
-            _ => {}
         }
     }
 
     fn is_digit(&self) -> bool {
-        let c = self.peek();
-        c >= '0' && c <= '9'
+        self.peek().is_ascii_digit()
     }
 
     fn is_alpha(&self) -> bool {
-        let c = self.peek();
-        (c >= 'A' && c <= 'Z') || (c >= 'a' && c <= 'z')
+        self.peek().is_ascii_alphabetic()
     }
 
     fn is_alphanumeric(&self) -> bool {
@@ -604,7 +631,7 @@ impl<'a> Lexer<'a> {
                 }
             }
             c if c != '\'' && c != '\n' => {
-                self.tokens.push(Token::Char(c));
+                self.push(Token::Char(c));
                 self.advance();
                 if !self.match_char('\'') {
                     return Err(LexerError::new(self, "Improper Character found"));
@@ -621,28 +648,28 @@ impl<'a> Lexer<'a> {
         match self.peek() {
             'n' => {
                 self.advance();
-                self.tokens.push(Token::Char('\n'));
-                return Ok(());
+                self.push(Token::Char('\n'));
+                Ok(())
             }
             't' => {
                 self.advance();
-                self.tokens.push(Token::Char('\t'));
-                return Ok(());
+                self.push(Token::Char('\t'));
+                Ok(())
             }
             'r' => {
                 self.advance();
-                self.tokens.push(Token::Char('\r'));
-                return Ok(());
+                self.push(Token::Char('\r'));
+                Ok(())
             }
             '\\' => {
                 self.advance();
-                self.tokens.push(Token::Char('\\'));
-                return Ok(());
+                self.push(Token::Char('\\'));
+                Ok(())
             }
             '\'' => {
                 self.advance();
-                self.tokens.push(Token::Char('\''));
-                return Ok(());
+                self.push(Token::Char('\''));
+                Ok(())
             }
             'u' => self.lex_unicode_escape(4),
             'U' => self.lex_unicode_escape(8),
@@ -658,16 +685,16 @@ impl<'a> Lexer<'a> {
         if c == expected {
             self.advance();
         }
-        return c == expected;
+        c == expected
     }
 
     fn expected_char(&mut self, expected: char) -> Result<(), LexerError> {
         let c = self.peek();
         if c == expected {
             self.advance();
-            return Ok(());
+            Ok(())
         } else {
-            return Err(LexerError::new(self, format!("Unexpected token ({})", c)));
+            Err(LexerError::new(self, format!("Unexpected token ({})", c)))
         }
     }
 
@@ -688,13 +715,13 @@ impl<'a> Lexer<'a> {
             self.advance();
         }
         if let Some(c) = char::from_u32(value) {
-            self.tokens.push(Token::Char(c));
-            return Ok(());
+            self.push(Token::Char(c));
+            Ok(())
         } else {
-            return Err(LexerError::new(
+            Err(LexerError::new(
                 self,
                 format!("Invalid Char Value: {}", value),
-            ));
+            ))
         }
     }
 
@@ -703,18 +730,10 @@ impl<'a> Lexer<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-enum NumLiteralType {
-    Int,
-    Float,
-    Hex,
-    Binary,
-}
-
 pub fn is_digit(c: char) -> bool {
-    c >= '0' && c <= '9'
+    c.is_ascii_digit()
 }
 
 pub fn is_alpha(c: char) -> bool {
-    (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_'
+    c.is_ascii_alphabetic() || c == '_'
 }
@@ -1,18 +1,37 @@
 use super::core::Lexer;
+use crate::diagnostics;
 use std::fmt;
 
+#[derive(Debug, Clone)]
 pub struct LexerError {
     pub span: Span,
     pub msg: String,
+    pub code: &'static str,
 }
 
 impl LexerError {
+    // Covers the token being lexed up to the cursor, or at least the
+    // character under the cursor
     pub fn new(lex: &Lexer, msg: impl Into<String>) -> Self {
         Self {
-            span: Span::new(lex),
+            span: Span {
+                end: lex.cursor.max(
+                    lex.start.start
+                        + lex.src[lex.cursor..]
+                            .chars()
+                            .next()
+                            .map_or(0, char::len_utf8),
+                ),
+                ..lex.start
+            },
             msg: msg.into(),
+            code: diagnostics::LEX_ERROR,
         }
     }
+
+    pub fn with_code(self, code: &'static str) -> Self {
+        Self { code, ..self }
+    }
 }
 
 // Used to track where lexer is in file for error messaging
@@ -34,6 +53,14 @@ impl Span {
         }
     }
 
+    // Span covering self through the end of other
+    pub fn to(&self, other: Span) -> Span {
+        Span {
+            end: other.end.max(self.end),
+            ..*self
+        }
+    }
+
     pub fn text<'a>(&self, src: &'a str) -> &'a str {
         &src[self.start..self.end]
     }
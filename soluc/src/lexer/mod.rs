@@ -2,6 +2,9 @@ mod core;
 mod error;
 mod token;
 
+#[cfg(test)]
+mod tests;
+
 pub use core::*;
 pub use error::*;
 pub use token::*;
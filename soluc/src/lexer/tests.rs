@@ -0,0 +1,129 @@
+use super::*;
+
+fn lex(src: &str) -> Vec<SpannedToken> {
+    Lexer::new(src)
+        .parse()
+        .unwrap_or_else(|e| panic!("lexer errors in {:?}: {:?}", src, e))
+}
+
+fn tokens(src: &str) -> Vec<Token> {
+    lex(src).into_iter().map(|t| t.token).collect()
+}
+
+// Line, column and message of each error
+fn errors(src: &str) -> Vec<(usize, usize, String)> {
+    match Lexer::new(src).parse() {
+        Ok(_) => panic!("{:?} lexed", src),
+        Err(errors) => errors
+            .iter()
+            .map(|e| (e.span.line, e.span.col, e.msg.clone()))
+            .collect(),
+    }
+}
+
+fn int(src: &str) -> u64 {
+    match tokens(src)[..] {
+        [Token::Num(NumLiteral::Int(n)), Token::Special(Special::Eof)] => n,
+        ref t => panic!("{:?} lexed to {:?}", src, t),
+    }
+}
+
+#[test]
+fn number_bases() {
+    assert_eq!(int("42"), 42);
+    assert_eq!(int("0xab"), 0xab);
+    assert_eq!(int("0xFF"), 255);
+    assert_eq!(int("0xb0"), 0xb0);
+    assert_eq!(int("0b1011"), 11);
+    assert_eq!(int("0o17"), 15);
+    assert_eq!(int("0"), 0);
+    assert_eq!(tokens("1.5")[0], Token::Num(NumLiteral::Float(1.5)));
+}
+
+#[test]
+fn malformed_numbers() {
+    for (src, msg) in [
+        ("0x", "Malformed Hex Value: 0x"),
+        ("0xfg", "Malformed Hex Value: 0xfg"),
+        ("0b", "Malformed Binary Value: 0b"),
+        ("0b102", "Malformed Binary Value: 0b102"),
+        ("0o", "Malformed Octal Value: 0o"),
+        ("0o78", "Malformed Octal Value: 0o78"),
+        ("1.2.3", "Malformed Float"),
+        (
+            "12ab",
+            "Failed to parse int literal: invalid digit found in string",
+        ),
+        (
+            "99999999999999999999",
+            "Failed to parse int literal: number too large to fit in target type",
+        ),
+    ] {
+        assert_eq!(errors(src), [(1, 0, msg.to_string())], "{}", src);
+    }
+}
+
+// A range's `..` isn't taken for a decimal point
+#[test]
+fn range_after_number() {
+    assert_eq!(
+        tokens("0..10"),
+        [
+            Token::Num(NumLiteral::Int(0)),
+            Token::Operator(Operator::Range),
+            Token::Num(NumLiteral::Int(10)),
+            Token::Special(Special::Eof),
+        ]
+    );
+}
+
+// Spans are byte ranges into the source, columns count from the line start.
+// A newline runs on through the next line's indentation.
+#[test]
+fn token_spans() {
+    let spans: Vec<(usize, usize, usize, usize)> = lex("x: i32 = 0xab\n  y += 1.5")
+        .iter()
+        .map(|t| (t.span.start, t.span.end, t.span.line, t.span.col))
+        .collect();
+    assert_eq!(
+        spans,
+        [
+            (0, 1, 1, 0),
+            (1, 2, 1, 1),
+            (3, 6, 1, 3),
+            (7, 8, 1, 7),
+            (9, 13, 1, 9),
+            (13, 16, 1, 13),
+            (16, 17, 2, 2),
+            (18, 20, 2, 4),
+            (21, 24, 2, 7),
+            (24, 24, 2, 10),
+        ]
+    );
+}
+
+// Multibyte characters move the cursor by their length in bytes
+#[test]
+fn multibyte_source() {
+    let toks = lex("s = \"héllo\" -- ç\ny = 'é'");
+    let spanned: Vec<(Token, usize, usize)> = toks
+        .into_iter()
+        .map(|t| (t.token, t.span.start, t.span.end))
+        .collect();
+    assert_eq!(spanned[2], (Token::Str("héllo".to_string()), 4, 12));
+    assert_eq!(spanned[6], (Token::Char('é'), 23, 27));
+}
+
+// Every error in the file is reported, each at its own position
+#[test]
+fn recovers_from_errors() {
+    assert_eq!(
+        errors("a = 0b12 + $\nb = 1.2.3 + 4\nc = \"open"),
+        [
+            (1, 4, "Malformed Binary Value: 0b12".to_string()),
+            (1, 11, "Invalid character '$'".to_string()),
+            (2, 4, "Malformed Float".to_string()),
+            (3, 4, "Unterminated string literal".to_string()),
+        ]
+    );
+}
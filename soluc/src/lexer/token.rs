@@ -1,5 +1,13 @@
+use super::Span;
 use std::fmt;
 
+// A token along with the range of source it was lexed from
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum Token {
     Identifier(String),
@@ -56,6 +64,8 @@ pub enum Keyword {
     Break,
     Continue,
     Switch,
+    Case,
+    Default,
     // Declarations
     Mut,
     Struct,
@@ -67,6 +77,9 @@ pub enum Keyword {
     Arena,
     Defer,
     New,
+    // Builtins
+    Sizeof,
+    Alignof,
     // Literals
     True,
     False,
@@ -158,6 +171,8 @@ impl fmt::Display for Token {
                 Keyword::Break => write!(f, "BREAK"),
                 Keyword::Continue => write!(f, "CONTINUE"),
                 Keyword::Switch => write!(f, "SWITCH"),
+                Keyword::Case => write!(f, "CASE"),
+                Keyword::Default => write!(f, "DEFAULT"),
                 Keyword::Mut => write!(f, "MUT"),
                 Keyword::Struct => write!(f, "STRUCT"),
                 Keyword::Enum => write!(f, "ENUM"),
@@ -167,6 +182,8 @@ impl fmt::Display for Token {
                 Keyword::Arena => write!(f, "ARENA"),
                 Keyword::Defer => write!(f, "DEFER"),
                 Keyword::New => write!(f, "NEW"),
+                Keyword::Sizeof => write!(f, "SIZEOF"),
+                Keyword::Alignof => write!(f, "ALIGNOF"),
                 Keyword::True => write!(f, "TRUE"),
                 Keyword::False => write!(f, "FALSE"),
                 Keyword::Nil => write!(f, "NIL"),
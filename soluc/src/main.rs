@@ -1,5 +1,10 @@
+pub mod codegen;
+pub mod diagnostics;
+pub mod ir;
 pub mod lexer;
+pub mod opt;
 pub mod parser;
+pub mod sema;
 
 use std::env;
 use std::process::ExitCode;
@@ -12,7 +17,21 @@ fn main() -> ExitCode {
         eprintln!("Usage: dnc <file.dn> [options]");
         eprintln!();
         eprintln!("Options:");
-        eprintln!("  --tokens    Print tokens only (don't compile)");
+        eprintln!("  --print     Print tokens only (don't compile)");
+        eprintln!("  --ast       Print the parsed AST (don't compile)");
+        eprintln!("  -o <file>   Write the bytecode to <file> (default <file.dn> with .dvm)");
+        eprintln!("  -O          Optimize the IR before generating bytecode");
+        eprintln!("  --skip=<pass,...>");
+        eprintln!("              Leave out optimization passes: fold, copy-prop, dce,");
+        eprintln!("              jump-threading, inline, tail-calls");
+        eprintln!("  --emit=<bytecode|ir>");
+        eprintln!("              What to output, ir prints the IR instead (default bytecode)");
+        eprintln!("  --color=<auto|always|never>");
+        eprintln!("              Color diagnostics (default auto)");
+        eprintln!("  --error-format=<human|json>");
+        eprintln!("              Diagnostic output format (default human)");
+        eprintln!("  --allow=<code,...|warnings>");
+        eprintln!("              Don't report these warnings");
         eprintln!("  --help      Show this help");
         return ExitCode::FAILURE;
     }
@@ -25,6 +44,73 @@ fn main() -> ExitCode {
         return ExitCode::SUCCESS;
     }
 
+    let output = match args.iter().position(|arg| arg == "-o") {
+        Some(i) => match args.get(i + 1) {
+            Some(out) => out.clone(),
+            None => {
+                eprintln!("Error: -o needs a file name");
+                return ExitCode::FAILURE;
+            }
+        },
+        None => std::path::Path::new(&filename)
+            .with_extension("dvm")
+            .to_string_lossy()
+            .into_owned(),
+    };
+
+    let emit_ir = match args.iter().find_map(|arg| arg.strip_prefix("--emit=")) {
+        Some("ir") => true,
+        Some("bytecode") | None => false,
+        Some(kind) => {
+            eprintln!("Error: unknown emit kind {}", kind);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut passes = match args.iter().any(|arg| arg == "-O") {
+        true => opt::Passes::all(),
+        false => opt::Passes::none(),
+    };
+    for name in args
+        .iter()
+        .filter_map(|arg| arg.strip_prefix("--skip="))
+        .flat_map(|names| names.split(','))
+    {
+        if !passes.set(name, false) {
+            eprintln!(
+                "Error: unknown pass {}, expected one of {}",
+                name,
+                opt::Passes::NAMES.join(", ")
+            );
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let color = match args.iter().find_map(|arg| arg.strip_prefix("--color=")) {
+        Some(mode) => match diagnostics::ColorMode::parse(mode) {
+            Some(c) => c,
+            None => {
+                eprintln!("Error: unknown color mode {}", mode);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => diagnostics::ColorMode::Auto,
+    };
+
+    let format = match args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--error-format="))
+    {
+        Some(f) => match diagnostics::ErrorFormat::parse(f) {
+            Some(f) => f,
+            None => {
+                eprintln!("Error: unknown error format {}", f);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => diagnostics::ErrorFormat::Human,
+    };
+
     let code = match std::fs::read_to_string(&filename) {
         Ok(s) => s,
         Err(e) => {
@@ -33,30 +119,117 @@ fn main() -> ExitCode {
         }
     };
 
+    let allow: Vec<String> = args
+        .iter()
+        .filter_map(|arg| arg.strip_prefix("--allow="))
+        .flat_map(|codes| codes.split(','))
+        .map(String::from)
+        .collect();
+
+    let emitter = diagnostics::Emitter::new(&filename, &code, format, color.enabled()).allow(allow);
+
     let mut lex = lexer::Lexer::new(&code);
 
     let tokens = match lex.parse() {
         Ok(t) => t,
-        Err(e) => {
-            eprintln!(
-                "{}:{}:{}: Error: {}",
-                filename, e.span.line, e.span.col, e.msg
-            );
+        Err(errors) => {
+            let diags: Vec<_> = errors.iter().map(diagnostics::Diagnostic::from).collect();
+            emitter.emit(&diags);
             return ExitCode::FAILURE;
         }
     };
 
     if args.iter().any(|arg| arg == "--print") {
         for token in tokens.iter() {
-            if *token != crate::lexer::Token::Delim(crate::lexer::Delimeter::Term) {
-                print!("{}  ", token);
+            if token.token != crate::lexer::Token::Delim(crate::lexer::Delimeter::Term) {
+                print!("{}  ", token.token);
             } else {
-                println!("{}", token);
+                println!("{}", token.token);
             }
         }
         return ExitCode::SUCCESS;
     }
 
-    println!("Compiled file: {}", filename);
-    return ExitCode::SUCCESS;
+    let mut par = parser::Parser::new(tokens);
+    let ast = match par.parse() {
+        Ok(a) => a,
+        Err(errors) => {
+            let diags: Vec<_> = errors.iter().map(diagnostics::Diagnostic::from).collect();
+            emitter.emit(&diags);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if args.iter().any(|arg| arg == "--ast") {
+        println!("{:#?}", ast);
+        return ExitCode::SUCCESS;
+    }
+
+    // Later passes only run on programs the earlier ones accepted so one
+    // mistake doesn't cascade into a page of errors
+    let (symbols, diags) = sema::resolve(&ast);
+    emitter.emit(&diags);
+    if diags.iter().any(|d| d.is_error()) {
+        return ExitCode::FAILURE;
+    }
+
+    let (types, diags) = sema::check(&ast, &symbols);
+    emitter.emit(&diags);
+    if diags.iter().any(|d| d.is_error()) {
+        return ExitCode::FAILURE;
+    }
+
+    let diags = sema::check_mutability(&ast, &symbols, &types);
+    emitter.emit(&diags);
+    if diags.iter().any(|d| d.is_error()) {
+        return ExitCode::FAILURE;
+    }
+
+    let diags = sema::check_flow(&ast, &types);
+    emitter.emit(&diags);
+    if diags.iter().any(|d| d.is_error()) {
+        return ExitCode::FAILURE;
+    }
+
+    let diags = sema::check_init(&ast, &types);
+    emitter.emit(&diags);
+    if diags.iter().any(|d| d.is_error()) {
+        return ExitCode::FAILURE;
+    }
+
+    let (program, diags) = ir::lower(&ast, &symbols, &types);
+    emitter.emit(&diags);
+    if diags.iter().any(|d| d.is_error()) {
+        return ExitCode::FAILURE;
+    }
+
+    let mut program = program;
+    opt::optimize(&mut program, passes);
+
+    // A program that fails verification is a compiler bug
+    if let Err(errors) = ir::verify(&program) {
+        for e in errors.iter() {
+            eprintln!("internal error: invalid IR in {}", e);
+        }
+        return ExitCode::FAILURE;
+    }
+
+    if emit_ir {
+        print!("{}", program);
+        return ExitCode::SUCCESS;
+    }
+
+    let (image, diags) = codegen::generate(&program);
+    emitter.emit(&diags);
+    if diags.iter().any(|d| d.is_error()) {
+        return ExitCode::FAILURE;
+    }
+
+    if let Err(e) = std::fs::write(&output, image.to_bytes()) {
+        eprintln!("Error: {}, couldn't write {}", e, output);
+        return ExitCode::FAILURE;
+    }
+
+    println!("Compiled file: {} -> {}", filename, output);
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,48 @@
+use super::facts::{Facts, facts_on_entry};
+use crate::ir::{Function, Inst, VReg};
+
+// Copy propagation. Reads of a copy's destination read its source instead
+// while both still hold the same value, which often leaves the copy dead for
+// dead code elimination. Copies of a register to itself are dropped.
+pub fn propagate_copies(func: &mut Function) -> bool {
+    let entry = facts_on_entry(func, transfer, |b, _| func.succs(b));
+    let mut changed = false;
+    for (b, copies) in entry.iter().enumerate() {
+        let Some(copies) = copies else {
+            continue;
+        };
+        let mut copies = Facts::new(copies);
+        let block = &mut func.blocks[b];
+        for inst in block.insts.iter_mut() {
+            inst.rename_uses(|r| match copies.get(r) {
+                Some(&src) => {
+                    changed = true;
+                    src
+                }
+                None => r,
+            });
+            transfer(inst, &mut copies);
+        }
+        let before = block.insts.len();
+        block
+            .insts
+            .retain(|inst| !matches!(inst, Inst::Copy { dst, src } if dst == src));
+        changed |= block.insts.len() != before;
+    }
+    changed
+}
+
+// A copy still valid at a point is a fact about its destination, the
+// source, for copies done on every path that neither register was written
+// on since. Writing a register ends every copy to or from it.
+fn transfer(inst: &Inst, copies: &mut Facts<VReg>) {
+    let Some(dst) = inst.def() else {
+        return;
+    };
+    let src = match inst {
+        // The source may itself be a copy still valid here
+        Inst::Copy { src, .. } => Some(copies.get(*src).copied().unwrap_or(*src)),
+        _ => None,
+    };
+    copies.set(dst, src.filter(|src| *src != dst));
+}
@@ -0,0 +1,54 @@
+use crate::ir::{BlockId, Function, Inst, needed, strongly_live_out, transfer};
+
+// Dead code elimination. Blocks that can't be reached from the entry are
+// removed, then every instruction whose result is never read by one that's
+// needed. Calls stay for their side effects, only the unused result is
+// dropped.
+pub fn eliminate_dead_code(func: &mut Function) -> bool {
+    let mut changed = remove_unreachable(func);
+    let mut live_out = strongly_live_out(func);
+    for (b, block) in func.blocks.iter_mut().enumerate() {
+        let live = &mut live_out[b];
+        let mut keep = vec![true; block.insts.len()];
+        for (i, inst) in block.insts.iter_mut().enumerate().rev() {
+            if !needed(inst, live) {
+                keep[i] = false;
+                changed = true;
+                continue;
+            }
+            if let Inst::Call { dst, .. } = inst
+                && let Some(d) = *dst
+                && !live.contains(d.0 as usize)
+            {
+                *dst = None;
+                changed = true;
+            }
+            transfer(inst, live);
+        }
+        let mut keep = keep.into_iter();
+        block.insts.retain(|_| keep.next().unwrap());
+    }
+    changed
+}
+fn remove_unreachable(func: &mut Function) -> bool {
+    let mut reached = vec![false; func.blocks.len()];
+    reached[0] = true;
+    let mut work = vec![BlockId(0)];
+    while let Some(b) = work.pop() {
+        for s in func.succs(b) {
+            if !reached[s.0] {
+                reached[s.0] = true;
+                work.push(s);
+            }
+        }
+    }
+    if reached.iter().all(|r| *r) {
+        return false;
+    }
+    let order: Vec<BlockId> = (0..func.blocks.len())
+        .filter(|b| reached[*b])
+        .map(BlockId)
+        .collect();
+    func.reorder(&order);
+    true
+}
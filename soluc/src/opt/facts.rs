@@ -0,0 +1,136 @@
+use crate::ir::{BlockId, Function, Inst, VReg, Worklist};
+use crate::sema::Value;
+use std::collections::HashMap;
+
+// Something known about a register's value. It can depend on another
+// register, like a copy on its source, and then stops holding when that one
+// is written as well.
+pub trait Fact: Clone + PartialEq {
+    fn depends_on(&self) -> Option<VReg> {
+        None
+    }
+}
+
+impl Fact for Value {}
+
+// A register copied from this one
+impl Fact for VReg {
+    fn depends_on(&self) -> Option<VReg> {
+        Some(*self)
+    }
+}
+
+// The facts at a point in a block: the ones on entry that nothing since
+// undid, and those the block's own instructions established. Entry facts
+// are only borrowed, so walking a block doesn't copy them.
+pub struct Facts<'a, T> {
+    entry: &'a HashMap<VReg, T>,
+    // With the time of the write that established them
+    local: HashMap<VReg, (T, usize)>,
+    // Time of the last write of each register in the block
+    written: HashMap<VReg, usize>,
+    time: usize,
+}
+
+impl<'a, T: Fact> Facts<'a, T> {
+    pub fn new(entry: &'a HashMap<VReg, T>) -> Self {
+        Self {
+            entry,
+            local: HashMap::new(),
+            written: HashMap::new(),
+            time: 0,
+        }
+    }
+
+    pub fn get(&self, r: VReg) -> Option<&T> {
+        let (fact, since) = match self.local.get(&r) {
+            Some((fact, t)) => (fact, *t),
+            None if self.written.contains_key(&r) => return None,
+            None => (self.entry.get(&r)?, 0),
+        };
+        match fact.depends_on() {
+            Some(d) if self.written.get(&d).is_some_and(|&t| t > since) => None,
+            _ => Some(fact),
+        }
+    }
+
+    // A write of `r`, with what's known about the value written
+    pub fn set(&mut self, r: VReg, fact: Option<T>) {
+        self.time += 1;
+        self.written.insert(r, self.time);
+        match fact {
+            Some(fact) => self.local.insert(r, (fact, self.time)),
+            None => self.local.remove(&r),
+        };
+    }
+
+    fn to_map(&self) -> HashMap<VReg, T> {
+        self.entry
+            .keys()
+            .chain(self.local.keys())
+            .filter_map(|r| Some((*r, self.get(*r)?.clone())))
+            .collect()
+    }
+}
+
+// What a path into a block changes about its entry facts
+enum Update<T> {
+    // The first path there, bringing all of these
+    First(HashMap<VReg, T>),
+    // Facts the path doesn't bring, which no longer hold
+    Lost(Vec<VReg>),
+}
+
+// Facts holding on entry to each block on every path there, None for blocks
+// never reached. Blocks are visited in reverse postorder, and a block's
+// facts are only narrowed in place when a path brings fewer of them.
+// `transfer` walks an instruction, `succs` picks the blocks the facts at the
+// end of a block flow to.
+pub fn facts_on_entry<T: Fact>(
+    func: &Function,
+    transfer: impl Fn(&Inst, &mut Facts<T>),
+    succs: impl Fn(BlockId, &Facts<T>) -> Vec<BlockId>,
+) -> Vec<Option<HashMap<VReg, T>>> {
+    let mut entry: Vec<Option<HashMap<VReg, T>>> = vec![None; func.blocks.len()];
+    entry[0] = Some(HashMap::new());
+    let mut work = Worklist::forward(func);
+    while let Some(b) = work.pop() {
+        let Some(start) = &entry[b.0] else {
+            continue;
+        };
+        let mut facts = Facts::new(start);
+        for inst in func.blocks[b.0].insts.iter() {
+            transfer(inst, &mut facts);
+        }
+        let updates: Vec<(BlockId, Update<T>)> = succs(b, &facts)
+            .into_iter()
+            .map(|s| match &entry[s.0] {
+                None => (s, Update::First(facts.to_map())),
+                Some(known) => (
+                    s,
+                    Update::Lost(
+                        known
+                            .iter()
+                            .filter(|(r, fact)| facts.get(**r) != Some(*fact))
+                            .map(|(r, _)| *r)
+                            .collect(),
+                    ),
+                ),
+            })
+            .collect();
+        for (s, update) in updates {
+            match update {
+                Update::First(known) => entry[s.0] = Some(known),
+                Update::Lost(lost) if lost.is_empty() => continue,
+                Update::Lost(lost) => {
+                    let known = entry[s.0].as_mut().unwrap();
+                    for r in lost {
+                        known.remove(&r);
+                    }
+                }
+            }
+            work.push(s);
+        }
+    }
+    entry
+}
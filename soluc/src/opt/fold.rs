@@ -0,0 +1,97 @@
+use super::facts::{Facts, facts_on_entry};
+use crate::ir::{BlockId, Function, Inst, VReg};
+use crate::sema::{Value, eval_binary, eval_cast, eval_unary};
+use std::collections::HashMap;
+
+type Known = HashMap<VReg, Value>;
+
+// Constant folding. Registers holding the same constant on every path into a
+// block are tracked through it, and instructions whose operands are all
+// known become constants. Values are computed like constant expressions, so
+// anything that would overflow or divide by zero is left to run. A branch on
+// a known condition becomes a jump.
+pub fn fold_constants(func: &mut Function) -> bool {
+    let entry = known_on_entry(func);
+    let mut changed = false;
+    for (b, known) in entry.iter().enumerate() {
+        // Blocks never reached keep their code for dead code elimination
+        let Some(known) = known else {
+            continue;
+        };
+        let mut known = Facts::new(known);
+        for inst in func.blocks[b].insts.iter_mut() {
+            transfer(inst, &mut known);
+            if let Some(dst) = inst.def()
+                && let Some(value) = known.get(dst)
+                && !matches!(inst, Inst::Const { .. })
+            {
+                *inst = Inst::Const {
+                    dst,
+                    value: value.clone(),
+                };
+                changed = true;
+            }
+            if let Some(target) = taken(inst, &known) {
+                *inst = Inst::Jump(target);
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+// Constants known at the start of each block, None for blocks that can't be
+// reached. Only the side a branch on a known condition takes is followed,
+// so what the other side writes doesn't hide a constant where they meet.
+fn known_on_entry(func: &Function) -> Vec<Option<Known>> {
+    facts_on_entry(func, transfer, |b, known| {
+        match func.blocks[b.0].insts.last().and_then(|i| taken(i, known)) {
+            Some(target) => vec![target],
+            None => func.succs(b),
+        }
+    })
+}
+
+fn transfer(inst: &Inst, known: &mut Facts<Value>) {
+    if let Some(dst) = inst.def() {
+        let value = eval(inst, known);
+        known.set(dst, value);
+    }
+}
+
+// Where a branch on a known condition goes
+fn taken(inst: &Inst, known: &Facts<Value>) -> Option<BlockId> {
+    match *inst {
+        Inst::Branch {
+            cond,
+            then,
+            otherwise,
+        } => Some(if truth(known.get(cond)?)? {
+            then
+        } else {
+            otherwise
+        }),
+        _ => None,
+    }
+}
+
+// Value `inst` writes when its operands are `known`
+fn eval(inst: &Inst, known: &Facts<Value>) -> Option<Value> {
+    let get = |r: &VReg| known.get(*r).cloned();
+    match inst {
+        Inst::Const { value, .. } => Some(value.clone()),
+        Inst::Copy { src, .. } => get(src),
+        Inst::Unary { op, src, .. } => eval_unary(*op, get(src)?).ok(),
+        Inst::Binary { op, lhs, rhs, .. } => eval_binary(*op, get(lhs)?, get(rhs)?).ok(),
+        Inst::Convert { to, src, .. } => eval_cast(get(src)?, *to).ok(),
+        _ => None,
+    }
+}
+
+fn truth(v: &Value) -> Option<bool> {
+    match v {
+        Value::Bool(b) => Some(*b),
+        Value::Int(n, _) => Some(*n != 0),
+        _ => None,
+    }
+}
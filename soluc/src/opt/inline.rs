@@ -0,0 +1,164 @@
+use crate::ir::{BlockId, Function, Inst, Program, VReg};
+
+// Callees with at most this many instructions are inlined
+const INLINE_SIZE: usize = 16;
+
+// Inlining. Calls of small functions are replaced by a copy of the callee's
+// body, saving the `Call`, the `Ret` and a new frame. Callees are inlined as
+// they were before this pass, one level deep, and functions that call
+// themselves never are. Functions no longer called from `main` are removed.
+pub fn inline_calls(program: &mut Program) -> bool {
+    let callees = program.functions.clone();
+    let small: Vec<bool> = callees
+        .iter()
+        .enumerate()
+        .map(|(i, f)| size(f) <= INLINE_SIZE && !calls(f, i))
+        .collect();
+
+    let mut changed = false;
+    for (i, func) in program.functions.iter_mut().enumerate() {
+        let mut order: Vec<BlockId> = (0..func.blocks.len()).map(BlockId).collect();
+        // Blocks copied from a callee aren't searched for calls, only the
+        // caller's own and the rest of them after an inlined call
+        let mut search: Vec<bool> = vec![true; func.blocks.len()];
+        let mut b = 0;
+        while b < func.blocks.len() {
+            if !search[b] {
+                b += 1;
+                continue;
+            }
+            let site = func.blocks[b].insts.iter().position(
+                |inst| matches!(inst, Inst::Call { func: c, .. } if *c != i && small[*c]),
+            );
+            match site {
+                Some(at) => {
+                    let first = func.blocks.len();
+                    inline_at(func, BlockId(b), at, &callees);
+                    let pos = order.iter().position(|o| o.0 == b).unwrap();
+                    order.splice(pos + 1..pos + 1, (first..func.blocks.len()).map(BlockId));
+                    search.resize(func.blocks.len() - 1, false);
+                    search.push(true);
+                    changed = true;
+                }
+                None => b += 1,
+            }
+        }
+        func.reorder(&order);
+    }
+    if changed {
+        remove_unused(program);
+    }
+    changed
+}
+
+fn size(func: &Function) -> usize {
+    func.blocks.iter().map(|b| b.insts.len()).sum()
+}
+
+fn calls(func: &Function, index: usize) -> bool {
+    func.blocks.iter().flat_map(|b| b.insts.iter()).any(|inst| {
+        matches!(inst, Inst::Call { func, .. } | Inst::TailCall { func, .. } if *func == index)
+    })
+}
+
+// Splits the block at the call, copying the callee's blocks in between. The
+// callee's parameters are copied from the arguments and its returns jump to
+// the rest of the block, which is the last block added. The callee's memory
+// goes after the caller's.
+fn inline_at(func: &mut Function, b: BlockId, at: usize, callees: &[Function]) {
+    let mut insts = std::mem::take(&mut func.blocks[b.0].insts);
+    let rest: Vec<Inst> = insts.split_off(at + 1);
+    let Some(Inst::Call { func: c, dst, args }) = insts.pop() else {
+        unreachable!()
+    };
+    let callee = &callees[c];
+
+    let regs = func.regs.len() as u32;
+    func.regs.extend(callee.regs.iter().cloned());
+    let reg = |r: VReg| VReg(r.0 + regs);
+    let memory = func.memory.next_multiple_of(8);
+    func.memory = memory + callee.memory;
+    let blocks = func.blocks.len();
+    let after = BlockId(blocks + callee.blocks.len());
+
+    for (p, arg) in args.iter().enumerate() {
+        insts.push(Inst::Copy {
+            dst: reg(VReg(p as u32)),
+            src: *arg,
+        });
+    }
+    insts.push(Inst::Jump(BlockId(blocks)));
+    func.blocks[b.0].insts = insts;
+
+    for block in callee.blocks.iter() {
+        let mut block = block.clone();
+        let mut insts = vec![];
+        for mut inst in block.insts.drain(..) {
+            inst.rename_uses(reg);
+            inst.rename_def(reg);
+            inst.retarget(|t| BlockId(t.0 + blocks));
+            if let Inst::Frame { offset, .. } = &mut inst {
+                *offset += memory;
+            }
+            match inst {
+                Inst::Return(value) => {
+                    if let (Some(dst), Some(v)) = (dst, value) {
+                        insts.push(Inst::Copy { dst, src: v });
+                    }
+                    insts.push(Inst::Jump(after));
+                }
+                inst => insts.push(inst),
+            }
+        }
+        block.insts = insts;
+        func.blocks.push(block);
+    }
+    let b = func.new_block();
+    func.blocks[b.0].insts = rest;
+}
+
+// Drops functions nothing reachable from the entry calls any more
+fn remove_unused(program: &mut Program) {
+    let mut reached = vec![false; program.functions.len()];
+    reached[program.entry] = true;
+    let mut work = vec![program.entry];
+    while let Some(f) = work.pop() {
+        for inst in program.functions[f]
+            .blocks
+            .iter()
+            .flat_map(|b| b.insts.iter())
+        {
+            if let Inst::Call { func, .. } | Inst::TailCall { func, .. } = inst
+                && !reached[*func]
+            {
+                reached[*func] = true;
+                work.push(*func);
+            }
+        }
+    }
+
+    let mut number = vec![usize::MAX; reached.len()];
+    let mut n = 0;
+    for (f, r) in reached.iter().enumerate() {
+        if *r {
+            number[f] = n;
+            n += 1;
+        }
+    }
+    let mut f = 0;
+    program.functions.retain(|_| {
+        f += 1;
+        reached[f - 1]
+    });
+    program.entry = number[program.entry];
+    for inst in program
+        .functions
+        .iter_mut()
+        .flat_map(|f| f.blocks.iter_mut())
+        .flat_map(|b| b.insts.iter_mut())
+    {
+        if let Inst::Call { func, .. } | Inst::TailCall { func, .. } = inst {
+            *func = number[*func];
+        }
+    }
+}
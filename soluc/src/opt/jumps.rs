@@ -0,0 +1,83 @@
+use crate::ir::{BlockId, Function, Inst};
+
+// Jump threading. A jump to a block that does nothing but jump on goes
+// straight to where the chain ends, and one to a block that only returns
+// returns itself. A branch with both sides going to the same block becomes a
+// jump, and a block only reached by a jump from one other block is merged
+// into the end of it.
+pub fn thread_jumps(func: &mut Function) -> bool {
+    let n = func.blocks.len();
+    let mut changed = false;
+
+    // Where a jump to each block ends up, a cycle of empty blocks is left
+    // alone
+    let target: Vec<BlockId> = (0..n)
+        .map(|b| {
+            let mut t = BlockId(b);
+            for _ in 0..n {
+                match func.blocks[t.0].insts.as_slice() {
+                    [Inst::Jump(next)] if *next != t => t = *next,
+                    _ => break,
+                }
+            }
+            t
+        })
+        .collect();
+    let returns: Vec<Option<Inst>> = func
+        .blocks
+        .iter()
+        .map(|b| match b.insts.as_slice() {
+            [ret @ Inst::Return(_)] => Some(ret.clone()),
+            _ => None,
+        })
+        .collect();
+    for block in func.blocks.iter_mut() {
+        let Some(last) = block.insts.last_mut() else {
+            continue;
+        };
+        last.retarget(|t| {
+            changed |= target[t.0] != t;
+            target[t.0]
+        });
+        if let Inst::Jump(t) = *last
+            && let Some(ret) = &returns[t.0]
+        {
+            *last = ret.clone();
+            changed = true;
+        }
+        if let Inst::Branch {
+            then, otherwise, ..
+        } = *last
+            && then == otherwise
+        {
+            *last = Inst::Jump(then);
+            changed = true;
+        }
+    }
+
+    // The entry block is never merged, it's entered without a jump
+    let preds = func.preds();
+    let mut merged = vec![false; n];
+    for b in 0..n {
+        if merged[b] {
+            continue;
+        }
+        while let Some(Inst::Jump(t)) = func.blocks[b].insts.last().cloned()
+            && t.0 != 0
+            && t.0 != b
+            && preds[t.0].len() == 1
+            && !merged[t.0]
+        {
+            let mut tail = std::mem::take(&mut func.blocks[t.0].insts);
+            func.blocks[b].insts.pop();
+            func.blocks[b].insts.append(&mut tail);
+            merged[t.0] = true;
+            changed = true;
+        }
+    }
+    if merged.iter().any(|m| *m) {
+        let order: Vec<BlockId> = (0..n).filter(|b| !merged[*b]).map(BlockId).collect();
+        func.reorder(&order);
+    }
+    changed
+}
@@ -0,0 +1,16 @@
+mod copies;
+mod dce;
+mod facts;
+mod fold;
+mod inline;
+mod jumps;
+mod passes;
+mod tail;
+
+pub use copies::*;
+pub use dce::*;
+pub use fold::*;
+pub use inline::*;
+pub use jumps::*;
+pub use passes::*;
+pub use tail::*;
@@ -0,0 +1,117 @@
+use super::copies::propagate_copies;
+use super::dce::eliminate_dead_code;
+use super::fold::fold_constants;
+use super::inline::inline_calls;
+use super::jumps::thread_jumps;
+use super::tail::eliminate_tail_calls;
+use crate::ir::{Function, Program};
+
+// Which passes `optimize` runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Passes {
+    pub fold: bool,
+    pub copy_prop: bool,
+    pub dce: bool,
+    pub jump_threading: bool,
+    pub inline: bool,
+    pub tail_calls: bool,
+}
+
+impl Passes {
+    // Names used on the command line
+    pub const NAMES: [&'static str; 6] = [
+        "fold",
+        "copy-prop",
+        "dce",
+        "jump-threading",
+        "inline",
+        "tail-calls",
+    ];
+
+    pub fn all() -> Self {
+        Self {
+            fold: true,
+            copy_prop: true,
+            dce: true,
+            jump_threading: true,
+            inline: true,
+            tail_calls: true,
+        }
+    }
+
+    pub fn none() -> Self {
+        Self {
+            fold: false,
+            copy_prop: false,
+            dce: false,
+            jump_threading: false,
+            inline: false,
+            tail_calls: false,
+        }
+    }
+
+    // Turns the pass called `name` on or off, false for an unknown name
+    pub fn set(&mut self, name: &str, on: bool) -> bool {
+        let pass = match name {
+            "fold" => &mut self.fold,
+            "copy-prop" => &mut self.copy_prop,
+            "dce" => &mut self.dce,
+            "jump-threading" => &mut self.jump_threading,
+            "inline" => &mut self.inline,
+            "tail-calls" => &mut self.tail_calls,
+            _ => return false,
+        };
+        *pass = on;
+        true
+    }
+}
+
+// A pass can leave work for the others, like a folded branch leaving a block
+// unreachable, so they run in turn until none of them changes anything
+const MAX_ROUNDS: usize = 16;
+
+// Functions are simplified before inlining so their size is what would be
+// copied, and again after it with the callee's code in place. Tail calls are
+// only left once inlining has removed the ones it could.
+pub fn optimize(program: &mut Program, passes: Passes) {
+    for func in program.functions.iter_mut() {
+        simplify(func, passes);
+    }
+    if passes.inline && inline_calls(program) {
+        for func in program.functions.iter_mut() {
+            simplify(func, passes);
+        }
+    }
+    if passes.tail_calls {
+        for (i, func) in program.functions.iter_mut().enumerate() {
+            if eliminate_tail_calls(func, i) {
+                simplify(func, passes);
+            }
+        }
+    }
+}
+
+fn simplify(func: &mut Function, passes: Passes) {
+    for _ in 0..MAX_ROUNDS {
+        if !round(func, passes) {
+            break;
+        }
+    }
+}
+
+fn round(func: &mut Function, passes: Passes) -> bool {
+    let mut changed = false;
+    if passes.fold {
+        changed |= fold_constants(func);
+    }
+    if passes.copy_prop {
+        changed |= propagate_copies(func);
+    }
+    if passes.dce {
+        changed |= eliminate_dead_code(func);
+    }
+    if passes.jump_threading {
+        changed |= thread_jumps(func);
+    }
+    changed
+}
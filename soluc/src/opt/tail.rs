@@ -0,0 +1,63 @@
+use crate::ir::{BlockId, Function, Inst, VReg};
+
+// Tail calls. A call whose result is returned right away doesn't need a
+// frame of its own. A function calling itself jumps back to its start with
+// the arguments as its new parameters, a call of another function becomes a
+// `TailCall` that reuses the caller's frame. Recursion in tail position then
+// runs in constant stack space. Functions with memory are left alone, the
+// arguments may point into it.
+pub fn eliminate_tail_calls(func: &mut Function, index: usize) -> bool {
+    if func.memory > 0 {
+        return false;
+    }
+    let mut changed = false;
+    for b in 0..func.blocks.len() {
+        let insts = &func.blocks[b].insts;
+        let n = insts.len();
+        if n < 2 {
+            continue;
+        }
+        let (
+            Inst::Call {
+                func: callee,
+                dst,
+                args,
+            },
+            Inst::Return(value),
+        ) = (&insts[n - 2], &insts[n - 1])
+        else {
+            continue;
+        };
+        if dst != value {
+            continue;
+        }
+        let (callee, args) = (*callee, args.clone());
+        func.blocks[b].insts.truncate(n - 2);
+
+        if callee != index {
+            func.blocks[b]
+                .insts
+                .push(Inst::TailCall { func: callee, args });
+        } else {
+            // Every argument is read before any parameter is written, one
+            // may be computed from another parameter
+            let temps: Vec<_> = args
+                .iter()
+                .map(|a| {
+                    let t = func.new_reg(func.regs[a.0 as usize]);
+                    func.blocks[b].insts.push(Inst::Copy { dst: t, src: *a });
+                    t
+                })
+                .collect();
+            for (p, t) in temps.into_iter().enumerate() {
+                func.blocks[b].insts.push(Inst::Copy {
+                    dst: VReg(p as u32),
+                    src: t,
+                });
+            }
+            func.blocks[b].insts.push(Inst::Jump(BlockId(0)));
+        }
+        changed = true;
+    }
+    changed
+}
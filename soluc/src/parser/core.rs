@@ -1,4 +1,5 @@
 use crate::lexer;
+use crate::lexer::Span;
 use crate::lexer::Token::Identifier as Ident;
 use crate::parser::types::*;
 
@@ -14,48 +15,103 @@ const IS: lexer::Token = lexer::Token::Keyword(lexer::Keyword::Is);
 const NAMESPACE: lexer::Token = lexer::Token::Keyword(lexer::Keyword::Namespace);
 const LPAREN: lexer::Token = lexer::Token::Delim(lexer::Delimeter::Lparen);
 const COLON: lexer::Token = lexer::Token::Delim(lexer::Delimeter::Colon);
+const RPAREN: lexer::Token = lexer::Token::Delim(lexer::Delimeter::Rparen);
+const LBRACKET: lexer::Token = lexer::Token::Delim(lexer::Delimeter::Lbracket);
+const RBRACKET: lexer::Token = lexer::Token::Delim(lexer::Delimeter::Rbracket);
+const COMMA: lexer::Token = lexer::Token::Delim(lexer::Delimeter::Comma);
+const END: lexer::Token = lexer::Token::Keyword(lexer::Keyword::End);
+const DO: lexer::Token = lexer::Token::Keyword(lexer::Keyword::Do);
+const WITH: lexer::Token = lexer::Token::Keyword(lexer::Keyword::With);
+const MUT: lexer::Token = lexer::Token::Keyword(lexer::Keyword::Mut);
+const RETURN: lexer::Token = lexer::Token::Keyword(lexer::Keyword::Return);
+const TRUE: lexer::Token = lexer::Token::Keyword(lexer::Keyword::True);
+const FALSE: lexer::Token = lexer::Token::Keyword(lexer::Keyword::False);
+const NIL: lexer::Token = lexer::Token::Keyword(lexer::Keyword::Nil);
+const SIZEOF: lexer::Token = lexer::Token::Keyword(lexer::Keyword::Sizeof);
+const ALIGNOF: lexer::Token = lexer::Token::Keyword(lexer::Keyword::Alignof);
+const ASSIGN: lexer::Token = lexer::Token::Operator(lexer::Operator::Eq);
+const STAR: lexer::Token = lexer::Token::Operator(lexer::Operator::Star);
+const AMP: lexer::Token = lexer::Token::Operator(lexer::Operator::Amp);
+const DOT: lexer::Token = lexer::Token::Operator(lexer::Operator::MemberAccessor);
+const QUESTION: lexer::Token = lexer::Token::Operator(lexer::Operator::Terinary);
+const RANGE: lexer::Token = lexer::Token::Operator(lexer::Operator::Range);
+const SEMI: lexer::Token = lexer::Token::Delim(lexer::Delimeter::SemiColon);
+const ELIF: lexer::Token = lexer::Token::Keyword(lexer::Keyword::Elif);
+const ELSE: lexer::Token = lexer::Token::Keyword(lexer::Keyword::Else);
+const WHILE: lexer::Token = lexer::Token::Keyword(lexer::Keyword::While);
+const FOR: lexer::Token = lexer::Token::Keyword(lexer::Keyword::For);
+const IN: lexer::Token = lexer::Token::Keyword(lexer::Keyword::In);
+const BREAK: lexer::Token = lexer::Token::Keyword(lexer::Keyword::Break);
+const CONTINUE: lexer::Token = lexer::Token::Keyword(lexer::Keyword::Continue);
+const CASE: lexer::Token = lexer::Token::Keyword(lexer::Keyword::Case);
+const DEFAULT: lexer::Token = lexer::Token::Keyword(lexer::Keyword::Default);
 
 #[derive(Debug, Clone)]
 pub struct Parser {
     pub pos: usize,
-    pub tokens: Vec<lexer::Token>,
+    pub tokens: Program,
     pub ast: Namespace,
+    pub errors: Vec<ParserError>,
 }
 
 impl Parser {
     pub fn new(tokens: Program) -> Self {
+        let span = tokens.first().map(|t| t.span).unwrap_or(Span {
+            start: 0,
+            end: 0,
+            line: 1,
+            col: 0,
+        });
         Self {
             pos: 0,
             tokens,
             ast: Namespace {
                 name: "Global".to_string(),
                 nodes: vec![],
+                span,
             },
+            errors: vec![],
         }
     }
 
-    pub fn parse(&mut self) -> Result<Namespace, ParserError> {
-        if self.tokens.len() == 0 {
+    // Parses the whole program, returning every error found if any
+    pub fn parse(&mut self) -> Result<Namespace, Vec<ParserError>> {
+        if !self.tokens.is_empty() {
+            self.ast.nodes = self.parse_p();
+            self.ast.span = self.span_from(0);
+        }
+        if self.errors.is_empty() {
             Ok(self.ast.clone())
         } else {
-            self.ast.nodes = self.parse_p()?;
-            Ok(self.ast.clone())
+            Err(self.errors.clone())
         }
     }
 
     // P ::= decl_list
-    fn parse_p(&mut self) -> Result<Vec<Decl>, ParserError> {
-        self.parse_decl_list()
+    fn parse_p(&mut self) -> Vec<Decl> {
+        let mut list = self.parse_decl_list();
+        while !self.check(&EOF) {
+            // stray `end` (or other closer) with no block to close
+            let err = ParserError::new(self, format!("unexpected {}", self.peek()));
+            self.errors.push(err);
+            self.advance();
+            list.append(&mut self.parse_decl_list());
+        }
+        list
     }
 
     // decl_list ::= D (TERM D)*
-    fn parse_decl_list(&mut self) -> Result<Vec<Decl>, ParserError> {
+    fn parse_decl_list(&mut self) -> Vec<Decl> {
         let mut list = Vec::new();
-        list.push(self.parse_d()?);
-        while self.matches(&TERM) {
-            list.push(self.parse_d()?);
+        self.skip_terms();
+        while !self.check(&END) && !self.check(&EOF) {
+            let start = self.pos;
+            let decl = self.parse_d();
+            if let Some(d) = self.finish_item(start, decl, true) {
+                list.push(d);
+            }
         }
-        Ok(list)
+        list
     }
 
     // D ::= struct_D | enum_D | const_D | func_D | method_D | namespace_D
@@ -66,43 +122,730 @@ impl Parser {
             CONST => self.parse_const_d(),
             STRUCT => self.parse_struct_d(),
             NAMESPACE => self.parse_namespace_d(),
+            RETURN => Err(ParserError::new(self, "`return` outside of a function")),
+            BREAK | CONTINUE => Err(ParserError::new(
+                self,
+                "`break`/`continue` outside of a loop",
+            )),
             _ => Err(ParserError::new(self, "Invalid Declaration")),
         }
     }
 
     // struct_D ::= "struct identifier "is" TERM? member_list_D TERM? "end"
     fn parse_struct_d(&mut self) -> Result<Decl, ParserError> {
-        todo!()
+        let start = self.pos;
+        self.expect(&STRUCT)?;
+        let name = self.expect_ident()?;
+        self.expect(&IS)?;
+        self.skip_terms();
+        let members = self.parse_member_list_d()?;
+        self.skip_terms();
+        self.expect(&END)?;
+        Ok(Decl::Struct(Struct {
+            name,
+            members,
+            span: self.span_from(start),
+        }))
+    }
+
+    // member_list_D ::= member_D (TERM member_D)*
+    // member_D ::= identifier ":" type
+    fn parse_member_list_d(&mut self) -> Result<Vec<Member>, ParserError> {
+        let mut members = Vec::new();
+        while !self.at_block_end() {
+            let start = self.pos;
+            let member = self.parse_member_d();
+            if let Some(m) = self.finish_item(start, member, false) {
+                members.push(m);
+            }
+        }
+        Ok(members)
+    }
+
+    fn parse_member_d(&mut self) -> Result<Member, ParserError> {
+        let start = self.pos;
+        let name = self.expect_ident()?;
+        self.expect(&COLON)?;
+        let ty = self.parse_type()?;
+        Ok(Member {
+            name,
+            ty,
+            span: self.span_from(start),
+        })
     }
 
-    // enum_D ::= "enum" identifier "is" TERM? variant_list TERM? "end"
+    // enum_D ::= "enum" identifier (":" type)? "is" TERM? variant_list TERM? "end"
+    // variant_list ::= variant (TERM variant)*
     fn parse_enum_d(&mut self) -> Result<Decl, ParserError> {
-        todo!()
+        let start = self.pos;
+        self.expect(&ENUM)?;
+        let name = self.expect_ident()?;
+        let ty = if self.matches(&COLON) {
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+        self.expect(&IS)?;
+        self.skip_terms();
+        let mut variants = Vec::new();
+        while !self.at_block_end() {
+            let start = self.pos;
+            let variant = self.parse_variant();
+            if let Some(v) = self.finish_item(start, variant, false) {
+                variants.push(v);
+            }
+        }
+        self.expect(&END)?;
+        Ok(Decl::Enum(Enum {
+            name,
+            ty,
+            variants,
+            span: self.span_from(start),
+        }))
     }
+
+    // variant ::= identifier ("=" expr)?
+    fn parse_variant(&mut self) -> Result<Variant, ParserError> {
+        let span = self.span();
+        let name = self.expect_ident()?;
+        let value = if self.matches(&ASSIGN) {
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+        Ok(Variant { name, value, span })
+    }
+
     // const_d ::= "const" identifier ":" type "=" expr
     fn parse_const_d(&mut self) -> Result<Decl, ParserError> {
-        todo!()
+        let start = self.pos;
+        self.expect(&CONST)?;
+        let name = self.expect_ident()?;
+        self.expect(&COLON)?;
+        let ty = self.parse_type()?;
+        self.expect(&ASSIGN)?;
+        let value = self.parse_expr()?;
+        Ok(Decl::Const(Const {
+            name,
+            ty,
+            value,
+            span: self.span_from(start),
+        }))
     }
 
     //func_D ::= identifier "(" params? ")" (":" type)? stmt_block
     //method_D ::= identifier "::" identifier "(" param_list? ")" (":" type)? stmt_block
     fn parse_func_and_method_d(&mut self, val: String) -> Result<Decl, ParserError> {
+        let start = self.pos;
         self.advance();
-        match self.peek() {
-            LPAREN => {}
+        let (receiver, name) = match self.peek() {
+            LPAREN => (None, val),
             COLON if self.next() == COLON => {
                 self.advance();
                 self.advance();
-                let 
+                (Some(val), self.expect_ident()?)
+            }
+            _ => return Err(ParserError::new(self, "Invalid Function/Method Structure")),
+        };
+        self.expect(&LPAREN)?;
+        let params = self.parse_params()?;
+        self.expect(&RPAREN)?;
+        let ret = if self.matches(&COLON) {
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+        let body = self.parse_stmt_block()?;
+        Ok(Decl::Function(Function {
+            name,
+            receiver,
+            params,
+            ret,
+            body,
+            span: self.span_from(start),
+        }))
+    }
+
+    // params ::= param ("," param)*
+    // param ::= identifier ":" type
+    fn parse_params(&mut self) -> Result<Vec<Param>, ParserError> {
+        let mut params = Vec::new();
+        if self.check(&RPAREN) {
+            return Ok(params);
+        }
+        loop {
+            let start = self.pos;
+            let name = self.expect_ident()?;
+            self.expect(&COLON)?;
+            let ty = self.parse_type()?;
+            params.push(Param {
+                name,
+                ty,
+                span: self.span_from(start),
+            });
+            if !self.matches(&COMMA) {
+                break;
             }
-            _ => Err(ParserError::new(self, "Invalid Function/Method Structure")),
         }
-        todo!()
+        Ok(params)
     }
 
     //namespace_D ::= "namespace" identifier "is TERM? decl_list TERM? "end"
     fn parse_namespace_d(&mut self) -> Result<Decl, ParserError> {
-        todo!()
+        let start = self.pos;
+        self.expect(&NAMESPACE)?;
+        let name = self.expect_ident()?;
+        self.expect(&IS)?;
+        let nodes = self.parse_decl_list();
+        self.expect(&END)?;
+        Ok(Decl::Namespace(Namespace {
+            name,
+            nodes,
+            span: self.span_from(start),
+        }))
+    }
+
+    // type ::= type_base ("[" expr "]")?
+    // type_base ::= primitive_type | path | "*" type | "&" type
+    fn parse_type(&mut self) -> Result<Type, ParserError> {
+        let start = self.pos;
+        let kind = match self.advance() {
+            lexer::Token::Keyword(k) if Primitive::from_keyword(&k).is_some() => {
+                TypeKind::Primitive(Primitive::from_keyword(&k).unwrap())
+            }
+            Ident(s) => TypeKind::Named(self.parse_path(s)?.join("::")),
+            STAR => TypeKind::Pointer(Box::new(self.parse_type()?)),
+            AMP => TypeKind::Ref(Box::new(self.parse_type()?)),
+            t => {
+                // leave the offending token for recovery to see
+                self.pos = start;
+                return Err(ParserError::new(self, format!("expected type, got {}", t)));
+            }
+        };
+        let base = Type {
+            kind,
+            span: self.span_from(start),
+        };
+        if self.matches(&LBRACKET) {
+            let size = self.parse_expr()?;
+            self.expect(&RBRACKET)?;
+            return Ok(Type {
+                kind: TypeKind::Array(Box::new(base), Box::new(size)),
+                span: self.span_from(start),
+            });
+        }
+        Ok(base)
+    }
+}
+
+// ---------------------------------------
+// Statements
+// ---------------------------------------
+impl Parser {
+    // stmt_block ::= "do" TERM? stmt_list TERM? "end"
+    fn parse_stmt_block(&mut self) -> Result<Block, ParserError> {
+        self.expect(&DO)?;
+        let body = self.parse_stmt_list()?;
+        self.expect(&END)?;
+        Ok(body)
+    }
+
+    // stmt_list ::= stmt (TERM stmt)*
+    // Stops at whichever keyword closes the enclosing block
+    fn parse_stmt_list(&mut self) -> Result<Block, ParserError> {
+        self.skip_terms();
+        let start = self.pos;
+        let mut stmts = Vec::new();
+        while !self.at_block_end() {
+            let start = self.pos;
+            let stmt = self.parse_stmt();
+            if let Some(s) = self.finish_item(start, stmt, false) {
+                stmts.push(s);
+            }
+        }
+        Ok(Block {
+            stmts,
+            span: self.span_from(start),
+        })
+    }
+
+    // stmt ::= var_D | assign_stmt | expr_stmt | ret_stmt | break_stmt
+    //        | cont_stmt | if_stmt | while_stmt | for_stmt | switch_stmt
+    fn parse_stmt(&mut self) -> Result<Stmt, ParserError> {
+        let start = self.pos;
+        let kind = match self.peek() {
+            RETURN => self.parse_ret_stmt()?,
+            BREAK => {
+                self.advance();
+                StmtKind::Break
+            }
+            CONTINUE => {
+                self.advance();
+                StmtKind::Continue
+            }
+            IF => self.parse_if_stmt()?,
+            WHILE => self.parse_while_stmt()?,
+            FOR => self.parse_for_stmt()?,
+            SWITCH => self.parse_switch_stmt()?,
+            MUT => self.parse_var_d()?,
+            // `x: i32` declares, `M::f()` starts an expression
+            Ident(_) if self.next() == COLON && self.peek_at(2) != COLON => self.parse_var_d()?,
+            _ => self.parse_assign_or_expr_stmt()?,
+        };
+        Ok(Stmt {
+            kind,
+            span: self.span_from(start),
+        })
+    }
+
+    // var_D ::= "mut"? identifier ":" type ("=" expr)?
+    fn parse_var_d(&mut self) -> Result<StmtKind, ParserError> {
+        let mutable = self.matches(&MUT);
+        let name = self.expect_ident()?;
+        self.expect(&COLON)?;
+        let ty = self.parse_type()?;
+        let value = if self.matches(&ASSIGN) {
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+        Ok(StmtKind::Var {
+            mutable,
+            name,
+            ty,
+            value,
+        })
+    }
+
+    // assign_stmt ::= lvalue assign_op (expr | assign_block)
+    // expr_stmt ::= expr
+    fn parse_assign_or_expr_stmt(&mut self) -> Result<StmtKind, ParserError> {
+        let target = if self.check(&STAR) {
+            self.parse_lvalue()?
+        } else {
+            self.parse_expr()?
+        };
+        match self.peek() {
+            lexer::Token::Operator(op) if AssignOp::from_operator(&op).is_some() => {
+                if !is_lvalue(&target) {
+                    return Err(ParserError::new(self, "invalid assignment target"));
+                }
+                self.advance();
+                let value = self.parse_expr()?;
+                Ok(StmtKind::Assign {
+                    target,
+                    op: AssignOp::from_operator(&op).unwrap(),
+                    value,
+                })
+            }
+            _ if matches!(
+                target.kind,
+                ExprKind::Unary {
+                    op: UnaryOp::Deref,
+                    ..
+                }
+            ) =>
+            {
+                Err(ParserError::new(
+                    self,
+                    "expected assignment after dereference",
+                ))
+            }
+            _ => Ok(StmtKind::Expr(target)),
+        }
+    }
+
+    // lvalue ::= "*"* identifier (lvalue_suffix)*
+    fn parse_lvalue(&mut self) -> Result<Expr, ParserError> {
+        let start = self.pos;
+        if self.matches(&STAR) {
+            let expr = self.parse_lvalue()?;
+            return Ok(Expr {
+                kind: ExprKind::Unary {
+                    op: UnaryOp::Deref,
+                    expr: Box::new(expr),
+                },
+                span: self.span_from(start),
+            });
+        }
+        self.parse_postfix_expr()
+    }
+
+    // ret_stmt ::= "return" expr?
+    fn parse_ret_stmt(&mut self) -> Result<StmtKind, ParserError> {
+        self.expect(&RETURN)?;
+        if self.check_term() || self.at_block_end() {
+            Ok(StmtKind::Return(None))
+        } else {
+            Ok(StmtKind::Return(Some(self.parse_expr()?)))
+        }
+    }
+
+    // if_stmt ::= "if" expr jmp_block
+    // jmp_block ::= "then" TERM? stmt_list
+    //               (TERM? "elif" expr "then" TERM? stmt_list)*
+    //               (TERM? "else" stmt_list)?
+    //               TERM? "end"
+    fn parse_if_stmt(&mut self) -> Result<StmtKind, ParserError> {
+        self.expect(&IF)?;
+        let mut branches = Vec::new();
+        let cond = self.parse_expr()?;
+        self.expect(&THEN)?;
+        branches.push((cond, self.parse_stmt_list()?));
+        while self.matches(&ELIF) {
+            let cond = self.parse_expr()?;
+            self.expect(&THEN)?;
+            branches.push((cond, self.parse_stmt_list()?));
+        }
+        let else_body = if self.matches(&ELSE) {
+            Some(self.parse_stmt_list()?)
+        } else {
+            None
+        };
+        self.expect(&END)?;
+        Ok(StmtKind::If {
+            branches,
+            else_body,
+        })
+    }
+
+    // while_stmt ::= "while" expr stmt_block
+    fn parse_while_stmt(&mut self) -> Result<StmtKind, ParserError> {
+        self.expect(&WHILE)?;
+        let cond = self.parse_expr()?;
+        let body = self.parse_stmt_block()?;
+        Ok(StmtKind::While { cond, body })
+    }
+
+    // for_stmt ::= "for" identifier "in" expr (".." expr)? stmt_block
+    fn parse_for_stmt(&mut self) -> Result<StmtKind, ParserError> {
+        self.expect(&FOR)?;
+        let var = self.expect_ident()?;
+        self.expect(&IN)?;
+        let start = self.parse_expr()?;
+        let end = if self.matches(&RANGE) {
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+        let body = self.parse_stmt_block()?;
+        Ok(StmtKind::For {
+            var,
+            start,
+            end,
+            body,
+        })
+    }
+
+    // switch_stmt ::= "switch" (expr | identifier) "then" TERM?
+    //                 case_clause+
+    //                 default_clause?
+    //                 TERM? "end"
+    // case_clause ::= "case" expr ":" TERM? stmt_list?
+    // default_clause ::= "default" ":" TERM? stmt_list?
+    fn parse_switch_stmt(&mut self) -> Result<StmtKind, ParserError> {
+        self.expect(&SWITCH)?;
+        let value = self.parse_expr()?;
+        self.expect(&THEN)?;
+        self.skip_terms();
+        let mut cases = Vec::new();
+        while self.check(&CASE) {
+            let start = self.pos;
+            self.advance();
+            let value = self.parse_expr()?;
+            self.expect(&COLON)?;
+            let body = self.parse_stmt_list()?;
+            cases.push(Case {
+                value,
+                body,
+                span: self.span_from(start),
+            });
+        }
+        if cases.is_empty() {
+            return Err(ParserError::new(self, "switch needs at least one case"));
+        }
+        let default = if self.matches(&DEFAULT) {
+            self.expect(&COLON)?;
+            Some(self.parse_stmt_list()?)
+        } else {
+            None
+        };
+        self.expect(&END)?;
+        Ok(StmtKind::Switch {
+            value,
+            cases,
+            default,
+        })
+    }
+}
+
+fn is_lvalue(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Ident(_) => true,
+        ExprKind::Member { base, .. } | ExprKind::Index { base, .. } => is_lvalue(base),
+        ExprKind::Unary {
+            op: UnaryOp::Deref,
+            expr,
+        } => is_lvalue(expr),
+        _ => false,
+    }
+}
+
+// ---------------------------------------
+// Expressions
+// ---------------------------------------
+
+// Binding powers for the precedence table in docs/grammar.md. Level N of the
+// table maps to (14 - N) * 2, so a higher power binds tighter. Left
+// associative operators bind their right side one step tighter.
+const BP_TERNARY: u8 = 2; // 13
+const BP_PREFIX: u8 = 22; // 2 and 3, casts and unary operators
+const BP_POSTFIX: u8 = 26; // 1, calls / member access / indexing
+
+fn infix_binding_power(op: BinaryOp) -> (u8, u8) {
+    let bp = match op {
+        BinaryOp::Or => 4,
+        BinaryOp::And => 6,
+        BinaryOp::BitOr => 8,
+        BinaryOp::BitXor => 10,
+        BinaryOp::BitAnd => 12,
+        BinaryOp::Shl | BinaryOp::Shr => 14,
+        BinaryOp::Eq
+        | BinaryOp::NotEq
+        | BinaryOp::Lt
+        | BinaryOp::Gt
+        | BinaryOp::LtEq
+        | BinaryOp::GtEq => 16,
+        BinaryOp::Add | BinaryOp::Sub => 18,
+        BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => 20,
+    };
+    (bp, bp + 1)
+}
+
+impl Parser {
+    // expr ::= ternary_expr
+    fn parse_expr(&mut self) -> Result<Expr, ParserError> {
+        self.parse_expr_bp(0)
+    }
+
+    // Parses operators that bind at least as tightly as `min_bp`
+    fn parse_expr_bp(&mut self, min_bp: u8) -> Result<Expr, ParserError> {
+        let start = self.pos;
+        let mut lhs = self.parse_prefix_expr()?;
+        loop {
+            let kind = match self.peek() {
+                // postfix_op ::= "(" (expr ("," expr)*)? ")" | "." identifier | "[" expr "]"
+                LPAREN | DOT | LBRACKET if BP_POSTFIX >= min_bp => self.parse_postfix_op(lhs)?,
+                // ternary_expr ::= logical_or_expr ( "?" expr ":" ternary_expr)?
+                QUESTION if BP_TERNARY >= min_bp => {
+                    self.advance();
+                    let then = self.parse_expr()?;
+                    self.expect(&COLON)?;
+                    let otherwise = self.parse_expr_bp(BP_TERNARY)?;
+                    ExprKind::Ternary {
+                        cond: Box::new(lhs),
+                        then: Box::new(then),
+                        otherwise: Box::new(otherwise),
+                    }
+                }
+                _ => match self.peek_binary_op() {
+                    Some(op) if infix_binding_power(op).0 >= min_bp => {
+                        self.advance();
+                        let rhs = self.parse_expr_bp(infix_binding_power(op).1)?;
+                        ExprKind::Binary {
+                            op,
+                            lhs: Box::new(lhs),
+                            rhs: Box::new(rhs),
+                        }
+                    }
+                    _ => return Ok(lhs),
+                },
+            };
+            lhs = Expr {
+                kind,
+                span: self.span_from(start),
+            };
+        }
+    }
+
+    // unary_expr ::= ("-" | "!" | "~") unary_expr | type_cast_expr | postfix_expr
+    // type_cast_expr ::= "[" type "]" unary_expr
+    fn parse_prefix_expr(&mut self) -> Result<Expr, ParserError> {
+        let start = self.pos;
+        let kind = match self.peek() {
+            LBRACKET => {
+                self.advance();
+                let ty = self.parse_type()?;
+                self.expect(&RBRACKET)?;
+                let expr = self.parse_expr_bp(BP_PREFIX)?;
+                ExprKind::Cast {
+                    ty,
+                    expr: Box::new(expr),
+                }
+            }
+            lexer::Token::Operator(op) => {
+                let op = match op {
+                    lexer::Operator::Minus => UnaryOp::Neg,
+                    lexer::Operator::Not => UnaryOp::Not,
+                    lexer::Operator::Tilde => UnaryOp::BitNot,
+                    _ => {
+                        return Err(ParserError::new(
+                            self,
+                            format!("expected expression, got {}", self.peek()),
+                        ));
+                    }
+                };
+                self.advance();
+                let expr = self.parse_expr_bp(BP_PREFIX)?;
+                ExprKind::Unary {
+                    op,
+                    expr: Box::new(expr),
+                }
+            }
+            _ => return self.parse_primary_expr(),
+        };
+        Ok(Expr {
+            kind,
+            span: self.span_from(start),
+        })
+    }
+
+    fn parse_postfix_op(&mut self, base: Expr) -> Result<ExprKind, ParserError> {
+        match self.advance() {
+            LPAREN => {
+                let mut args = Vec::new();
+                if !self.check(&RPAREN) {
+                    loop {
+                        args.push(self.parse_expr()?);
+                        if !self.matches(&COMMA) {
+                            break;
+                        }
+                    }
+                }
+                self.expect(&RPAREN)?;
+                Ok(ExprKind::Call {
+                    callee: Box::new(base),
+                    args,
+                })
+            }
+            DOT => Ok(ExprKind::Member {
+                base: Box::new(base),
+                field: self.expect_ident()?,
+            }),
+            _ => {
+                let index = self.parse_expr()?;
+                self.expect(&RBRACKET)?;
+                Ok(ExprKind::Index {
+                    base: Box::new(base),
+                    index: Box::new(index),
+                })
+            }
+        }
+    }
+
+    // postfix_expr ::= primary_expr (postfix_op)*
+    fn parse_postfix_expr(&mut self) -> Result<Expr, ParserError> {
+        self.parse_expr_bp(BP_POSTFIX)
+    }
+
+    // primary_expr ::= literal | "nil" | struct_literal | path | "(" expr ")"
+    fn parse_primary_expr(&mut self) -> Result<Expr, ParserError> {
+        let start = self.pos;
+        let kind = match self.advance() {
+            lexer::Token::Num(lexer::NumLiteral::Int(n)) => ExprKind::Int(n),
+            lexer::Token::Num(lexer::NumLiteral::Float(f)) => ExprKind::Float(f),
+            lexer::Token::Char(c) => ExprKind::Char(c),
+            lexer::Token::Str(s) => ExprKind::Str(s),
+            TRUE => ExprKind::Bool(true),
+            FALSE => ExprKind::Bool(false),
+            NIL => ExprKind::Nil,
+            // ("sizeof" | "alignof") "(" type ")"
+            t @ (SIZEOF | ALIGNOF) => {
+                self.expect(&LPAREN)?;
+                let ty = self.parse_type()?;
+                self.expect(&RPAREN)?;
+                if t == SIZEOF {
+                    ExprKind::SizeOf(ty)
+                } else {
+                    ExprKind::AlignOf(ty)
+                }
+            }
+            Ident(s) => {
+                let mut path = self.parse_path(s)?;
+                if self.check(&WITH) {
+                    self.parse_struct_literal(path.join("::"))?
+                } else if path.len() == 1 {
+                    ExprKind::Ident(path.pop().unwrap())
+                } else {
+                    ExprKind::Path(path)
+                }
+            }
+            LPAREN => {
+                let expr = self.parse_expr()?;
+                self.expect(&RPAREN)?;
+                return Ok(expr);
+            }
+            t => {
+                // leave the offending token for recovery to see
+                self.pos = start;
+                return Err(ParserError::new(
+                    self,
+                    format!("expected expression, got {}", t),
+                ));
+            }
+        };
+        Ok(Expr {
+            kind,
+            span: self.span_from(start),
+        })
+    }
+
+    // path ::= identifier ("::" identifier)*
+    // The first identifier has already been consumed
+    fn parse_path(&mut self, first: String) -> Result<Vec<String>, ParserError> {
+        let mut path = vec![first];
+        while self.check(&COLON) && self.next() == COLON {
+            self.advance();
+            self.advance();
+            path.push(self.expect_ident()?);
+        }
+        Ok(path)
+    }
+
+    // struct_literal ::= path "with" TERM? assign_list TERM? "end"
+    // assign_list ::= identifier "=" expr (TERM identifier "=" expr)*
+    fn parse_struct_literal(&mut self, name: String) -> Result<ExprKind, ParserError> {
+        self.expect(&WITH)?;
+        self.skip_terms();
+        let mut fields = Vec::new();
+        while !self.at_block_end() {
+            let start = self.pos;
+            let field = self.parse_field_init();
+            if let Some(f) = self.finish_item(start, field, false) {
+                fields.push(f);
+            }
+        }
+        self.expect(&END)?;
+        Ok(ExprKind::StructLit { name, fields })
+    }
+
+    fn parse_field_init(&mut self) -> Result<FieldInit, ParserError> {
+        let start = self.pos;
+        let name = self.expect_ident()?;
+        self.expect(&ASSIGN)?;
+        let value = self.parse_expr()?;
+        Ok(FieldInit {
+            name,
+            value,
+            span: self.span_from(start),
+        })
+    }
+
+    fn peek_binary_op(&self) -> Option<BinaryOp> {
+        match self.peek() {
+            lexer::Token::Operator(op) => BinaryOp::from_operator(&op),
+            _ => None,
+        }
     }
 }
 
@@ -112,30 +855,45 @@ impl Parser {
 impl Parser {
     fn peek(&self) -> lexer::Token {
         if self.pos < self.tokens.len() {
-            self.tokens.get(self.pos).unwrap().clone()
+            self.tokens[self.pos].token.clone()
         } else {
             EOF
         }
     }
 
-    fn previous(&self) -> lexer::Token {
-        if self.pos == 0 {
-            EOF
-        } else if (self.pos - 1) < self.tokens.len() {
-            self.tokens.get(self.pos - 1).unwrap().clone()
-        } else {
-            EOF
+    fn next(&self) -> lexer::Token {
+        self.peek_at(1)
+    }
+
+    fn peek_at(&self, offset: usize) -> lexer::Token {
+        match self.tokens.get(self.pos + offset) {
+            Some(t) => t.token.clone(),
+            None => EOF,
         }
     }
 
-    fn next(&self) -> lexer::Token {
-        if (self.pos + 1) < self.tokens.len() {
-            self.tokens.get(self.pos + 1).unwrap().clone()
-        } else {
-            EOF
+    // Span of the current token (or the last one once past the end)
+    pub fn span(&self) -> Span {
+        self.span_at(self.pos)
+    }
+
+    fn span_at(&self, pos: usize) -> Span {
+        match self.tokens.get(pos).or(self.tokens.last()) {
+            Some(t) => t.span,
+            None => self.ast.span,
         }
     }
 
+    // Span from the token at `start` through the last consumed token
+    fn span_from(&self, start: usize) -> Span {
+        let end = if self.pos > start {
+            self.pos - 1
+        } else {
+            start
+        };
+        self.span_at(start).to(self.span_at(end))
+    }
+
     fn check(&self, tok: &lexer::Token) -> bool {
         *tok == self.peek()
     }
@@ -149,20 +907,118 @@ impl Parser {
     fn matches(&mut self, tok: &lexer::Token) -> bool {
         if self.check(tok) {
             self.advance();
-            return true;
+            true
         } else {
-            return false;
+            false
         }
     }
 
-    fn expect(&self, tok: &lexer::Token) -> Result<(), ParserError> {
+    fn expect(&mut self, tok: &lexer::Token) -> Result<(), ParserError> {
         if *tok != self.peek() {
             Err(ParserError::new(
                 self,
                 format!("expected symble {}, got {}", *tok, self.peek()),
             ))
         } else {
+            self.advance();
             Ok(())
         }
     }
+
+    fn expect_ident(&mut self) -> Result<String, ParserError> {
+        match self.peek() {
+            Ident(s) => {
+                self.advance();
+                Ok(s)
+            }
+            t => Err(ParserError::new(
+                self,
+                format!("expected identifier, got {}", t),
+            )),
+        }
+    }
+
+    // TERM ::= "\n" | ";"
+    fn check_term(&self) -> bool {
+        self.check(&TERM) || self.check(&SEMI)
+    }
+
+    fn skip_terms(&mut self) {
+        while self.check_term() {
+            self.advance();
+        }
+    }
+
+    // Keywords that close a declaration or statement list
+    fn at_block_end(&self) -> bool {
+        matches!(self.peek(), END | EOF | ELIF | ELSE | CASE | DEFAULT)
+    }
+
+    // Closes a list item that began at `start`. On failure the error is
+    // recorded and the parser skips ahead to the next item so the rest of
+    // the list still gets checked.
+    fn finish_item<T>(
+        &mut self,
+        start: usize,
+        item: Result<T, ParserError>,
+        decl_level: bool,
+    ) -> Option<T> {
+        match item.and_then(|t| self.end_of_item().map(|_| t)) {
+            Ok(t) => Some(t),
+            Err(e) => {
+                self.errors.push(e);
+                self.synchronize(decl_level);
+                // only `end` closes a declaration list, a stray `case` or
+                // `else` between declarations has to be skipped
+                let closed = if decl_level {
+                    self.check(&END) || self.check(&EOF)
+                } else {
+                    self.at_block_end()
+                };
+                if self.pos == start && !closed {
+                    self.advance();
+                    self.synchronize(decl_level);
+                }
+                None
+            }
+        }
+    }
+
+    // Panic mode recovery: skip tokens until the next TERM, a keyword that
+    // closes the current block, or (between declarations) a keyword that
+    // starts a new declaration. Nested blocks are skipped whole so their
+    // `end` isn't mistaken for ours.
+    fn synchronize(&mut self, decl_level: bool) {
+        let mut depth = 0usize;
+        loop {
+            match self.peek() {
+                EOF => return,
+                TERM | SEMI if depth == 0 => {
+                    self.skip_terms();
+                    return;
+                }
+                END | ELIF | ELSE | CASE | DEFAULT if depth == 0 => return,
+                STRUCT | ENUM | CONST | NAMESPACE if depth == 0 && decl_level => return,
+                END => depth -= 1,
+                IF | SWITCH | DO | IS | WITH => depth += 1,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    // Items in a list are separated by TERM unless the list is closing
+    fn end_of_item(&mut self) -> Result<(), ParserError> {
+        if !self.at_block_end() {
+            if !self.check_term() {
+                return Err(ParserError::new(
+                    self,
+                    format!("expected TERM, got {}", self.peek()),
+                ));
+            }
+            self.advance();
+        }
+        self.skip_terms();
+        Ok(())
+    }
 }
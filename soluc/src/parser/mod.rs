@@ -1,4 +1,8 @@
 mod core;
 mod types;
 
+#[cfg(test)]
+mod tests;
+
 pub use core::*;
+pub use types::*;
@@ -0,0 +1,334 @@
+use super::*;
+use crate::lexer::Lexer;
+
+fn parse(src: &str) -> Vec<Decl> {
+    let tokens = Lexer::new(src).parse().expect("lexer errors");
+    match Parser::new(tokens).parse() {
+        Ok(ast) => ast.nodes,
+        Err(errors) => panic!("parser errors in {:?}: {:?}", src, errors),
+    }
+}
+
+fn parse_one(src: &str) -> Decl {
+    let mut decls = parse(src);
+    assert_eq!(decls.len(), 1, "{:?}", src);
+    decls.remove(0)
+}
+
+fn members(s: &Struct) -> Vec<String> {
+    s.members
+        .iter()
+        .map(|m| format!("{}: {}", m.name, m.ty))
+        .collect()
+}
+
+#[test]
+fn struct_decl() {
+    let Decl::Struct(s) = parse_one(
+        "struct Map is\n\tw: i32\n\ttiles: i32[10 * 10]\n\tnext: *Map\n\towner: &Map\nend",
+    ) else {
+        panic!("not a struct");
+    };
+    assert_eq!(s.name, "Map");
+    assert_eq!(
+        members(&s),
+        [
+            "w: i32",
+            "tiles: i32[(10 * 10)]",
+            "next: *Map",
+            "owner: &Map"
+        ]
+    );
+}
+
+#[test]
+fn enum_decl() {
+    let Decl::Enum(e) = parse_one("enum Color: u8 is\n\tRed\n\tGreen = 4\n\tBlue\nend") else {
+        panic!("not an enum");
+    };
+    assert_eq!(e.name, "Color");
+    assert_eq!(e.ty.map(|t| t.to_string()).as_deref(), Some("u8"));
+    let variants: Vec<String> = e
+        .variants
+        .iter()
+        .map(|v| match &v.value {
+            Some(value) => format!("{} = {}", v.name, value),
+            None => v.name.clone(),
+        })
+        .collect();
+    assert_eq!(variants, ["Red", "Green = 4", "Blue"]);
+
+    let Decl::Enum(e) = parse_one("enum Dir is North; South end") else {
+        panic!("not an enum");
+    };
+    assert!(e.ty.is_none());
+    assert_eq!(e.variants.len(), 2);
+}
+
+#[test]
+fn const_decl() {
+    let Decl::Const(c) = parse_one("const SIZE: i64 = 4 * 1024") else {
+        panic!("not a const");
+    };
+    assert_eq!(c.name, "SIZE");
+    assert_eq!(c.ty.to_string(), "i64");
+    assert_eq!(c.value.to_string(), "(4 * 1024)");
+}
+
+#[test]
+fn function_decl() {
+    let Decl::Function(f) = parse_one("add(a: i32, b: i32): i32 do\n\treturn a + b\nend") else {
+        panic!("not a function");
+    };
+    assert_eq!(f.name, "add");
+    assert!(f.receiver.is_none());
+    let params: Vec<String> = f
+        .params
+        .iter()
+        .map(|p| format!("{}: {}", p.name, p.ty))
+        .collect();
+    assert_eq!(params, ["a: i32", "b: i32"]);
+    assert_eq!(f.ret.map(|t| t.to_string()).as_deref(), Some("i32"));
+    assert_eq!(f.body.stmts.len(), 1);
+
+    let Decl::Function(f) = parse_one("tick() do end") else {
+        panic!("not a function");
+    };
+    assert!(f.params.is_empty() && f.ret.is_none() && f.body.stmts.is_empty());
+}
+
+#[test]
+fn method_decl() {
+    let Decl::Function(f) =
+        parse_one("Entity::move(xd: i32, yd: i32) do\n\tthis.x += xd\n\tthis.y += yd\nend")
+    else {
+        panic!("not a method");
+    };
+    assert_eq!(f.name, "move");
+    assert_eq!(f.receiver.as_deref(), Some("Entity"));
+    assert_eq!(f.params.len(), 2);
+    assert!(f.ret.is_none());
+    assert_eq!(f.body.stmts.len(), 2);
+}
+
+#[test]
+fn namespace_decl() {
+    let Decl::Namespace(n) = parse_one(
+        "namespace Math is\n\tconst PI: f64 = 3.14\n\tnamespace Inner is\n\t\tsq(x: i32): i32 do return x * x end\n\tend\nend",
+    ) else {
+        panic!("not a namespace");
+    };
+    assert_eq!(n.name, "Math");
+    let names: Vec<&str> = n.nodes.iter().map(|d| d.name()).collect();
+    assert_eq!(names, ["PI", "Inner"]);
+    let Decl::Namespace(inner) = &n.nodes[1] else {
+        panic!("not a namespace");
+    };
+    assert!(matches!(&inner.nodes[..], [Decl::Function(f)] if f.name == "sq"));
+}
+
+#[test]
+fn example_program() {
+    let decls = parse(include_str!("../../../examples/syntax.sl"));
+    let names: Vec<&str> = decls.iter().map(|d| d.name()).collect();
+    assert_eq!(names, ["Map", "Entity", "Entity", "move", "main"]);
+}
+
+fn expr(src: &str) -> String {
+    match parse_one(&format!("const X: i32 = {}", src)) {
+        Decl::Const(c) => c.value.to_string(),
+        _ => unreachable!(),
+    }
+}
+
+// Levels from the precedence table in docs/grammar.md, tightest first
+#[test]
+fn precedence() {
+    let cases = [
+        // 1, calls / member access / indexing, chained left to right
+        ("f(a)(b)", "f(a)(b)"),
+        ("a.b.c", "a.b.c"),
+        ("a[i][j]", "a[i][j]"),
+        ("a.f(x)[i].y", "a.f(x)[i].y"),
+        ("f(a + b, c ? d : e)", "f((a + b), (c ? d : e))"),
+        // 2, casts bind looser than postfix operators
+        ("[i64] a.b", "([i64] a.b)"),
+        ("[i64] f(x)", "([i64] f(x))"),
+        ("[f32] [i64] a", "([f32] ([i64] a))"),
+        // 3, unary operators bind looser than postfix operators
+        ("-a.b", "(-a.b)"),
+        ("!f(x)", "(!f(x))"),
+        ("~a[i]", "(~a[i])"),
+        ("!~a", "(!(~a))"),
+        ("-[i64] a", "(-([i64] a))"),
+        ("[i64] -a", "([i64] (-a))"),
+        // 4, multiplicative
+        ("-a * b", "((-a) * b)"),
+        ("[i64] a * b", "(([i64] a) * b)"),
+        ("a * b / c % d", "(((a * b) / c) % d)"),
+        // 5, additive
+        ("a + b * c", "(a + (b * c))"),
+        ("a * b - c", "((a * b) - c)"),
+        ("a - b + c", "((a - b) + c)"),
+        // 6, comparison
+        ("a + b < c - d", "((a + b) < (c - d))"),
+        ("a == b != c", "((a == b) != c)"),
+        ("a < b >= c", "((a < b) >= c)"),
+        // 7, shifts bind looser than comparisons
+        ("a << b < c", "(a << (b < c))"),
+        ("a >> b << c", "((a >> b) << c)"),
+        ("a == b >> c", "((a == b) >> c)"),
+        // 8, &
+        ("a & b << c", "(a & (b << c))"),
+        ("a & b & c", "((a & b) & c)"),
+        // 9, ^
+        ("a ^ b & c", "(a ^ (b & c))"),
+        ("a ^ b ^ c", "((a ^ b) ^ c)"),
+        // 10, |
+        ("a | b ^ c", "(a | (b ^ c))"),
+        ("a | b | c", "((a | b) | c)"),
+        // 11, &&
+        ("a && b | c", "(a && (b | c))"),
+        ("a && b && c", "((a && b) && c)"),
+        // 12, ||
+        ("a || b && c", "(a || (b && c))"),
+        ("a || b || c", "((a || b) || c)"),
+        // 13, the ternary is right associative and loosest
+        ("a || b ? c : d", "((a || b) ? c : d)"),
+        ("a ? b : c ? d : e", "(a ? b : (c ? d : e))"),
+        ("a ? b ? c : d : e", "(a ? (b ? c : d) : e)"),
+        ("a ? b || c : d && e", "(a ? (b || c) : (d && e))"),
+        // Comparisons mixed with bitwise operators compare first
+        ("a & b == c", "(a & (b == c))"),
+        ("a == b | c", "((a == b) | c)"),
+        ("a < b ^ c < d", "((a < b) ^ (c < d))"),
+        ("a | b != c & d", "(a | ((b != c) & d))"),
+        ("a & b < c && d", "((a & (b < c)) && d)"),
+        // Parentheses override all of it
+        ("(a + b) * c", "((a + b) * c)"),
+        ("-(a.b + c)", "(-(a.b + c))"),
+        ("(a ? b : c) ? d : e", "((a ? b : c) ? d : e)"),
+    ];
+    for (src, want) in cases {
+        assert_eq!(expr(src), want, "{}", src);
+    }
+}
+
+#[test]
+fn struct_literal_operand() {
+    assert_eq!(
+        expr("Geo::Point with x = 1 + 2; y = -b end.x * 2"),
+        "(Geo::Point with x = (1 + 2); y = (-b); end.x * 2)"
+    );
+}
+
+#[test]
+fn path_call_stmt() {
+    let Decl::Function(f) = parse_one("main() do\n\tM::hit()\n\tx: i32 = M::N\nend") else {
+        panic!("not a function");
+    };
+    let [first, second] = &f.body.stmts[..] else {
+        panic!("expected two statements");
+    };
+    assert!(matches!(&first.kind, StmtKind::Expr(e) if e.to_string() == "M::hit()"));
+    assert!(matches!(&second.kind, StmtKind::Var { name, .. } if name == "x"));
+}
+
+// Line, column and message of each error, for sources that fail to parse
+fn errors(src: &str) -> Vec<(usize, usize, String)> {
+    let tokens = Lexer::new(src).parse().expect("lexer errors");
+    match Parser::new(tokens).parse() {
+        Ok(_) => panic!("{:?} parsed", src),
+        Err(errors) => errors
+            .iter()
+            .map(|e| (e.span.line, e.span.col, e.msg.clone()))
+            .collect(),
+    }
+}
+
+#[test]
+fn recovers_between_declarations() {
+    assert_eq!(
+        errors(
+            "struct A is\n\tx i32\nend\nf() do\n\ty: = 3\n\treturn\nend\ng(): i32 do return 1 + end"
+        ),
+        [
+            (2, 3, "expected symble :, got I32".to_string()),
+            (5, 4, "expected type, got =".to_string()),
+            (8, 23, "expected expression, got END".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn recovers_inside_blocks() {
+    assert_eq!(
+        errors(
+            "f() do\n\tif a then\n\t\tx = *\n\telse\n\t\ty = = 2\n\tend\n\tz = 1 +\n\tw = 2\nend\nstruct S is x: end"
+        ),
+        [
+            (3, 6, "expected expression, got *".to_string()),
+            (5, 6, "expected expression, got =".to_string()),
+            (7, 8, "expected expression, got TERM".to_string()),
+            (10, 15, "expected type, got END".to_string()),
+        ]
+    );
+}
+
+// A `case` or `else` outside of a block used to stop the parser advancing
+#[test]
+fn stray_block_keywords() {
+    assert_eq!(
+        errors("case\nf() do end\nelse\ng() do end\nend"),
+        [
+            (1, 0, "Invalid Declaration".to_string()),
+            (3, 0, "Invalid Declaration".to_string()),
+            (5, 0, "unexpected END".to_string()),
+        ]
+    );
+}
+
+// Assignments in a function body, as "target op value"
+fn assignments(body: &str) -> Vec<String> {
+    let Decl::Function(f) = parse_one(&format!("f() do\n{}\nend", body)) else {
+        panic!("not a function");
+    };
+    f.body
+        .stmts
+        .iter()
+        .map(|s| match &s.kind {
+            StmtKind::Assign { target, op, value } => format!("{} {:?} {}", target, op, value),
+            _ => panic!("not an assignment"),
+        })
+        .collect()
+}
+
+// The value of a compound assignment is a whole expression, however loose
+// its operators bind
+#[test]
+fn compound_assignment() {
+    assert_eq!(
+        assignments(
+            "x = a | b\nx += a << b | c\nx -= a - b\nx *= a ? b : c\nx /= a / b\nx %= a == b\nx &= a & b ^ c\nx |= a || b\nx ^= a ^ b ^ c"
+        ),
+        [
+            "x Assign (a | b)",
+            "x Add ((a << b) | c)",
+            "x Sub (a - b)",
+            "x Mul (a ? b : c)",
+            "x Div (a / b)",
+            "x Mod (a == b)",
+            "x BitAnd ((a & b) ^ c)",
+            "x BitOr (a || b)",
+            "x BitXor ((a ^ b) ^ c)",
+        ]
+    );
+    assert_eq!(assignments("a.b[i] += 1"), ["a.b[i] Add 1"]);
+}
+
+// Assignments are statements, they don't chain
+#[test]
+fn chained_assignment() {
+    assert_eq!(errors("f() do\n\tx = y = 1\nend").len(), 1);
+    assert_eq!(errors("f() do\n\tx += y -= 1\nend").len(), 1);
+}
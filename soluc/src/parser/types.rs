@@ -1,29 +1,557 @@
 use super::core::Parser;
 use crate::lexer;
+use crate::lexer::Span;
 use std::fmt;
 
-pub type Program = Vec<lexer::Token>;
+pub type Program = Vec<lexer::SpannedToken>;
 pub type Ast = Vec<Namespace>;
 
+// ---------------------------------------
+// Declarations
+// ---------------------------------------
+
 #[derive(Debug, Clone)]
 pub struct Namespace {
     pub name: String,
     pub nodes: Vec<Decl>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub enum Decl {
-    Function,
-    Struct,
-    Enum,
-    Namespace,
-    Const,
+    Function(Function),
+    Struct(Struct),
+    Enum(Enum),
+    Namespace(Namespace),
+    Const(Const),
+}
+
+impl Decl {
+    pub fn name(&self) -> &str {
+        match self {
+            Decl::Function(f) => &f.name,
+            Decl::Struct(s) => &s.name,
+            Decl::Enum(e) => &e.name,
+            Decl::Namespace(n) => &n.name,
+            Decl::Const(c) => &c.name,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Decl::Function(f) => f.span,
+            Decl::Struct(s) => s.span,
+            Decl::Enum(e) => e.span,
+            Decl::Namespace(n) => n.span,
+            Decl::Const(c) => c.span,
+        }
+    }
+}
+
+// Methods are functions with a receiver type (Type::name)
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    pub receiver: Option<String>,
+    pub params: Vec<Param>,
+    pub ret: Option<Type>,
+    pub body: Block,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: String,
+    pub ty: Type,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Struct {
+    pub name: String,
+    pub members: Vec<Member>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub name: String,
+    pub ty: Type,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Enum {
+    pub name: String,
+    // The integer type discriminants are stored as, i32 when omitted
+    pub ty: Option<Type>,
+    pub variants: Vec<Variant>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: String,
+    pub value: Option<Expr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Const {
+    pub name: String,
+    pub ty: Type,
+    pub value: Expr,
+    pub span: Span,
+}
+
+// ---------------------------------------
+// Types
+// ---------------------------------------
+
+#[derive(Debug, Clone)]
+pub struct Type {
+    pub kind: TypeKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum TypeKind {
+    Primitive(Primitive),
+    Named(String),               // Struct or enum, `X::T` when qualified
+    Pointer(Box<Type>),          // *T
+    Ref(Box<Type>),              // &T
+    Array(Box<Type>, Box<Expr>), // T[N] | T[ident]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Primitive {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+    Char,
+    Bool,
+}
+
+impl Primitive {
+    pub fn from_keyword(k: &lexer::Keyword) -> Option<Self> {
+        match k {
+            lexer::Keyword::TypeI8 => Some(Primitive::I8),
+            lexer::Keyword::TypeI16 => Some(Primitive::I16),
+            lexer::Keyword::TypeI32 => Some(Primitive::I32),
+            lexer::Keyword::TypeI64 => Some(Primitive::I64),
+            lexer::Keyword::TypeU8 => Some(Primitive::U8),
+            lexer::Keyword::TypeU16 => Some(Primitive::U16),
+            lexer::Keyword::TypeU32 => Some(Primitive::U32),
+            lexer::Keyword::TypeU64 => Some(Primitive::U64),
+            lexer::Keyword::TypeF32 => Some(Primitive::F32),
+            lexer::Keyword::TypeF64 => Some(Primitive::F64),
+            lexer::Keyword::TypeChar => Some(Primitive::Char),
+            lexer::Keyword::TypeBool => Some(Primitive::Bool),
+            _ => None,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        !matches!(
+            self,
+            Primitive::F32 | Primitive::F64 | Primitive::Bool | Primitive::Char
+        )
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(
+            self,
+            Primitive::I8 | Primitive::I16 | Primitive::I32 | Primitive::I64
+        )
+    }
+
+    // Size in bytes
+    pub fn size(&self) -> u64 {
+        match self {
+            Primitive::I8 | Primitive::U8 | Primitive::Bool => 1,
+            Primitive::I16 | Primitive::U16 => 2,
+            Primitive::I32 | Primitive::U32 | Primitive::F32 | Primitive::Char => 4,
+            Primitive::I64 | Primitive::U64 | Primitive::F64 => 8,
+        }
+    }
+
+    // Whether an integer literal fits, negated literals pass `neg`
+    pub fn fits(&self, n: u64, neg: bool) -> bool {
+        let bits = self.size() * 8;
+        if self.is_signed() {
+            let max = 1u64 << (bits - 1);
+            if neg { n <= max } else { n < max }
+        } else {
+            !neg && (bits == 64 || n < (1u64 << bits))
+        }
+    }
+}
+
+impl fmt::Display for Primitive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Primitive::I8 => write!(f, "i8"),
+            Primitive::I16 => write!(f, "i16"),
+            Primitive::I32 => write!(f, "i32"),
+            Primitive::I64 => write!(f, "i64"),
+            Primitive::U8 => write!(f, "u8"),
+            Primitive::U16 => write!(f, "u16"),
+            Primitive::U32 => write!(f, "u32"),
+            Primitive::U64 => write!(f, "u64"),
+            Primitive::F32 => write!(f, "f32"),
+            Primitive::F64 => write!(f, "f64"),
+            Primitive::Char => write!(f, "char"),
+            Primitive::Bool => write!(f, "bool"),
+        }
+    }
+}
+
+// ---------------------------------------
+// Statements
+// ---------------------------------------
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum StmtKind {
+    Var {
+        mutable: bool,
+        name: String,
+        ty: Type,
+        value: Option<Expr>,
+    },
+    Assign {
+        target: Expr,
+        op: AssignOp,
+        value: Expr,
+    },
+    Expr(Expr),
+    Return(Option<Expr>),
+    Break,
+    Continue,
+    // if/elif conditions in order, else as the fallthrough
+    If {
+        branches: Vec<(Expr, Block)>,
+        else_body: Option<Block>,
+    },
+    While {
+        cond: Expr,
+        body: Block,
+    },
+    // for x in start..end, or for x in iterable when end is None
+    For {
+        var: String,
+        start: Expr,
+        end: Option<Expr>,
+        body: Block,
+    },
+    Switch {
+        value: Expr,
+        cases: Vec<Case>,
+        default: Option<Block>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Case {
+    pub value: Expr,
+    pub body: Block,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssignOp {
+    Assign, // =
+    Add,    // +=
+    Sub,    // -=
+    Mul,    // *=
+    Div,    // /=
+    Mod,    // %=
+    BitAnd, // &=
+    BitOr,  // |=
+    BitXor, // ^=
 }
 
+impl AssignOp {
+    pub fn from_operator(op: &lexer::Operator) -> Option<Self> {
+        match op {
+            lexer::Operator::Eq => Some(AssignOp::Assign),
+            lexer::Operator::PlusEq => Some(AssignOp::Add),
+            lexer::Operator::SubEq => Some(AssignOp::Sub),
+            lexer::Operator::StarEq => Some(AssignOp::Mul),
+            lexer::Operator::DivEq => Some(AssignOp::Div),
+            lexer::Operator::ModEq => Some(AssignOp::Mod),
+            lexer::Operator::AmpEq => Some(AssignOp::BitAnd),
+            lexer::Operator::PipeEq => Some(AssignOp::BitOr),
+            lexer::Operator::CaretEq => Some(AssignOp::BitXor),
+            _ => None,
+        }
+    }
+
+    // The arithmetic behind a compound assignment (x += y => x = x + y)
+    pub fn binary(&self) -> Option<BinaryOp> {
+        match self {
+            AssignOp::Assign => None,
+            AssignOp::Add => Some(BinaryOp::Add),
+            AssignOp::Sub => Some(BinaryOp::Sub),
+            AssignOp::Mul => Some(BinaryOp::Mul),
+            AssignOp::Div => Some(BinaryOp::Div),
+            AssignOp::Mod => Some(BinaryOp::Mod),
+            AssignOp::BitAnd => Some(BinaryOp::BitAnd),
+            AssignOp::BitOr => Some(BinaryOp::BitOr),
+            AssignOp::BitXor => Some(BinaryOp::BitXor),
+        }
+    }
+}
+
+// ---------------------------------------
+// Expressions
+// ---------------------------------------
+
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
+    Int(u64),
+    Float(f64),
+    Bool(bool),
+    Char(char),
+    Str(String),
+    Nil,
+    Ident(String),
+    // X::f, Color::Red
+    Path(Vec<String>),
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    // cond ? then : otherwise
+    Ternary {
+        cond: Box<Expr>,
+        then: Box<Expr>,
+        otherwise: Box<Expr>,
+    },
+    // [type] expr
+    Cast {
+        ty: Type,
+        expr: Box<Expr>,
+    },
+    Call {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    Member {
+        base: Box<Expr>,
+        field: String,
+    },
+    Index {
+        base: Box<Expr>,
+        index: Box<Expr>,
+    },
+    // sizeof(T), alignof(T)
+    SizeOf(Type),
+    AlignOf(Type),
+    // Entity with ... end, the name may be qualified
+    StructLit {
+        name: String,
+        fields: Vec<FieldInit>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldInit {
+    pub name: String,
+    pub value: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,    // -
+    Not,    // !
+    BitNot, // ~
+    Deref,  // * (lvalues only)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    // Arithmatic
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    // Comparison
+    Eq,
+    NotEq,
+    Lt,
+    Gt,
+    LtEq,
+    GtEq,
+    // Bitwise
+    Shl,
+    Shr,
+    BitAnd,
+    BitXor,
+    BitOr,
+    // Logic
+    And,
+    Or,
+}
+
+impl BinaryOp {
+    pub fn from_operator(op: &lexer::Operator) -> Option<Self> {
+        match op {
+            lexer::Operator::Plus => Some(BinaryOp::Add),
+            lexer::Operator::Minus => Some(BinaryOp::Sub),
+            lexer::Operator::Star => Some(BinaryOp::Mul),
+            lexer::Operator::Div => Some(BinaryOp::Div),
+            lexer::Operator::Mod => Some(BinaryOp::Mod),
+            lexer::Operator::EqEq => Some(BinaryOp::Eq),
+            lexer::Operator::NotEq => Some(BinaryOp::NotEq),
+            lexer::Operator::Lt => Some(BinaryOp::Lt),
+            lexer::Operator::Gt => Some(BinaryOp::Gt),
+            lexer::Operator::LtEq => Some(BinaryOp::LtEq),
+            lexer::Operator::GtEq => Some(BinaryOp::GtEq),
+            lexer::Operator::ShiftL => Some(BinaryOp::Shl),
+            lexer::Operator::ShiftR => Some(BinaryOp::Shr),
+            lexer::Operator::Amp => Some(BinaryOp::BitAnd),
+            lexer::Operator::Caret => Some(BinaryOp::BitXor),
+            lexer::Operator::Pipe => Some(BinaryOp::BitOr),
+            lexer::Operator::LAnd => Some(BinaryOp::And),
+            lexer::Operator::LOr => Some(BinaryOp::Or),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnaryOp::Neg => write!(f, "-"),
+            UnaryOp::Not => write!(f, "!"),
+            UnaryOp::BitNot => write!(f, "~"),
+            UnaryOp::Deref => write!(f, "*"),
+        }
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BinaryOp::Add => write!(f, "+"),
+            BinaryOp::Sub => write!(f, "-"),
+            BinaryOp::Mul => write!(f, "*"),
+            BinaryOp::Div => write!(f, "/"),
+            BinaryOp::Mod => write!(f, "%"),
+            BinaryOp::Eq => write!(f, "=="),
+            BinaryOp::NotEq => write!(f, "!="),
+            BinaryOp::Lt => write!(f, "<"),
+            BinaryOp::Gt => write!(f, ">"),
+            BinaryOp::LtEq => write!(f, "<="),
+            BinaryOp::GtEq => write!(f, ">="),
+            BinaryOp::Shl => write!(f, "<<"),
+            BinaryOp::Shr => write!(f, ">>"),
+            BinaryOp::BitAnd => write!(f, "&"),
+            BinaryOp::BitXor => write!(f, "^"),
+            BinaryOp::BitOr => write!(f, "|"),
+            BinaryOp::And => write!(f, "&&"),
+            BinaryOp::Or => write!(f, "||"),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            TypeKind::Primitive(p) => write!(f, "{}", p),
+            TypeKind::Named(n) => write!(f, "{}", n),
+            TypeKind::Pointer(t) => write!(f, "*{}", t),
+            TypeKind::Ref(t) => write!(f, "&{}", t),
+            TypeKind::Array(t, n) => write!(f, "{}[{}]", t, n),
+        }
+    }
+}
+
+// Fully parenthesized so the tree shape is visible
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ExprKind::Int(n) => write!(f, "{}", n),
+            ExprKind::Float(n) => write!(f, "{:?}", n),
+            ExprKind::Bool(b) => write!(f, "{}", b),
+            ExprKind::Char(c) => write!(f, "{:?}", c),
+            ExprKind::Str(s) => write!(f, "{:?}", s),
+            ExprKind::Nil => write!(f, "nil"),
+            ExprKind::SizeOf(ty) => write!(f, "sizeof({})", ty),
+            ExprKind::AlignOf(ty) => write!(f, "alignof({})", ty),
+            ExprKind::Ident(s) => write!(f, "{}", s),
+            ExprKind::Path(p) => write!(f, "{}", p.join("::")),
+            ExprKind::Unary { op, expr } => write!(f, "({}{})", op, expr),
+            ExprKind::Binary { op, lhs, rhs } => write!(f, "({} {} {})", lhs, op, rhs),
+            ExprKind::Ternary {
+                cond,
+                then,
+                otherwise,
+            } => write!(f, "({} ? {} : {})", cond, then, otherwise),
+            ExprKind::Cast { ty, expr } => write!(f, "([{}] {})", ty, expr),
+            ExprKind::Call { callee, args } => {
+                write!(f, "{}(", callee)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
+            ExprKind::Member { base, field } => write!(f, "{}.{}", base, field),
+            ExprKind::Index { base, index } => write!(f, "{}[{}]", base, index),
+            ExprKind::StructLit { name, fields } => {
+                write!(f, "{} with", name)?;
+                for field in fields {
+                    write!(f, " {} = {};", field.name, field.value)?;
+                }
+                write!(f, " end")
+            }
+        }
+    }
+}
+
+// ---------------------------------------
+// Errors
+// ---------------------------------------
+
 #[derive(Debug, Clone)]
 pub struct ParserError {
-    span: lexer::Span,
-    msg: String,
+    pub span: lexer::Span,
+    pub msg: String,
 }
 
 impl fmt::Display for ParserError {
@@ -35,12 +563,7 @@ impl fmt::Display for ParserError {
 impl ParserError {
     pub fn new(par: &Parser, msg: impl Into<String>) -> Self {
         Self {
-            span: lexer::Span {
-                start: par.pos,
-                end: par.pos,
-                line: par.pos,
-                col: par.pos,
-            },
+            span: par.span(),
             msg: msg.into(),
         }
     }
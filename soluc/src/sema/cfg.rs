@@ -0,0 +1,278 @@
+use crate::diagnostics::{self, Diagnostic};
+use crate::lexer::Span;
+use crate::parser::*;
+use std::collections::HashSet;
+
+pub type BlockId = usize;
+
+// What a basic block does, in order
+#[derive(Debug, Clone, Copy)]
+pub enum Item<'a> {
+    // Every statement is listed in the block it starts in. Compound
+    // statements come before the items of their header.
+    Stmt(&'a Stmt),
+    // Expression evaluated to pick a branch: if/while conditions, switch
+    // values and case labels, for loop ranges
+    Cond(&'a Expr),
+    // A for loop variable, assigned at the top of every iteration
+    Bind(&'a str, &'a Stmt),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BasicBlock<'a> {
+    pub items: Vec<Item<'a>>,
+    pub succs: Vec<BlockId>,
+    pub preds: Vec<BlockId>,
+    // Block and statement this one follows. The block is unreachable
+    // because of that statement when the block it's in is reachable.
+    pub after: Option<(BlockId, Span)>,
+}
+
+// Control flow graph of one function body. Every `return` and the end of the
+// body lead to `exit`.
+#[derive(Debug, Clone)]
+pub struct Cfg<'a> {
+    pub blocks: Vec<BasicBlock<'a>>,
+    pub entry: BlockId,
+    pub exit: BlockId,
+    // Block running off the end of the body without a `return`
+    pub fallthrough: BlockId,
+}
+
+impl<'a> Cfg<'a> {
+    // Builds the graph, reporting `break`/`continue` outside of loops
+    // `exhaustive` holds the switches that handle every variant of an enum
+    // without a `default`, they can't fall through
+    pub fn build(
+        body: &'a Block,
+        exhaustive: &HashSet<(usize, usize)>,
+        diags: &mut Vec<Diagnostic>,
+    ) -> Self {
+        let mut b = Builder {
+            cfg: Cfg {
+                blocks: vec![BasicBlock::default(), BasicBlock::default()],
+                entry: 0,
+                exit: 1,
+                fallthrough: 0,
+            },
+            cur: 0,
+            loops: vec![],
+            exhaustive,
+            diags,
+        };
+        b.block(body);
+        let end = b.cur;
+        b.edge(end, 1);
+        b.cfg.fallthrough = end;
+        b.cfg
+    }
+
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut seen = vec![false; self.blocks.len()];
+        let mut stack = vec![self.entry];
+        while let Some(id) = stack.pop() {
+            if seen[id] {
+                continue;
+            }
+            seen[id] = true;
+            stack.extend(self.blocks[id].succs.iter().copied());
+        }
+        seen
+    }
+}
+
+struct Builder<'a, 'd> {
+    cfg: Cfg<'a>,
+    cur: BlockId,
+    // (continue target, break target) of each enclosing loop
+    loops: Vec<(BlockId, BlockId)>,
+    exhaustive: &'d HashSet<(usize, usize)>,
+    diags: &'d mut Vec<Diagnostic>,
+}
+
+impl<'a> Builder<'a, '_> {
+    fn block(&mut self, block: &'a Block) {
+        for stmt in block.stmts.iter() {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &'a Stmt) {
+        self.push(Item::Stmt(stmt));
+        match &stmt.kind {
+            StmtKind::Var { .. } | StmtKind::Assign { .. } | StmtKind::Expr(_) => {}
+            StmtKind::Return(_) => {
+                self.edge(self.cur, self.cfg.exit);
+                self.cur = self.dead_block(stmt.span);
+            }
+            StmtKind::Break | StmtKind::Continue => {
+                let is_break = matches!(stmt.kind, StmtKind::Break);
+                match self.loops.last() {
+                    Some(&(cont, brk)) => {
+                        self.edge(self.cur, if is_break { brk } else { cont });
+                        self.cur = self.dead_block(stmt.span);
+                    }
+                    None => {
+                        let word = if is_break { "break" } else { "continue" };
+                        self.diags.push(
+                            Diagnostic::error(
+                                if is_break {
+                                    diagnostics::BREAK_OUTSIDE_LOOP
+                                } else {
+                                    diagnostics::CONTINUE_OUTSIDE_LOOP
+                                },
+                                stmt.span,
+                                format!("`{}` outside of a loop", word),
+                            )
+                            .with_label(format!("cannot `{}` here", word)),
+                        );
+                    }
+                }
+            }
+            StmtKind::If {
+                branches,
+                else_body,
+            } => {
+                let origin = self.cur;
+                let join = self.new_block();
+                for (cond, body) in branches.iter() {
+                    self.push(Item::Cond(cond));
+                    let then = self.new_block();
+                    // a `false` branch is never taken but stays in the graph
+                    if !is_const_bool(cond, false) {
+                        self.edge(self.cur, then);
+                    }
+                    let test = self.cur;
+                    self.cur = then;
+                    self.block(body);
+                    self.edge(self.cur, join);
+                    if is_const_bool(cond, true) {
+                        // later branches are never taken either
+                        self.cur = self.new_block();
+                    } else {
+                        let next = self.new_block();
+                        self.edge(test, next);
+                        self.cur = next;
+                    }
+                }
+                if let Some(body) = else_body {
+                    self.block(body);
+                }
+                self.edge(self.cur, join);
+                self.seal_join(join, origin, stmt);
+            }
+            StmtKind::While { cond, body } => {
+                let header = self.new_block();
+                // only left through `break` when the condition is `true`
+                let after = if is_const_bool(cond, true) {
+                    self.dead_block(stmt.span)
+                } else {
+                    self.new_block()
+                };
+                self.edge(self.cur, header);
+                self.cur = header;
+                self.push(Item::Cond(cond));
+                if !is_const_bool(cond, true) {
+                    self.edge(header, after);
+                }
+                self.loop_body(header, after, body);
+                self.cur = after;
+            }
+            StmtKind::For {
+                var,
+                start,
+                end,
+                body,
+            } => {
+                self.push(Item::Cond(start));
+                if let Some(end) = end {
+                    self.push(Item::Cond(end));
+                }
+                let header = self.new_block();
+                let after = self.new_block();
+                self.edge(self.cur, header);
+                self.edge(header, after);
+                let bind = self.new_block();
+                self.edge(header, bind);
+                self.cur = bind;
+                self.push(Item::Bind(var, stmt));
+                self.loops.push((header, after));
+                self.block(body);
+                self.loops.pop();
+                self.edge(self.cur, header);
+                self.cur = after;
+            }
+            StmtKind::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                self.push(Item::Cond(value));
+                let test = self.cur;
+                let join = self.new_block();
+                for case in cases.iter() {
+                    let arm = self.new_block();
+                    self.edge(test, arm);
+                    self.cur = arm;
+                    self.push(Item::Cond(&case.value));
+                    self.block(&case.body);
+                    self.edge(self.cur, join);
+                }
+                match default {
+                    Some(body) => {
+                        let arm = self.new_block();
+                        self.edge(test, arm);
+                        self.cur = arm;
+                        self.block(body);
+                        self.edge(self.cur, join);
+                    }
+                    None if self.exhaustive.contains(&(stmt.span.start, stmt.span.end)) => {}
+                    None => self.edge(test, join),
+                }
+                self.seal_join(join, test, stmt);
+            }
+        }
+    }
+
+    fn loop_body(&mut self, cont: BlockId, brk: BlockId, body: &'a Block) {
+        let start = self.new_block();
+        self.edge(self.cur, start);
+        self.cur = start;
+        self.loops.push((cont, brk));
+        self.block(body);
+        self.loops.pop();
+        self.edge(self.cur, cont);
+    }
+
+    // Continues after a branching statement, which is unreachable when every
+    // branch left through `return`/`break`/`continue`
+    fn seal_join(&mut self, join: BlockId, origin: BlockId, stmt: &Stmt) {
+        self.cfg.blocks[join].after = Some((origin, stmt.span));
+        self.cur = join;
+    }
+
+    fn push(&mut self, item: Item<'a>) {
+        self.cfg.blocks[self.cur].items.push(item);
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        self.cfg.blocks.push(BasicBlock::default());
+        self.cfg.blocks.len() - 1
+    }
+
+    fn dead_block(&mut self, after: Span) -> BlockId {
+        let id = self.new_block();
+        self.cfg.blocks[id].after = Some((self.cur, after));
+        id
+    }
+
+    fn edge(&mut self, from: BlockId, to: BlockId) {
+        self.cfg.blocks[from].succs.push(to);
+        self.cfg.blocks[to].preds.push(from);
+    }
+}
+
+// `true`/`false` literal conditions decide the branch at compile time
+fn is_const_bool(cond: &Expr, value: bool) -> bool {
+    matches!(cond.kind, ExprKind::Bool(b) if b == value)
+}
@@ -0,0 +1,512 @@
+use super::enums::EnumInfo;
+use super::layout::StructLayout;
+use super::symbols::*;
+use super::typeck::is_literal;
+use crate::diagnostics::{self, Diagnostic};
+use crate::lexer::Span;
+use crate::parser::*;
+use std::collections::HashMap;
+use std::fmt;
+
+// Result of evaluating a constant expression. Integers are kept wide enough
+// for any primitive and checked against their type after every operation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i128, Primitive),
+    Float(f64, Primitive),
+    Bool(bool),
+    Char(char),
+    Str(String),
+    Variant(String, String, i128), // Enum path, variant and discriminant
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(n, _) => write!(f, "{}", n),
+            Value::Float(x, _) => write!(f, "{}", x),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Char(c) => write!(f, "{:?}", c),
+            Value::Str(s) => write!(f, "{:?}", s),
+            Value::Variant(e, v, _) => write!(f, "{}::{}", e, v),
+        }
+    }
+}
+
+// Evaluates expressions at compile time: literals, `const`s, enum variants,
+// operators, casts between primitives and `sizeof`/`alignof`. Type errors are
+// left to the type checker, evaluation just gives up on them.
+pub struct ConstEval<'a> {
+    pub(super) table: &'a SymbolTable,
+    decls: HashMap<String, (&'a Const, NsId)>,
+    // None when the initializer couldn't be evaluated
+    values: HashMap<String, Option<Value>>,
+    // Consts being evaluated, to catch cycles
+    visiting: Vec<String>,
+    // Array lengths by the span of their size expression
+    lens: HashMap<(usize, usize), Option<i128>>,
+    // Struct declarations in source order, and their layouts once computed
+    pub(super) structs: Vec<(String, &'a Struct, NsId)>,
+    pub(super) layouts: HashMap<String, Option<StructLayout>>,
+    // Structs being laid out and the field currently being placed in each,
+    // to catch structs that contain themselves
+    pub(super) laying_out: Vec<(String, Option<Span>)>,
+    // Enum declarations in source order, and their discriminants once
+    // computed
+    pub(super) enums: Vec<(String, &'a Enum, NsId)>,
+    pub(super) discriminants: HashMap<String, Option<EnumInfo>>,
+    // Enums whose discriminants are being computed, with the variants done
+    // so far. Later variants can refer to earlier ones.
+    pub(super) numbering: Vec<(String, Vec<(String, i128)>)>,
+    pub diags: Vec<Diagnostic>,
+}
+
+impl<'a> ConstEval<'a> {
+    pub fn new(ast: &'a Namespace, table: &'a SymbolTable) -> Self {
+        let mut eval = ConstEval {
+            table,
+            decls: HashMap::new(),
+            values: HashMap::new(),
+            visiting: vec![],
+            lens: HashMap::new(),
+            structs: vec![],
+            layouts: HashMap::new(),
+            laying_out: vec![],
+            enums: vec![],
+            discriminants: HashMap::new(),
+            numbering: vec![],
+            diags: vec![],
+        };
+        eval.collect(&ast.nodes, GLOBAL);
+        eval
+    }
+
+    fn collect(&mut self, nodes: &'a [Decl], ns: NsId) {
+        for decl in nodes {
+            match decl {
+                Decl::Namespace(n) => {
+                    if let Some(SymbolKind::Namespace(id)) =
+                        self.table.scopes[ns].items.get(&n.name).map(|s| &s.kind)
+                    {
+                        self.collect(&n.nodes, *id);
+                    }
+                }
+                Decl::Const(c) => {
+                    self.decls.insert(self.table.qualify(ns, &c.name), (c, ns));
+                }
+                Decl::Struct(s) => {
+                    self.structs.push((self.table.qualify(ns, &s.name), s, ns));
+                }
+                Decl::Enum(e) => {
+                    self.enums.push((self.table.qualify(ns, &e.name), e, ns));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Every const evaluated so far, by qualified path
+    pub fn values(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.values
+            .iter()
+            .filter_map(|(path, v)| v.as_ref().map(|v| (path, v)))
+    }
+
+    // Value of the const at `path`, evaluated the first time it's asked for
+    pub fn value_of(&mut self, path: &str) -> Option<Value> {
+        if let Some(v) = self.values.get(path) {
+            return v.clone();
+        }
+        let &(c, ns) = self.decls.get(path)?;
+        if let Some(pos) = self.visiting.iter().position(|p| p == path) {
+            let cycle = self.visiting[pos..].join("` -> `");
+            self.diags.push(
+                Diagnostic::error(
+                    diagnostics::CONST_CYCLE,
+                    c.span,
+                    format!("constant `{}` depends on itself", c.name),
+                )
+                .with_note(format!("cycle: `{}` -> `{}`", cycle, path)),
+            );
+            self.values.insert(path.to_string(), None);
+            return None;
+        }
+        self.visiting.push(path.to_string());
+        let expected = match c.ty.kind {
+            TypeKind::Primitive(p) => Some(p),
+            _ => None,
+        };
+        let v = self
+            .eval(&c.value, ns, expected)
+            .map(|v| coerce(v, expected));
+        self.visiting.pop();
+        // A cycle through this const already stored None
+        self.values.entry(path.to_string()).or_insert(v).clone()
+    }
+
+    // Length of an array type, evaluated once per size expression. Negative
+    // lengths are reported by the type checker.
+    pub fn array_len(&mut self, size: &Expr, ns: NsId) -> Option<i128> {
+        let key = (size.span.start, size.span.end);
+        if let Some(len) = self.lens.get(&key) {
+            return *len;
+        }
+        let len = match self.eval(size, ns, Some(Primitive::U64)) {
+            Some(Value::Int(n, _)) => Some(n),
+            _ => None,
+        };
+        self.lens.insert(key, len);
+        len
+    }
+
+    // `expected` types integer and float literals, like the type checker
+    // does from their context
+    pub fn eval(&mut self, expr: &Expr, ns: NsId, expected: Option<Primitive>) -> Option<Value> {
+        match &expr.kind {
+            ExprKind::Int(n) => Some(match expected {
+                Some(p @ (Primitive::F32 | Primitive::F64)) => Value::Float(*n as f64, p),
+                Some(p) if p.is_integer() => Value::Int(*n as i128, p),
+                _ => Value::Int(*n as i128, Primitive::I32),
+            }),
+            ExprKind::Float(x) => Some(match expected {
+                Some(Primitive::F32) => Value::Float(*x as f32 as f64, Primitive::F32),
+                _ => Value::Float(*x, Primitive::F64),
+            }),
+            ExprKind::Bool(b) => Some(Value::Bool(*b)),
+            ExprKind::Char(c) => Some(Value::Char(*c)),
+            ExprKind::Str(s) => Some(Value::Str(s.clone())),
+            ExprKind::Ident(name) => match self.table.lookup(ns, name) {
+                Some(sym) if matches!(sym.kind, SymbolKind::Const) => {
+                    let path = sym.path.clone();
+                    self.value_of(&path)
+                }
+                Some(_) => self.not_constant(expr, format!("`{}` is not a constant", name)),
+                // Reported by the resolver
+                None => None,
+            },
+            ExprKind::Path(path) => match self.table.lookup_path(ns, path) {
+                Ok(Res::Symbol(sym)) if matches!(sym.kind, SymbolKind::Const) => {
+                    let path = sym.path.clone();
+                    self.value_of(&path)
+                }
+                Ok(Res::Variant(sym, idx)) => {
+                    let path = sym.path.clone();
+                    let (name, n) = self.variant(expr, &path, idx)?;
+                    Some(Value::Variant(path, name, n))
+                }
+                Ok(_) => {
+                    self.not_constant(expr, format!("`{}` is not a constant", path.join("::")))
+                }
+                Err(_) => None,
+            },
+            ExprKind::Unary { op, expr: inner } => {
+                let v = self.eval(inner, ns, expected)?;
+                let result = eval_unary(*op, v);
+                self.report(expr, result)
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                // A literal on the left takes its type from the right
+                let (l, r) = if is_literal(lhs) && !is_literal(rhs) {
+                    let r = self.eval(rhs, ns, expected)?;
+                    let l = self.eval(lhs, ns, prim_of(&r))?;
+                    (l, r)
+                } else {
+                    let l = self.eval(lhs, ns, expected)?;
+                    let hint = if matches!(op, BinaryOp::Shl | BinaryOp::Shr) {
+                        None
+                    } else {
+                        prim_of(&l)
+                    };
+                    let r = self.eval(rhs, ns, hint)?;
+                    (l, r)
+                };
+                let result = eval_binary(*op, l, r);
+                self.report(expr, result)
+            }
+            ExprKind::Ternary {
+                cond,
+                then,
+                otherwise,
+            } => match self.eval(cond, ns, None)? {
+                Value::Bool(true) => self.eval(then, ns, expected),
+                Value::Bool(false) => self.eval(otherwise, ns, expected),
+                _ => None,
+            },
+            ExprKind::Cast { ty, expr: inner } => {
+                if let Some(path) = self.enum_path(ty, ns) {
+                    let v = self.eval(inner, ns, None)?;
+                    return self.cast_to_enum(expr, v, &path);
+                }
+                let TypeKind::Primitive(to) = ty.kind else {
+                    return self.not_constant(
+                        expr,
+                        format!("casts to `{}` can't be evaluated at compile time", ty),
+                    );
+                };
+                let v = self.eval(inner, ns, None)?;
+                let result = eval_cast(v, to);
+                self.report(expr, result)
+            }
+            ExprKind::SizeOf(ty) | ExprKind::AlignOf(ty) => {
+                let layout = self.type_layout(ty, ns)?;
+                let n = if matches!(expr.kind, ExprKind::SizeOf(_)) {
+                    layout.size
+                } else {
+                    layout.align
+                };
+                let p = expected
+                    .filter(|p| p.is_integer())
+                    .unwrap_or(Primitive::U64);
+                self.checked(expr, Some(n as i128), p)
+            }
+            ExprKind::Nil => self.not_constant(expr, "`nil` is not a constant"),
+            ExprKind::Call { .. } => {
+                self.not_constant(expr, "calls can't be evaluated at compile time")
+            }
+            ExprKind::Member { .. } | ExprKind::Index { .. } | ExprKind::StructLit { .. } => {
+                self.not_constant(expr, "only primitive values can be constants")
+            }
+        }
+    }
+
+    // Turns an evaluation error into a diagnostic at `expr`
+    fn report(&mut self, expr: &Expr, result: Result<Value, EvalError>) -> Option<Value> {
+        match result {
+            Ok(v) => Some(v),
+            Err(EvalError::Overflow(p)) => {
+                self.overflow(expr, format!("`{}` overflows `{}`", expr, p));
+                None
+            }
+            Err(EvalError::ShiftOverflow(b, p)) => {
+                self.overflow(expr, format!("shift by {} overflows `{}`", b, p));
+                None
+            }
+            Err(EvalError::InvalidChar(n)) => {
+                self.overflow(expr, format!("{} is not a valid `char`", n));
+                None
+            }
+            Err(EvalError::DivisionByZero) => {
+                self.diags.push(
+                    Diagnostic::error(
+                        diagnostics::DIVISION_BY_ZERO,
+                        expr.span,
+                        format!("`{}` divides by zero", expr),
+                    )
+                    .with_label("evaluated at compile time"),
+                );
+                None
+            }
+            Err(EvalError::Unsupported) => None,
+        }
+    }
+
+    // Result of integer arithmetic, reported at `expr` when it doesn't fit
+    fn checked(&mut self, expr: &Expr, n: Option<i128>, p: Primitive) -> Option<Value> {
+        let result = checked(n, p);
+        self.report(expr, result)
+    }
+
+    pub(super) fn overflow(&mut self, expr: &Expr, msg: String) {
+        self.diags.push(
+            Diagnostic::error(diagnostics::CONST_OVERFLOW, expr.span, msg)
+                .with_label("evaluated at compile time"),
+        );
+    }
+
+    fn not_constant(&mut self, expr: &Expr, msg: impl Into<String>) -> Option<Value> {
+        self.diags.push(not_constant(expr.span, msg));
+        None
+    }
+}
+
+pub fn not_constant(span: Span, msg: impl Into<String>) -> Diagnostic {
+    Diagnostic::error(diagnostics::NOT_CONSTANT, span, msg)
+        .with_label("not a constant expression")
+        .with_help("only literals, `const`s, enum variants, operators and casts can be used here")
+}
+
+// Why an operation on constants has no value
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    Overflow(Primitive), // The result doesn't fit the type
+    ShiftOverflow(i128, Primitive),
+    DivisionByZero,
+    InvalidChar(i128),
+    // Not an operation on these values, left to the type checker
+    Unsupported,
+}
+
+pub fn eval_unary(op: UnaryOp, v: Value) -> Result<Value, EvalError> {
+    match (op, v) {
+        (UnaryOp::Neg, Value::Int(n, p)) if p.is_signed() => checked(n.checked_neg(), p),
+        (UnaryOp::Neg, Value::Float(x, p)) => Ok(Value::Float(-x, p)),
+        (UnaryOp::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+        (UnaryOp::BitNot, Value::Int(n, p)) => Ok(Value::Int(wrap(!n, p), p)),
+        _ => Err(EvalError::Unsupported),
+    }
+}
+
+// Integer arithmetic is checked against the type of the operands
+pub fn eval_binary(op: BinaryOp, l: Value, r: Value) -> Result<Value, EvalError> {
+    use BinaryOp::*;
+    match (l, r) {
+        (Value::Int(a, p), Value::Int(b, _)) if matches!(op, Shl | Shr) => {
+            let bits = p.size() as i128 * 8;
+            if b < 0 || b >= bits {
+                return Err(EvalError::ShiftOverflow(b, p));
+            }
+            Ok(Value::Int(
+                if op == Shl { wrap(a << b, p) } else { a >> b },
+                p,
+            ))
+        }
+        (Value::Int(a, p), Value::Int(b, q)) if p == q => match op {
+            Add => checked(a.checked_add(b), p),
+            Sub => checked(a.checked_sub(b), p),
+            Mul => checked(a.checked_mul(b), p),
+            Div | Mod if b == 0 => Err(EvalError::DivisionByZero),
+            Div => checked(a.checked_div(b), p),
+            Mod => checked(a.checked_rem(b), p),
+            BitAnd => Ok(Value::Int(a & b, p)),
+            BitOr => Ok(Value::Int(a | b, p)),
+            BitXor => Ok(Value::Int(a ^ b, p)),
+            _ => compare(op, a.cmp(&b)),
+        },
+        (Value::Float(a, p), Value::Float(b, q)) if p == q => {
+            let x = match op {
+                Add => a + b,
+                Sub => a - b,
+                Mul => a * b,
+                Div => a / b,
+                Mod => a % b,
+                _ => return compare(op, a.partial_cmp(&b).ok_or(EvalError::Unsupported)?),
+            };
+            let x = if p == Primitive::F32 {
+                x as f32 as f64
+            } else {
+                x
+            };
+            if !x.is_finite() && a.is_finite() && b.is_finite() {
+                if matches!(op, Div | Mod) && b == 0.0 {
+                    return Err(EvalError::DivisionByZero);
+                }
+                return Err(EvalError::Overflow(p));
+            }
+            Ok(Value::Float(x, p))
+        }
+        (Value::Bool(a), Value::Bool(b)) => match op {
+            And => Ok(Value::Bool(a && b)),
+            Or => Ok(Value::Bool(a || b)),
+            Eq => Ok(Value::Bool(a == b)),
+            NotEq => Ok(Value::Bool(a != b)),
+            _ => Err(EvalError::Unsupported),
+        },
+        (Value::Char(a), Value::Char(b)) => compare(op, a.cmp(&b)),
+        (Value::Variant(e, _, a), Value::Variant(f, _, b)) if e == f => compare(op, a.cmp(&b)),
+        _ => Err(EvalError::Unsupported),
+    }
+}
+
+// Explicit casts truncate integers, only values that can't be represented
+// at all are errors
+pub fn eval_cast(v: Value, to: Primitive) -> Result<Value, EvalError> {
+    let int = match v {
+        Value::Int(n, _) => n,
+        Value::Char(c) => c as i128,
+        Value::Bool(b) => b as i128,
+        Value::Variant(_, _, n) => n,
+        Value::Float(x, _) => {
+            if matches!(to, Primitive::F32 | Primitive::F64) {
+                return Ok(float(x, to));
+            }
+            let n = x.trunc();
+            if !x.is_finite() || n < i128::MIN as f64 || n > i128::MAX as f64 {
+                return Err(EvalError::Overflow(to));
+            }
+            let n = n as i128;
+            if to.is_integer() && !int_fits(n, to) {
+                return Err(EvalError::Overflow(to));
+            }
+            n
+        }
+        Value::Str(_) => return Err(EvalError::Unsupported),
+    };
+    match to {
+        Primitive::F32 | Primitive::F64 => Ok(float(int as f64, to)),
+        Primitive::Bool => Ok(Value::Bool(int != 0)),
+        Primitive::Char => u32::try_from(int)
+            .ok()
+            .and_then(char::from_u32)
+            .map(Value::Char)
+            .ok_or(EvalError::InvalidChar(int)),
+        p => Ok(Value::Int(wrap(int, p), p)),
+    }
+}
+
+// Result of integer arithmetic, an error when it doesn't fit `p` or
+// overflowed i128 itself
+fn checked(n: Option<i128>, p: Primitive) -> Result<Value, EvalError> {
+    match n {
+        Some(n) if int_fits(n, p) => Ok(Value::Int(n, p)),
+        _ => Err(EvalError::Overflow(p)),
+    }
+}
+
+fn compare(op: BinaryOp, ord: std::cmp::Ordering) -> Result<Value, EvalError> {
+    use std::cmp::Ordering::*;
+    let b = match op {
+        BinaryOp::Eq => ord == Equal,
+        BinaryOp::NotEq => ord != Equal,
+        BinaryOp::Lt => ord == Less,
+        BinaryOp::Gt => ord == Greater,
+        BinaryOp::LtEq => ord != Greater,
+        BinaryOp::GtEq => ord != Less,
+        _ => return Err(EvalError::Unsupported),
+    };
+    Ok(Value::Bool(b))
+}
+
+// Implicit conversion of a value to the type it's stored as, already checked
+// to be allowed by the type checker
+fn coerce(v: Value, to: Option<Primitive>) -> Value {
+    match (v, to) {
+        (Value::Int(n, _), Some(p)) if p.is_integer() => Value::Int(n, p),
+        (Value::Int(n, _), Some(p @ (Primitive::F32 | Primitive::F64))) => float(n as f64, p),
+        (Value::Float(x, _), Some(Primitive::F64)) => Value::Float(x, Primitive::F64),
+        (v, _) => v,
+    }
+}
+
+fn prim_of(v: &Value) -> Option<Primitive> {
+    match v {
+        Value::Int(_, p) | Value::Float(_, p) => Some(*p),
+        _ => None,
+    }
+}
+
+fn float(x: f64, p: Primitive) -> Value {
+    if p == Primitive::F32 {
+        Value::Float(x as f32 as f64, p)
+    } else {
+        Value::Float(x, p)
+    }
+}
+
+pub(super) fn int_fits(n: i128, p: Primitive) -> bool {
+    if n < 0 {
+        n >= -(u64::MAX as i128) && p.fits(n.unsigned_abs() as u64, true)
+    } else {
+        n <= u64::MAX as i128 && p.fits(n as u64, false)
+    }
+}
+
+// Truncates to the width of `p`, reinterpreting the top bit for signed types
+fn wrap(n: i128, p: Primitive) -> i128 {
+    let bits = p.size() * 8;
+    let m = n & ((1i128 << bits) - 1);
+    if p.is_signed() && m >= 1i128 << (bits - 1) {
+        m - (1i128 << bits)
+    } else {
+        m
+    }
+}
@@ -0,0 +1,183 @@
+use super::consteval::{ConstEval, Value, int_fits};
+use super::symbols::*;
+use crate::diagnostics::{self, Diagnostic};
+use crate::parser::*;
+
+// An enum's underlying type and the discriminant of each variant, in
+// declaration order
+#[derive(Debug, Clone)]
+pub struct EnumInfo {
+    pub repr: Primitive,
+    pub variants: Vec<(String, i128)>,
+}
+
+impl EnumInfo {
+    pub fn discriminant(&self, variant: &str) -> Option<i128> {
+        self.variants
+            .iter()
+            .find(|(name, _)| name == variant)
+            .map(|(_, n)| *n)
+    }
+
+    pub fn variant_of(&self, n: i128) -> Option<&str> {
+        self.variants
+            .iter()
+            .find(|(_, m)| *m == n)
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+// Discriminants are stored as i32 unless the enum names another integer type.
+// Other types are reported by the type checker.
+pub fn enum_repr(e: &Enum) -> Primitive {
+    match e.ty.as_ref().map(|t| &t.kind) {
+        Some(TypeKind::Primitive(p)) if p.is_integer() => *p,
+        _ => Primitive::I32,
+    }
+}
+
+impl ConstEval<'_> {
+    // Discriminants of the enum at `path`, numbered the first time they're
+    // asked for. A variant without a value is one more than the variant
+    // before it, the first one is 0.
+    pub fn enum_info(&mut self, path: &str) -> Option<EnumInfo> {
+        if let Some(info) = self.discriminants.get(path) {
+            return info.clone();
+        }
+        if self.numbering.iter().any(|(p, _)| p == path) {
+            return None;
+        }
+        let &(_, e, ns) = self.enums.iter().find(|(p, ..)| p == path)?;
+        let repr = enum_repr(e);
+
+        self.numbering.push((path.to_string(), vec![]));
+        let mut next = Some(0i128);
+        for v in e.variants.iter() {
+            let n = match &v.value {
+                Some(value) => match self.eval(value, ns, Some(repr)) {
+                    Some(Value::Int(n, _)) if int_fits(n, repr) => Some(n),
+                    Some(Value::Int(n, _)) => {
+                        self.overflow(value, format!("{} doesn't fit in `{}`", n, repr));
+                        None
+                    }
+                    // Values of the wrong type are reported by the type checker
+                    _ => None,
+                },
+                None => match next {
+                    Some(n) if int_fits(n, repr) => Some(n),
+                    Some(_) => {
+                        self.diags.push(
+                            Diagnostic::error(
+                                diagnostics::CONST_OVERFLOW,
+                                v.span,
+                                format!("discriminant of `{}` overflows `{}`", v.name, repr),
+                            )
+                            .with_label("one more than the variant before it")
+                            .with_help("give it an explicit value"),
+                        );
+                        None
+                    }
+                    None => None,
+                },
+            };
+
+            // Variants that couldn't be numbered still get a value so the
+            // enum stays usable, the error has been reported
+            let done = &self.numbering.last().unwrap().1;
+            let value = n.unwrap_or_else(|| next.unwrap_or(0));
+            if n.is_some()
+                && let Some((prev, _)) = done.iter().find(|(_, m)| *m == value)
+                && let Some(first) = e.variants.iter().find(|p| p.name == *prev)
+            {
+                self.diags.push(
+                    Diagnostic::error(
+                        diagnostics::DUPLICATE_DISCRIMINANT,
+                        v.span,
+                        format!("`{}` has the same value as `{}`", v.name, prev),
+                    )
+                    .with_label(format!("= {}", value))
+                    .with_secondary(first.span, "first used here")
+                    .with_help("every variant needs its own value"),
+                );
+            }
+            self.numbering
+                .last_mut()
+                .unwrap()
+                .1
+                .push((v.name.clone(), value));
+            next = value.checked_add(1);
+        }
+        let (_, variants) = self.numbering.pop().unwrap();
+
+        let info = EnumInfo { repr, variants };
+        self.discriminants
+            .insert(path.to_string(), Some(info.clone()));
+        Some(info)
+    }
+
+    // Name and discriminant of the `idx`th variant of an enum. While the
+    // enum itself is being numbered only the variants before the current
+    // one are known.
+    pub(super) fn variant(
+        &mut self,
+        expr: &Expr,
+        path: &str,
+        idx: usize,
+    ) -> Option<(String, i128)> {
+        if let Some((_, done)) = self.numbering.iter().find(|(p, _)| p == path) {
+            if let Some(v) = done.get(idx) {
+                return Some(v.clone());
+            }
+            self.diags.push(
+                Diagnostic::error(
+                    diagnostics::CONST_CYCLE,
+                    expr.span,
+                    format!("discriminants of enum `{}` depend on themselves", path),
+                )
+                .with_label("its value isn't known yet")
+                .with_note("a variant can only refer to the variants declared before it"),
+            );
+            return None;
+        }
+        self.enum_info(path)?.variants.get(idx).cloned()
+    }
+
+    // Path of the enum a type names, None for any other type
+    pub(super) fn enum_path(&self, ty: &Type, ns: NsId) -> Option<String> {
+        let TypeKind::Named(name) = &ty.kind else {
+            return None;
+        };
+        let sym = self.table.lookup_type(ns, name)?;
+        matches!(sym.kind, SymbolKind::Enum(_)).then(|| sym.path.clone())
+    }
+
+    // Casting an integer to an enum is checked, the value has to be one of
+    // its discriminants
+    pub(super) fn cast_to_enum(&mut self, expr: &Expr, v: Value, path: &str) -> Option<Value> {
+        let info = self.enum_info(path)?;
+        let n = match v {
+            Value::Int(n, _) | Value::Variant(_, _, n) => n,
+            _ => return None,
+        };
+        match info.variant_of(n) {
+            Some(name) => Some(Value::Variant(path.to_string(), name.to_string(), n)),
+            None => {
+                let values: Vec<String> = info
+                    .variants
+                    .iter()
+                    .map(|(name, n)| format!("{} = {}", name, n))
+                    .collect();
+                self.diags.push(
+                    Diagnostic::error(
+                        diagnostics::INVALID_DISCRIMINANT,
+                        expr.span,
+                        format!("no variant of `{}` has the value {}", path, n),
+                    )
+                    .with_label("evaluated at compile time")
+                    .with_note(format!("`{}` has {}", path, values.join(", "))),
+                );
+                None
+            }
+        }
+    }
+}
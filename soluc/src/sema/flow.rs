@@ -0,0 +1,68 @@
+use super::cfg::{Cfg, Item};
+use super::typeck::TypeInfo;
+use crate::diagnostics::{self, Diagnostic};
+use crate::parser::*;
+
+// Checks each function body on its control flow graph: functions with a
+// return type must return on every path, `break`/`continue` must be inside a
+// loop, and statements that can never run are warned about.
+pub fn check_flow(ast: &Namespace, info: &TypeInfo) -> Vec<Diagnostic> {
+    let mut diags = vec![];
+    check_decls(&ast.nodes, info, &mut diags);
+    diags
+}
+
+fn check_decls(nodes: &[Decl], info: &TypeInfo, diags: &mut Vec<Diagnostic>) {
+    for decl in nodes {
+        match decl {
+            Decl::Namespace(n) => check_decls(&n.nodes, info, diags),
+            Decl::Function(f) => check_function(f, info, diags),
+            _ => {}
+        }
+    }
+}
+
+fn check_function(f: &Function, info: &TypeInfo, diags: &mut Vec<Diagnostic>) {
+    let cfg = Cfg::build(&f.body, &info.exhaustive, diags);
+    let reachable = cfg.reachable();
+
+    if let Some(ret) = &f.ret
+        && reachable[cfg.fallthrough]
+    {
+        diags.push(
+            Diagnostic::error(
+                diagnostics::MISSING_RETURN,
+                ret.span,
+                format!("function `{}` may end without returning a value", f.name),
+            )
+            .with_label(format!("expected `{}` to be returned on every path", ret))
+            .with_note("add a `return` before the function's `end`"),
+        );
+    }
+
+    // Only the first statement of an unreachable region is reported, not the
+    // ones after a `return` that is already unreachable itself
+    for (id, block) in cfg.blocks.iter().enumerate() {
+        let Some((origin, after)) = block.after else {
+            continue;
+        };
+        if reachable[id] || !reachable[origin] {
+            continue;
+        }
+        let first = block.items.iter().find_map(|item| match item {
+            Item::Stmt(s) => Some(s),
+            _ => None,
+        });
+        if let Some(stmt) = first {
+            diags.push(
+                Diagnostic::warning(
+                    diagnostics::UNREACHABLE_CODE,
+                    stmt.span,
+                    "unreachable statement",
+                )
+                .with_label("this is never run")
+                .with_secondary(after, "any code following this is unreachable"),
+            );
+        }
+    }
+}
@@ -0,0 +1,344 @@
+use super::cfg::{Cfg, Item};
+use super::mutability::walk_expr;
+use super::ty::Ty;
+use super::typeck::TypeInfo;
+use crate::diagnostics::{self, Diagnostic};
+use crate::lexer::Span;
+use crate::parser::*;
+use std::collections::{HashMap, HashSet};
+
+// Locals declared without a value must be assigned on every path before
+// they're read. Struct locals can also be assigned field by field, the local
+// counts as assigned once all of its fields are.
+pub fn check_init(ast: &Namespace, info: &TypeInfo) -> Vec<Diagnostic> {
+    let mut c = Init {
+        info,
+        diags: vec![],
+        decls: HashMap::new(),
+        reported: HashSet::new(),
+    };
+    c.check_decls(&ast.nodes);
+    c.diags.sort_by_key(|d| d.span.start);
+    c.diags
+}
+
+// A local by the start of its declaration, and the field when only one of
+// its fields was assigned
+type Place = (usize, Option<String>);
+type State = HashSet<Place>;
+type Scopes = Vec<HashMap<String, Option<Span>>>;
+
+struct Init<'a> {
+    info: &'a TypeInfo,
+    diags: Vec<Diagnostic>,
+    // Each use of a local declared without a value -> its declaration
+    decls: HashMap<(usize, usize), Span>,
+    // Declarations already reported, one error per local is enough
+    reported: HashSet<usize>,
+}
+
+impl Init<'_> {
+    fn check_decls(&mut self, nodes: &[Decl]) {
+        for decl in nodes {
+            match decl {
+                Decl::Namespace(n) => self.check_decls(&n.nodes),
+                Decl::Function(f) => self.check_function(f),
+                _ => {}
+            }
+        }
+    }
+
+    fn check_function(&mut self, f: &Function) {
+        self.decls.clear();
+        let mut scopes: Scopes = vec![f.params.iter().map(|p| (p.name.clone(), None)).collect()];
+        self.bind_block(&f.body, &mut scopes);
+        if self.decls.is_empty() {
+            return;
+        }
+
+        // Break/continue errors were already reported by the flow check
+        let cfg = Cfg::build(&f.body, &self.info.exhaustive, &mut vec![]);
+
+        // What's assigned on every path into each block, None until the
+        // block is reached
+        let mut entry: Vec<Option<State>> = vec![None; cfg.blocks.len()];
+        entry[cfg.entry] = Some(State::new());
+        let mut work = vec![cfg.entry];
+        while let Some(id) = work.pop() {
+            let mut state = entry[id].clone().unwrap_or_default();
+            for item in cfg.blocks[id].items.iter() {
+                self.transfer(item, &mut state, false);
+            }
+            for &succ in cfg.blocks[id].succs.iter() {
+                let next = match &entry[succ] {
+                    Some(s) => s.intersection(&state).cloned().collect(),
+                    None => state.clone(),
+                };
+                if entry[succ].as_ref() != Some(&next) {
+                    entry[succ] = Some(next);
+                    work.push(succ);
+                }
+            }
+        }
+
+        for (id, block) in cfg.blocks.iter().enumerate() {
+            let Some(mut state) = entry[id].clone() else {
+                continue;
+            };
+            for item in block.items.iter() {
+                self.transfer(item, &mut state, true);
+            }
+        }
+    }
+
+    // Finds which declaration every use of a local refers to. Only locals
+    // declared without a value are tracked, a tracked name shadowed by an
+    // initialized one stops being tracked in that scope.
+    fn bind_block(&mut self, block: &Block, scopes: &mut Scopes) {
+        scopes.push(HashMap::new());
+        for stmt in block.stmts.iter() {
+            match &stmt.kind {
+                StmtKind::Var { name, value, .. } => {
+                    if let Some(v) = value {
+                        self.bind_expr(v, scopes);
+                    }
+                    let tracked = value.is_none().then_some(stmt.span);
+                    scopes.last_mut().unwrap().insert(name.clone(), tracked);
+                }
+                StmtKind::Assign { target, value, .. } => {
+                    self.bind_expr(target, scopes);
+                    self.bind_expr(value, scopes);
+                }
+                StmtKind::Expr(e) | StmtKind::Return(Some(e)) => self.bind_expr(e, scopes),
+                StmtKind::Return(None) | StmtKind::Break | StmtKind::Continue => {}
+                StmtKind::If {
+                    branches,
+                    else_body,
+                } => {
+                    for (cond, body) in branches.iter() {
+                        self.bind_expr(cond, scopes);
+                        self.bind_block(body, scopes);
+                    }
+                    if let Some(body) = else_body {
+                        self.bind_block(body, scopes);
+                    }
+                }
+                StmtKind::While { cond, body } => {
+                    self.bind_expr(cond, scopes);
+                    self.bind_block(body, scopes);
+                }
+                StmtKind::For {
+                    var,
+                    start,
+                    end,
+                    body,
+                } => {
+                    self.bind_expr(start, scopes);
+                    if let Some(end) = end {
+                        self.bind_expr(end, scopes);
+                    }
+                    scopes.push(HashMap::from([(var.clone(), None)]));
+                    self.bind_block(body, scopes);
+                    scopes.pop();
+                }
+                StmtKind::Switch {
+                    value,
+                    cases,
+                    default,
+                } => {
+                    self.bind_expr(value, scopes);
+                    for case in cases.iter() {
+                        self.bind_expr(&case.value, scopes);
+                        self.bind_block(&case.body, scopes);
+                    }
+                    if let Some(body) = default {
+                        self.bind_block(body, scopes);
+                    }
+                }
+            }
+        }
+        scopes.pop();
+    }
+
+    fn bind_expr(&mut self, expr: &Expr, scopes: &Scopes) {
+        let decls = &mut self.decls;
+        walk_expr(expr, &mut |e| {
+            if let ExprKind::Ident(name) = &e.kind
+                && let Some(Some(decl)) = scopes.iter().rev().find_map(|s| s.get(name))
+            {
+                decls.insert(key(e), *decl);
+            }
+        });
+    }
+
+    fn transfer(&mut self, item: &Item, state: &mut State, report: bool) {
+        match item {
+            Item::Stmt(stmt) => match &stmt.kind {
+                StmtKind::Var { value, .. } => {
+                    if let Some(v) = value {
+                        self.read(v, state, report);
+                    }
+                    // Declared again on every loop iteration
+                    state.retain(|(d, _)| *d != stmt.span.start);
+                }
+                StmtKind::Assign { target, op, value } => {
+                    self.read(value, state, report);
+                    if *op != AssignOp::Assign {
+                        self.read(target, state, report);
+                    }
+                    self.write(target, state, report);
+                }
+                StmtKind::Expr(e) | StmtKind::Return(Some(e)) => self.read(e, state, report),
+                _ => {}
+            },
+            Item::Cond(e) => self.read(e, state, report),
+            Item::Bind(..) => {}
+        }
+    }
+
+    fn read(&mut self, expr: &Expr, state: &State, report: bool) {
+        match &expr.kind {
+            ExprKind::Ident(name) => {
+                if report
+                    && let Some(&decl) = self.decls.get(&key(expr))
+                    && let Some(missing) = self.unassigned_fields(expr, decl, state)
+                {
+                    self.uninitialized(name.clone(), expr.span, decl, missing);
+                }
+            }
+            ExprKind::Member { base, field } => {
+                if let ExprKind::Ident(name) = &base.kind
+                    && let Some(&decl) = self.decls.get(&key(base))
+                    && !self.is_pointer(base)
+                {
+                    let whole = (decl.start, None);
+                    let part = (decl.start, Some(field.clone()));
+                    if report && !state.contains(&whole) && !state.contains(&part) {
+                        self.uninitialized(format!("{}.{}", name, field), expr.span, decl, vec![]);
+                    }
+                    return;
+                }
+                self.read(base, state, report);
+            }
+            ExprKind::Call { callee, args } => {
+                // A method call reads its whole receiver
+                match &callee.kind {
+                    ExprKind::Member { base, .. } => self.read(base, state, report),
+                    _ => self.read(callee, state, report),
+                }
+                for arg in args.iter() {
+                    self.read(arg, state, report);
+                }
+            }
+            ExprKind::Unary { expr, .. } | ExprKind::Cast { expr, .. } => {
+                self.read(expr, state, report)
+            }
+            ExprKind::Binary { lhs, rhs, .. } => {
+                self.read(lhs, state, report);
+                self.read(rhs, state, report);
+            }
+            ExprKind::Ternary {
+                cond,
+                then,
+                otherwise,
+            } => {
+                self.read(cond, state, report);
+                self.read(then, state, report);
+                self.read(otherwise, state, report);
+            }
+            ExprKind::Index { base, index } => {
+                self.read(base, state, report);
+                self.read(index, state, report);
+            }
+            ExprKind::StructLit { fields, .. } => {
+                for field in fields.iter() {
+                    self.read(&field.value, state, report);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn write(&mut self, target: &Expr, state: &mut State, report: bool) {
+        match &target.kind {
+            ExprKind::Ident(_) => {
+                if let Some(decl) = self.decls.get(&key(target)) {
+                    state.insert((decl.start, None));
+                }
+            }
+            // Writing through a pointer reads the pointer
+            ExprKind::Member { base, .. } | ExprKind::Index { base, .. }
+                if self.is_pointer(base) =>
+            {
+                self.read(target, state, report)
+            }
+            ExprKind::Member { base, field } => {
+                if matches!(base.kind, ExprKind::Ident(_))
+                    && let Some(decl) = self.decls.get(&key(base))
+                {
+                    state.insert((decl.start, Some(field.clone())));
+                } else {
+                    self.write(base, state, report);
+                }
+            }
+            // Arrays are filled element by element, writing any element
+            // counts as assigning the array
+            ExprKind::Index { base, index } => {
+                self.read(index, state, report);
+                self.write(base, state, report);
+            }
+            _ => self.read(target, state, report),
+        }
+    }
+
+    // None when the local is assigned. Otherwise the fields of a struct local
+    // that aren't, empty for other types.
+    fn unassigned_fields(&self, expr: &Expr, decl: Span, state: &State) -> Option<Vec<String>> {
+        if state.contains(&(decl.start, None)) {
+            return None;
+        }
+        let fields = match self.info.type_of(expr) {
+            Some(Ty::Struct(path)) => self.info.structs.get(path),
+            _ => None,
+        };
+        let Some(fields) = fields else {
+            return Some(vec![]);
+        };
+        let missing: Vec<String> = fields
+            .iter()
+            .filter(|(f, _)| !state.contains(&(decl.start, Some(f.clone()))))
+            .map(|(f, _)| f.clone())
+            .collect();
+        if missing.is_empty() {
+            None
+        } else {
+            Some(missing)
+        }
+    }
+
+    fn is_pointer(&self, expr: &Expr) -> bool {
+        self.info.type_of(expr).is_some_and(Ty::is_pointer_like)
+    }
+
+    fn uninitialized(&mut self, what: String, span: Span, decl: Span, missing: Vec<String>) {
+        if !self.reported.insert(decl.start) {
+            return;
+        }
+        let mut d = Diagnostic::error(
+            diagnostics::USE_OF_UNINITIALIZED,
+            span,
+            format!("`{}` is used before it is assigned", what),
+        )
+        .with_label("not assigned on every path to here")
+        .with_secondary(decl, "declared here without a value")
+        .with_help("assign it on every path before this, or give it an initial value");
+        if !missing.is_empty() {
+            d = d.with_note(format!("fields not assigned yet: {}", missing.join(", ")));
+        }
+        self.diags.push(d);
+    }
+}
+
+fn key(expr: &Expr) -> (usize, usize) {
+    (expr.span.start, expr.span.end)
+}
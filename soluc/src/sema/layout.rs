@@ -0,0 +1,212 @@
+use super::consteval::ConstEval;
+use super::enums::enum_repr;
+use super::symbols::*;
+use super::ty::Ty;
+use super::typeck::TypeInfo;
+use crate::diagnostics::{self, Diagnostic};
+use crate::parser::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub size: u64,
+    pub align: u64,
+}
+
+// Pointers, references and strings are one VM word. Strings are a handle to
+// the heap.
+pub const WORD: Layout = Layout { size: 8, align: 8 };
+
+#[derive(Debug, Clone)]
+pub struct FieldLayout {
+    pub name: String,
+    pub offset: u64,
+    pub layout: Layout,
+}
+
+// Fields are placed in declaration order, each at the next offset aligned
+// for it. The size is padded to a multiple of the alignment so elements of
+// an array stay aligned.
+#[derive(Debug, Clone)]
+pub struct StructLayout {
+    pub layout: Layout,
+    pub fields: Vec<FieldLayout>,
+}
+
+impl StructLayout {
+    pub fn field(&self, name: &str) -> Option<&FieldLayout> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+}
+
+impl Layout {
+    pub fn of_prim(p: Primitive) -> Self {
+        Layout {
+            size: p.size(),
+            align: p.size(),
+        }
+    }
+}
+
+impl TypeInfo {
+    // Layout of a checked type, None for unsized or erroneous types
+    pub fn layout(&self, ty: &Ty) -> Option<Layout> {
+        match ty {
+            Ty::Prim(p) => Some(Layout::of_prim(*p)),
+            Ty::Struct(path) => self.layouts.get(path).map(|l| l.layout),
+            // Enums are stored as their discriminant
+            Ty::Enum(path) => Some(Layout::of_prim(
+                self.enums.get(path).map_or(Primitive::I32, |e| e.repr),
+            )),
+            Ty::Pointer(_) | Ty::Ref(_) | Ty::Str | Ty::Nil => Some(WORD),
+            Ty::Array(t, Some(n)) => {
+                let elem = self.layout(t)?;
+                Some(Layout {
+                    size: elem.size.checked_mul(*n)?,
+                    align: elem.align,
+                })
+            }
+            Ty::Array(_, None) | Ty::Void | Ty::Error => None,
+        }
+    }
+}
+
+impl ConstEval<'_> {
+    // Lays out every struct, reporting the ones that contain themselves
+    pub fn layout_all(&mut self) {
+        for i in 0..self.structs.len() {
+            let path = self.structs[i].0.clone();
+            self.struct_layout(&path);
+        }
+    }
+
+    pub fn type_layout(&mut self, ty: &Type, ns: NsId) -> Option<Layout> {
+        match &ty.kind {
+            TypeKind::Primitive(p) => Some(Layout::of_prim(*p)),
+            TypeKind::Named(name) if BUILTIN_TYPES.contains(&name.as_str()) => Some(WORD),
+            TypeKind::Named(name) => {
+                let sym = self.table.lookup_type(ns, name)?;
+                match sym.kind {
+                    SymbolKind::Struct => {
+                        let path = sym.path.clone();
+                        self.struct_layout(&path).map(|l| l.layout)
+                    }
+                    _ => {
+                        let repr = self
+                            .enums
+                            .iter()
+                            .find(|(p, ..)| *p == sym.path)
+                            .map_or(Primitive::I32, |(_, e, _)| enum_repr(e));
+                        Some(Layout::of_prim(repr))
+                    }
+                }
+            }
+            TypeKind::Pointer(_) | TypeKind::Ref(_) => Some(WORD),
+            TypeKind::Array(elem, size) => {
+                let elem = self.type_layout(elem, ns)?;
+                let n = u64::try_from(self.array_len(size, ns)?).ok()?;
+                match elem.size.checked_mul(n) {
+                    Some(size) => Some(Layout {
+                        size,
+                        align: elem.align,
+                    }),
+                    None => {
+                        self.too_large(ty);
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn struct_layout(&mut self, path: &str) -> Option<StructLayout> {
+        if let Some(l) = self.layouts.get(path) {
+            return l.clone();
+        }
+        let &(_, s, ns) = self.structs.iter().find(|(p, ..)| p == path)?;
+        if let Some(pos) = self.laying_out.iter().position(|(p, _)| p == path) {
+            self.recursive(pos);
+            return None;
+        }
+
+        self.laying_out.push((path.to_string(), None));
+        let mut fields = vec![];
+        let mut offset = 0u64;
+        let mut align = 1u64;
+        let mut ok = true;
+        for m in s.members.iter() {
+            self.laying_out.last_mut().unwrap().1 = Some(m.span);
+            let Some(layout) = self.type_layout(&m.ty, ns) else {
+                ok = false;
+                continue;
+            };
+            let Some(start) = offset.checked_next_multiple_of(layout.align) else {
+                ok = false;
+                continue;
+            };
+            align = align.max(layout.align);
+            fields.push(FieldLayout {
+                name: m.name.clone(),
+                offset: start,
+                layout,
+            });
+            match start.checked_add(layout.size) {
+                Some(end) => offset = end,
+                None => {
+                    self.too_large(&m.ty);
+                    ok = false;
+                }
+            }
+        }
+        self.laying_out.pop();
+
+        let layout = match offset.checked_next_multiple_of(align) {
+            Some(size) if ok => Some(StructLayout {
+                layout: Layout { size, align },
+                fields,
+            }),
+            _ => None,
+        };
+        // A cycle through this struct already stored None
+        self.layouts
+            .entry(path.to_string())
+            .or_insert(layout)
+            .clone()
+    }
+
+    // The struct at `pos` in the stack contains itself through the ones
+    // after it
+    fn recursive(&mut self, pos: usize) {
+        let cycle: Vec<_> = self.laying_out[pos..].to_vec();
+        let (path, _) = &cycle[0];
+        let Some(&(_, s, _)) = self.structs.iter().find(|(p, ..)| p == path) else {
+            return;
+        };
+        let mut d = Diagnostic::error(
+            diagnostics::RECURSIVE_STRUCT,
+            s.span,
+            format!("recursive struct `{}` has infinite size", path),
+        )
+        .with_label("contains itself by value")
+        .with_help("store it behind a pointer instead, e.g. `*T`");
+        for (_, field) in cycle.iter() {
+            if let Some(span) = field {
+                d = d.with_secondary(*span, "recursive without a pointer");
+            }
+        }
+        self.diags.push(d);
+        for (p, _) in cycle {
+            self.layouts.insert(p, None);
+        }
+    }
+
+    fn too_large(&mut self, ty: &Type) {
+        self.diags.push(
+            Diagnostic::error(
+                diagnostics::TYPE_TOO_LARGE,
+                ty.span,
+                format!("`{}` is too large", ty),
+            )
+            .with_label("size doesn't fit in 64 bits"),
+        );
+    }
+}
@@ -0,0 +1,26 @@
+mod cfg;
+mod consteval;
+mod enums;
+mod flow;
+mod init;
+mod layout;
+mod mutability;
+mod resolve;
+mod symbols;
+mod ty;
+mod typeck;
+
+#[cfg(test)]
+mod tests;
+
+pub use cfg::*;
+pub use consteval::*;
+pub use enums::*;
+pub use flow::*;
+pub use init::*;
+pub use layout::*;
+pub use mutability::*;
+pub use resolve::*;
+pub use symbols::*;
+pub use ty::*;
+pub use typeck::*;
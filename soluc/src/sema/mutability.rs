@@ -0,0 +1,507 @@
+use super::symbols::*;
+use super::ty::Ty;
+use super::typeck::TypeInfo;
+use crate::diagnostics::{self, Diagnostic};
+use crate::lexer::Span;
+use crate::parser::*;
+use std::collections::HashMap;
+
+// Bindings are immutable unless declared `mut`. A write is allowed when the
+// place it goes through is rooted in a `mut` local, `this`, or a `*T`
+// pointer. `&T` references are read-only.
+//
+// Methods may write through `this`. Such a method is "mutating" and can only
+// be called on a receiver that could itself be assigned to.
+pub fn check_mutability(ast: &Namespace, table: &SymbolTable, info: &TypeInfo) -> Vec<Diagnostic> {
+    let mut m = Mutability {
+        table,
+        info,
+        diags: vec![],
+        locals: vec![],
+        mutating: HashMap::new(),
+    };
+    m.find_mutating(&ast.nodes, GLOBAL);
+    m.check_decls(&ast.nodes, GLOBAL);
+    m.diags
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Binding {
+    mutable: bool,
+    // Declared without an initializer, the first `=` initializes it
+    deferred: bool,
+    span: Span,
+}
+
+// Why a place can't be written to
+enum Denied {
+    Immutable(String, Span),
+    ThroughRef(Span),
+    NotAPlace(String),
+    This,
+}
+
+struct Mutability<'a> {
+    table: &'a SymbolTable,
+    info: &'a TypeInfo,
+    diags: Vec<Diagnostic>,
+    locals: Vec<HashMap<String, Binding>>,
+    // (struct path, method) -> span of the first write through `this`
+    mutating: HashMap<(String, String), Span>,
+}
+
+impl Mutability<'_> {
+    // Finds every method that writes through `this`, directly or by calling
+    // another mutating method on `this`
+    fn find_mutating(&mut self, nodes: &[Decl], ns: NsId) {
+        let mut calls: Vec<((String, String), String, Span)> = vec![];
+        self.collect_this_uses(nodes, ns, &mut calls);
+        loop {
+            let mut changed = false;
+            for (method, callee, span) in calls.iter() {
+                let target = (method.0.clone(), callee.clone());
+                if self.mutating.contains_key(&target) && !self.mutating.contains_key(method) {
+                    self.mutating.insert(method.clone(), *span);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    fn collect_this_uses(
+        &mut self,
+        nodes: &[Decl],
+        ns: NsId,
+        calls: &mut Vec<((String, String), String, Span)>,
+    ) {
+        for decl in nodes {
+            match decl {
+                Decl::Namespace(n) => {
+                    if let Some(SymbolKind::Namespace(id)) =
+                        self.table.scopes[ns].items.get(&n.name).map(|s| &s.kind)
+                    {
+                        self.collect_this_uses(&n.nodes, *id, calls);
+                    }
+                }
+                Decl::Function(f) if f.receiver.is_some() => {
+                    let Some(owner) = self.table.lookup(ns, f.receiver.as_ref().unwrap()) else {
+                        continue;
+                    };
+                    let key = (owner.path.clone(), f.name.clone());
+                    let mut writes = vec![];
+                    let mut this_calls = vec![];
+                    walk_block(
+                        &f.body,
+                        &mut |stmt| match &stmt.kind {
+                            StmtKind::Assign { target, .. } if rooted_in_this(target) => {
+                                writes.push(stmt.span)
+                            }
+                            _ => {}
+                        },
+                        &mut |expr| {
+                            if let ExprKind::Call { callee, .. } = &expr.kind
+                                && let ExprKind::Member { base, field } = &callee.kind
+                                && matches!(&base.kind, ExprKind::Ident(n) if n == "this")
+                            {
+                                this_calls.push((field.clone(), expr.span));
+                            }
+                        },
+                    );
+                    if let Some(span) = writes.first() {
+                        self.mutating.insert(key.clone(), *span);
+                    }
+                    for (callee, span) in this_calls {
+                        calls.push((key.clone(), callee, span));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_decls(&mut self, nodes: &[Decl], ns: NsId) {
+        for decl in nodes {
+            match decl {
+                Decl::Namespace(n) => {
+                    if let Some(SymbolKind::Namespace(id)) =
+                        self.table.scopes[ns].items.get(&n.name).map(|s| &s.kind)
+                    {
+                        self.check_decls(&n.nodes, *id);
+                    }
+                }
+                Decl::Function(f) => {
+                    let mut scope = HashMap::new();
+                    for p in f.params.iter() {
+                        scope.insert(
+                            p.name.clone(),
+                            Binding {
+                                mutable: false,
+                                deferred: false,
+                                span: p.span,
+                            },
+                        );
+                    }
+                    self.locals = vec![scope];
+                    self.check_block(&f.body);
+                    self.locals.clear();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_block(&mut self, block: &Block) {
+        self.locals.push(HashMap::new());
+        for stmt in block.stmts.iter() {
+            self.check_stmt(stmt);
+        }
+        self.locals.pop();
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Var {
+                mutable,
+                name,
+                value,
+                ..
+            } => {
+                if let Some(v) = value {
+                    self.check_expr(v);
+                }
+                self.declare(
+                    name,
+                    Binding {
+                        mutable: *mutable,
+                        deferred: value.is_none(),
+                        span: stmt.span,
+                    },
+                );
+            }
+            StmtKind::Assign { target, op, value } => {
+                self.check_expr(value);
+                self.check_calls_in_place(target);
+                let init = *op == AssignOp::Assign && self.is_deferred(target);
+                if !init && let Err(denied) = self.place(target) {
+                    let diag = denial(denied, target.span, "assign to");
+                    self.diags.push(diag);
+                }
+            }
+            StmtKind::Expr(e) | StmtKind::Return(Some(e)) => self.check_expr(e),
+            StmtKind::Return(None) | StmtKind::Break | StmtKind::Continue => {}
+            StmtKind::If {
+                branches,
+                else_body,
+            } => {
+                for (cond, body) in branches.iter() {
+                    self.check_expr(cond);
+                    self.check_block(body);
+                }
+                if let Some(body) = else_body {
+                    self.check_block(body);
+                }
+            }
+            StmtKind::While { cond, body } => {
+                self.check_expr(cond);
+                self.check_block(body);
+            }
+            StmtKind::For {
+                var,
+                start,
+                end,
+                body,
+            } => {
+                self.check_expr(start);
+                if let Some(end) = end {
+                    self.check_expr(end);
+                }
+                self.locals.push(HashMap::new());
+                self.declare(
+                    var,
+                    Binding {
+                        mutable: false,
+                        deferred: false,
+                        span: stmt.span,
+                    },
+                );
+                self.check_block(body);
+                self.locals.pop();
+            }
+            StmtKind::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                self.check_expr(value);
+                for case in cases.iter() {
+                    self.check_expr(&case.value);
+                    self.check_block(&case.body);
+                }
+                if let Some(body) = default {
+                    self.check_block(body);
+                }
+            }
+        }
+    }
+
+    // Method calls can hide inside an assignment target, `a.get()[0] = 1`
+    fn check_calls_in_place(&mut self, target: &Expr) {
+        match &target.kind {
+            ExprKind::Member { base, .. } => self.check_calls_in_place(base),
+            ExprKind::Index { base, index } => {
+                self.check_calls_in_place(base);
+                self.check_expr(index);
+            }
+            ExprKind::Unary { expr, .. } => self.check_calls_in_place(expr),
+            _ => self.check_expr(target),
+        }
+    }
+
+    fn check_expr(&mut self, expr: &Expr) {
+        let mut calls = vec![];
+        walk_expr(expr, &mut |e| {
+            if let ExprKind::Call { callee, .. } = &e.kind
+                && let ExprKind::Member { base, field } = &callee.kind
+            {
+                calls.push(((**base).clone(), field.clone()));
+            }
+        });
+        for (base, method) in calls {
+            self.check_method_call(&base, &method);
+        }
+    }
+
+    fn check_method_call(&mut self, recv: &Expr, method: &str) {
+        let Some(Ty::Struct(path)) = self.info.type_of(recv) else {
+            // Calls through a pointer may always mutate, calls through a
+            // reference are covered by `place`
+            if let Some(Ty::Ref(t)) = self.info.type_of(recv)
+                && let Ty::Struct(path) = &**t
+                && let Some(write) = self.mutating.get(&(path.clone(), method.to_string()))
+            {
+                let write = *write;
+                self.mutating_call(recv.span, method, Denied::ThroughRef(recv.span), write);
+            }
+            return;
+        };
+        let Some(write) = self.mutating.get(&(path.clone(), method.to_string())) else {
+            return;
+        };
+        let write = *write;
+        if let Err(denied) = self.place(recv) {
+            self.mutating_call(recv.span, method, denied, write);
+        }
+    }
+
+    // Checks that `expr` names something that can be written to
+    fn place(&self, expr: &Expr) -> Result<(), Denied> {
+        match &expr.kind {
+            ExprKind::Ident(name) if name == "this" && self.lookup(name).is_none() => {
+                Err(Denied::This)
+            }
+            ExprKind::Ident(name) => match self.lookup(name) {
+                Some(b) if b.mutable => Ok(()),
+                Some(b) => Err(Denied::Immutable(name.clone(), b.span)),
+                None => Err(Denied::NotAPlace(name.clone())),
+            },
+            ExprKind::Member { base, .. } | ExprKind::Index { base, .. } => {
+                match self.info.type_of(base) {
+                    Some(Ty::Pointer(_)) => Ok(()),
+                    Some(Ty::Ref(_)) => Err(Denied::ThroughRef(base.span)),
+                    _ if matches!(&base.kind, ExprKind::Ident(n) if n == "this") => Ok(()),
+                    _ => self.place(base),
+                }
+            }
+            ExprKind::Unary {
+                op: UnaryOp::Deref,
+                expr: inner,
+            } => match self.info.type_of(inner) {
+                Some(Ty::Ref(_)) => Err(Denied::ThroughRef(inner.span)),
+                _ => Ok(()),
+            },
+            _ => Err(Denied::NotAPlace(expr.to_string())),
+        }
+    }
+
+    // Plain `x = ...` to a binding declared without an initializer
+    fn is_deferred(&self, target: &Expr) -> bool {
+        match &target.kind {
+            ExprKind::Ident(name) => self.lookup(name).is_some_and(|b| b.deferred),
+            _ => false,
+        }
+    }
+
+    fn mutating_call(&mut self, span: Span, method: &str, denied: Denied, write: Span) {
+        let action = format!("call mutating method `{}` on", method);
+        let diag = Diagnostic {
+            code: diagnostics::MUTATING_CALL_ON_IMMUTABLE,
+            ..denial(denied, span, &action)
+        }
+        .with_secondary(write, format!("`{}` writes through `this` here", method));
+        self.diags.push(diag);
+    }
+
+    fn declare(&mut self, name: &str, binding: Binding) {
+        self.locals
+            .last_mut()
+            .unwrap()
+            .insert(name.to_string(), binding);
+    }
+
+    fn lookup(&self, name: &str) -> Option<Binding> {
+        self.locals
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+    }
+}
+
+fn denial(denied: Denied, span: Span, action: &str) -> Diagnostic {
+    match denied {
+        Denied::Immutable(name, decl) => Diagnostic::error(
+            diagnostics::ASSIGN_TO_IMMUTABLE,
+            span,
+            format!("cannot {} immutable binding `{}`", action, name),
+        )
+        .with_label("cannot be written to")
+        .with_secondary(decl, "declared immutable here")
+        .with_help(format!("declare it as `mut {}`", name)),
+        Denied::ThroughRef(r) => Diagnostic::error(
+            diagnostics::ASSIGN_THROUGH_REF,
+            span,
+            format!("cannot {} a place behind a `&` reference", action),
+        )
+        .with_label("write through a reference")
+        .with_secondary(r, "this is a read-only reference")
+        .with_help("use a `*` pointer for writable access"),
+        Denied::NotAPlace(name) => Diagnostic::error(
+            diagnostics::NOT_ASSIGNABLE,
+            span,
+            format!("cannot {} `{}`", action, name),
+        )
+        .with_label("not a variable"),
+        Denied::This => Diagnostic::error(
+            diagnostics::NOT_ASSIGNABLE,
+            span,
+            format!("cannot {} `this`", action),
+        )
+        .with_help("write to the fields of `this` instead"),
+    }
+}
+
+// `this` itself can't be replaced, only written through
+fn rooted_in_this(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Member { base, .. } | ExprKind::Index { base, .. } => {
+            matches!(&base.kind, ExprKind::Ident(n) if n == "this") || rooted_in_this(base)
+        }
+        ExprKind::Unary {
+            op: UnaryOp::Deref,
+            expr,
+        } => rooted_in_this(expr),
+        _ => false,
+    }
+}
+
+// Visits every statement and expression in a block, nested blocks included
+pub(super) fn walk_block(
+    block: &Block,
+    on_stmt: &mut impl FnMut(&Stmt),
+    on_expr: &mut impl FnMut(&Expr),
+) {
+    for stmt in block.stmts.iter() {
+        on_stmt(stmt);
+        match &stmt.kind {
+            StmtKind::Var { value, .. } => {
+                if let Some(v) = value {
+                    walk_expr(v, on_expr);
+                }
+            }
+            StmtKind::Assign { target, value, .. } => {
+                walk_expr(target, on_expr);
+                walk_expr(value, on_expr);
+            }
+            StmtKind::Expr(e) | StmtKind::Return(Some(e)) => walk_expr(e, on_expr),
+            StmtKind::Return(None) | StmtKind::Break | StmtKind::Continue => {}
+            StmtKind::If {
+                branches,
+                else_body,
+            } => {
+                for (cond, body) in branches.iter() {
+                    walk_expr(cond, on_expr);
+                    walk_block(body, on_stmt, on_expr);
+                }
+                if let Some(body) = else_body {
+                    walk_block(body, on_stmt, on_expr);
+                }
+            }
+            StmtKind::While { cond, body } => {
+                walk_expr(cond, on_expr);
+                walk_block(body, on_stmt, on_expr);
+            }
+            StmtKind::For {
+                start, end, body, ..
+            } => {
+                walk_expr(start, on_expr);
+                if let Some(end) = end {
+                    walk_expr(end, on_expr);
+                }
+                walk_block(body, on_stmt, on_expr);
+            }
+            StmtKind::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                walk_expr(value, on_expr);
+                for case in cases.iter() {
+                    walk_expr(&case.value, on_expr);
+                    walk_block(&case.body, on_stmt, on_expr);
+                }
+                if let Some(body) = default {
+                    walk_block(body, on_stmt, on_expr);
+                }
+            }
+        }
+    }
+}
+
+pub(super) fn walk_expr(expr: &Expr, f: &mut impl FnMut(&Expr)) {
+    f(expr);
+    match &expr.kind {
+        ExprKind::Unary { expr, .. } | ExprKind::Cast { expr, .. } => walk_expr(expr, f),
+        ExprKind::Binary { lhs, rhs, .. } => {
+            walk_expr(lhs, f);
+            walk_expr(rhs, f);
+        }
+        ExprKind::Ternary {
+            cond,
+            then,
+            otherwise,
+        } => {
+            walk_expr(cond, f);
+            walk_expr(then, f);
+            walk_expr(otherwise, f);
+        }
+        ExprKind::Call { callee, args } => {
+            walk_expr(callee, f);
+            for arg in args.iter() {
+                walk_expr(arg, f);
+            }
+        }
+        ExprKind::Member { base, .. } => walk_expr(base, f),
+        ExprKind::Index { base, index } => {
+            walk_expr(base, f);
+            walk_expr(index, f);
+        }
+        ExprKind::StructLit { fields, .. } => {
+            for field in fields.iter() {
+                walk_expr(&field.value, f);
+            }
+        }
+        _ => {}
+    }
+}
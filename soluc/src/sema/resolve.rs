@@ -0,0 +1,523 @@
+use super::symbols::*;
+use crate::diagnostics::{self, Diagnostic};
+use crate::lexer::Span;
+use crate::parser::*;
+use std::collections::HashMap;
+
+// Builds the symbol tables for a program and checks that every name it uses
+// refers to something. Resolution keeps going after an error so all of them
+// get reported.
+pub fn resolve(ast: &Namespace) -> (SymbolTable, Vec<Diagnostic>) {
+    let mut r = Resolver {
+        table: SymbolTable::new(),
+        diags: vec![],
+        ns: GLOBAL,
+        locals: vec![],
+        declared: vec![],
+    };
+    r.collect(&ast.nodes, GLOBAL);
+    r.collect_methods(&ast.nodes, GLOBAL);
+    r.resolve_decls(&ast.nodes, GLOBAL);
+    (r.table, r.diags)
+}
+
+struct Resolver {
+    table: SymbolTable,
+    diags: Vec<Diagnostic>,
+    ns: NsId,
+    // Innermost block last
+    locals: Vec<HashMap<String, Span>>,
+    // Every local the current function declares, to tell "used before its
+    // declaration" apart from "doesn't exist"
+    declared: Vec<(String, Span)>,
+}
+
+// ---------------------------------------
+// Declarations
+// ---------------------------------------
+impl Resolver {
+    // Enters every item of a namespace into its scope. Functions go last so
+    // one sharing a struct's name is seen as that struct's constructor.
+    fn collect(&mut self, nodes: &[Decl], ns: NsId) {
+        for decl in nodes {
+            let kind = match decl {
+                Decl::Function(_) => continue,
+                Decl::Struct(_) => SymbolKind::Struct,
+                Decl::Const(_) => SymbolKind::Const,
+                Decl::Enum(e) => {
+                    let mut variants: Vec<String> = vec![];
+                    for v in e.variants.iter() {
+                        if variants.contains(&v.name) {
+                            self.diags.push(
+                                Diagnostic::error(
+                                    diagnostics::DUPLICATE_DEFINITION,
+                                    v.span,
+                                    format!("variant `{}` is defined more than once", v.name),
+                                )
+                                .with_label("redefined here"),
+                            );
+                        } else {
+                            variants.push(v.name.clone());
+                        }
+                    }
+                    SymbolKind::Enum(variants)
+                }
+                Decl::Namespace(n) => {
+                    let id = self.table.add_scope(&n.name, ns);
+                    self.collect(&n.nodes, id);
+                    SymbolKind::Namespace(id)
+                }
+            };
+            self.define(ns, decl.name(), decl.span(), kind);
+        }
+        for decl in nodes {
+            let Decl::Function(f) = decl else { continue };
+            if f.receiver.is_some() {
+                continue;
+            }
+            match self.table.scopes[ns].items.get(&f.name) {
+                Some(sym) if matches!(sym.kind, SymbolKind::Struct) => {
+                    let path = sym.path.clone();
+                    if let Some(prev) = self.table.ctors.get(&path) {
+                        let prev = *prev;
+                        self.duplicate(&f.name, f.span, prev);
+                    } else {
+                        self.table.ctors.insert(path, f.span);
+                    }
+                }
+                _ => self.define(ns, &f.name, f.span, SymbolKind::Function),
+            }
+        }
+    }
+
+    // Attaches `Type::name` methods to their struct. Runs once every
+    // namespace is collected since the receiver may be declared anywhere.
+    fn collect_methods(&mut self, nodes: &[Decl], ns: NsId) {
+        for decl in nodes {
+            match decl {
+                Decl::Namespace(n) => {
+                    let id = self.scope_of(ns, &n.name);
+                    self.collect_methods(&n.nodes, id);
+                }
+                Decl::Function(f) if f.receiver.is_some() => {
+                    let receiver = f.receiver.as_ref().unwrap();
+                    let path = match self.table.lookup(ns, receiver) {
+                        Some(sym) if matches!(sym.kind, SymbolKind::Struct) => sym.path.clone(),
+                        Some(sym) => {
+                            let what = sym.describe();
+                            self.diags.push(
+                                Diagnostic::error(
+                                    diagnostics::UNKNOWN_RECEIVER,
+                                    f.span,
+                                    format!("method receiver `{}` is not a struct", receiver),
+                                )
+                                .with_label(format!("found {} `{}`", what, receiver))
+                                .with_help("methods are declared as `Struct::name(...)`"),
+                            );
+                            continue;
+                        }
+                        None => {
+                            self.diags.push(
+                                Diagnostic::error(
+                                    diagnostics::UNKNOWN_RECEIVER,
+                                    f.span,
+                                    format!(
+                                        "cannot find type `{}` for method `{}::{}`",
+                                        receiver, receiver, f.name
+                                    ),
+                                )
+                                .with_label("unknown receiver type")
+                                .with_help("methods are declared as `Struct::name(...)`"),
+                            );
+                            continue;
+                        }
+                    };
+                    let methods = self.table.methods.entry(path).or_default();
+                    if let Some(prev) = methods.get(&f.name) {
+                        let prev = *prev;
+                        self.duplicate(&f.name, f.span, prev);
+                    } else {
+                        methods.insert(f.name.clone(), f.span);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn resolve_decls(&mut self, nodes: &[Decl], ns: NsId) {
+        for decl in nodes {
+            self.ns = ns;
+            match decl {
+                Decl::Namespace(n) => {
+                    let id = self.scope_of(ns, &n.name);
+                    self.resolve_decls(&n.nodes, id);
+                }
+                Decl::Struct(s) => {
+                    let mut seen: HashMap<&str, Span> = HashMap::new();
+                    for m in s.members.iter() {
+                        if let Some(prev) = seen.get(m.name.as_str()) {
+                            self.duplicate(&m.name, m.span, *prev);
+                        } else {
+                            seen.insert(&m.name, m.span);
+                        }
+                        self.resolve_type(&m.ty);
+                    }
+                }
+                Decl::Const(c) => {
+                    self.resolve_type(&c.ty);
+                    self.resolve_expr(&c.value);
+                }
+                Decl::Function(f) => self.resolve_function(f),
+                Decl::Enum(e) => {
+                    if let Some(ty) = &e.ty {
+                        self.resolve_type(ty);
+                    }
+                    for v in e.variants.iter() {
+                        if let Some(value) = &v.value {
+                            self.resolve_expr(value);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn resolve_function(&mut self, f: &Function) {
+        self.declared.clear();
+        collect_locals(&f.body, &mut self.declared);
+        self.locals.push(HashMap::new());
+        if f.receiver.is_some() {
+            self.locals[0].insert("this".to_string(), f.span);
+        }
+        for p in f.params.iter() {
+            self.resolve_type(&p.ty);
+            self.declare(&p.name, p.span);
+        }
+        if let Some(ret) = &f.ret {
+            self.resolve_type(ret);
+        }
+        self.resolve_block(&f.body);
+        self.locals.pop();
+    }
+
+    fn define(&mut self, ns: NsId, name: &str, span: Span, kind: SymbolKind) {
+        if let Some(prev) = self.table.scopes[ns].items.get(name) {
+            let prev = prev.span;
+            self.duplicate(name, span, prev);
+            return;
+        }
+        let sym = Symbol {
+            kind,
+            name: name.to_string(),
+            path: self.table.qualify(ns, name),
+            ns,
+            span,
+        };
+        self.table.scopes[ns].items.insert(name.to_string(), sym);
+    }
+
+    // Scope created for a nested namespace while collecting
+    fn scope_of(&self, ns: NsId, name: &str) -> NsId {
+        match self.table.scopes[ns].items.get(name).map(|s| &s.kind) {
+            Some(SymbolKind::Namespace(id)) => *id,
+            // A duplicate namespace that never got its own scope
+            _ => ns,
+        }
+    }
+
+    fn duplicate(&mut self, name: &str, span: Span, prev: Span) {
+        self.diags.push(
+            Diagnostic::error(
+                diagnostics::DUPLICATE_DEFINITION,
+                span,
+                format!("`{}` is defined more than once", name),
+            )
+            .with_label("redefined here")
+            .with_secondary(prev, "first defined here"),
+        );
+    }
+}
+
+// ---------------------------------------
+// Statements
+// ---------------------------------------
+impl Resolver {
+    fn resolve_block(&mut self, block: &Block) {
+        self.locals.push(HashMap::new());
+        for stmt in block.stmts.iter() {
+            self.resolve_stmt(stmt);
+        }
+        self.locals.pop();
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Var {
+                name, ty, value, ..
+            } => {
+                // The initializer can't see the variable it initializes
+                if let Some(v) = value {
+                    self.resolve_expr(v);
+                }
+                self.resolve_type(ty);
+                self.declare(name, stmt.span);
+            }
+            StmtKind::Assign { target, value, .. } => {
+                self.resolve_expr(target);
+                self.resolve_expr(value);
+            }
+            StmtKind::Expr(e) | StmtKind::Return(Some(e)) => self.resolve_expr(e),
+            StmtKind::Return(None) | StmtKind::Break | StmtKind::Continue => {}
+            StmtKind::If {
+                branches,
+                else_body,
+            } => {
+                for (cond, body) in branches.iter() {
+                    self.resolve_expr(cond);
+                    self.resolve_block(body);
+                }
+                if let Some(body) = else_body {
+                    self.resolve_block(body);
+                }
+            }
+            StmtKind::While { cond, body } => {
+                self.resolve_expr(cond);
+                self.resolve_block(body);
+            }
+            StmtKind::For {
+                var,
+                start,
+                end,
+                body,
+            } => {
+                self.resolve_expr(start);
+                if let Some(end) = end {
+                    self.resolve_expr(end);
+                }
+                self.locals.push(HashMap::new());
+                self.declare(var, stmt.span);
+                self.resolve_block(body);
+                self.locals.pop();
+            }
+            StmtKind::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                self.resolve_expr(value);
+                for case in cases.iter() {
+                    self.resolve_expr(&case.value);
+                    self.resolve_block(&case.body);
+                }
+                if let Some(body) = default {
+                    self.resolve_block(body);
+                }
+            }
+        }
+    }
+
+    // Locals may shadow ones from enclosing blocks but not their own block
+    fn declare(&mut self, name: &str, span: Span) {
+        let scope = self.locals.last_mut().unwrap();
+        if let Some(prev) = scope.get(name) {
+            let prev = *prev;
+            self.duplicate(name, span, prev);
+        } else {
+            scope.insert(name.to_string(), span);
+        }
+    }
+
+    fn lookup_local(&self, name: &str) -> Option<Span> {
+        self.locals
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+    }
+}
+
+// Every variable declared anywhere in a block, nested blocks included
+fn collect_locals(block: &Block, out: &mut Vec<(String, Span)>) {
+    for stmt in block.stmts.iter() {
+        match &stmt.kind {
+            StmtKind::Var { name, .. } => out.push((name.clone(), stmt.span)),
+            StmtKind::If {
+                branches,
+                else_body,
+            } => {
+                for (_, body) in branches.iter() {
+                    collect_locals(body, out);
+                }
+                if let Some(body) = else_body {
+                    collect_locals(body, out);
+                }
+            }
+            StmtKind::While { body, .. } => collect_locals(body, out),
+            StmtKind::For { var, body, .. } => {
+                out.push((var.clone(), stmt.span));
+                collect_locals(body, out);
+            }
+            StmtKind::Switch { cases, default, .. } => {
+                for case in cases.iter() {
+                    collect_locals(&case.body, out);
+                }
+                if let Some(body) = default {
+                    collect_locals(body, out);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// ---------------------------------------
+// Expressions
+// ---------------------------------------
+impl Resolver {
+    fn resolve_expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Int(_)
+            | ExprKind::Float(_)
+            | ExprKind::Bool(_)
+            | ExprKind::Char(_)
+            | ExprKind::Str(_)
+            | ExprKind::Nil => {}
+            ExprKind::Ident(name) => self.resolve_ident(name, expr.span),
+            ExprKind::Path(path) => self.resolve_path(path, expr.span),
+            ExprKind::Unary { expr, .. } => self.resolve_expr(expr),
+            ExprKind::Binary { lhs, rhs, .. } => {
+                self.resolve_expr(lhs);
+                self.resolve_expr(rhs);
+            }
+            ExprKind::Ternary {
+                cond,
+                then,
+                otherwise,
+            } => {
+                self.resolve_expr(cond);
+                self.resolve_expr(then);
+                self.resolve_expr(otherwise);
+            }
+            ExprKind::Cast { ty, expr } => {
+                self.resolve_type(ty);
+                self.resolve_expr(expr);
+            }
+            ExprKind::SizeOf(ty) | ExprKind::AlignOf(ty) => self.resolve_type(ty),
+            ExprKind::Call { callee, args } => {
+                self.resolve_expr(callee);
+                for arg in args.iter() {
+                    self.resolve_expr(arg);
+                }
+            }
+            // Fields are checked once types are known
+            ExprKind::Member { base, .. } => self.resolve_expr(base),
+            ExprKind::Index { base, index } => {
+                self.resolve_expr(base);
+                self.resolve_expr(index);
+            }
+            ExprKind::StructLit { name, fields } => {
+                match self.table.lookup_type(self.ns, name) {
+                    Some(sym) if matches!(sym.kind, SymbolKind::Struct) => {}
+                    _ => self.undefined_type(name, expr.span),
+                }
+                for f in fields.iter() {
+                    self.resolve_expr(&f.value);
+                }
+            }
+        }
+    }
+
+    fn resolve_ident(&mut self, name: &str, span: Span) {
+        if self.lookup_local(name).is_some() {
+            return;
+        }
+        match self.table.lookup(self.ns, name) {
+            Some(sym) => self.expect_value(sym.clone(), name, span),
+            None => {
+                let later = self
+                    .declared
+                    .iter()
+                    .find(|(n, decl)| n == name && decl.start > span.start)
+                    .map(|(_, decl)| *decl);
+                let diag = match later {
+                    Some(decl) => Diagnostic::error(
+                        diagnostics::USE_BEFORE_DECLARATION,
+                        span,
+                        format!("`{}` is used before its declaration", name),
+                    )
+                    .with_label("used here")
+                    .with_secondary(decl, "declared here"),
+                    None => Diagnostic::error(
+                        diagnostics::UNDEFINED_NAME,
+                        span,
+                        format!("cannot find `{}` in this scope", name),
+                    )
+                    .with_label("not found"),
+                };
+                self.diags.push(diag);
+            }
+        }
+    }
+
+    fn resolve_path(&mut self, path: &[String], span: Span) {
+        match self.table.lookup_path(self.ns, path) {
+            Ok(Res::Symbol(sym)) => self.expect_value(sym.clone(), &path.join("::"), span),
+            Ok(_) => {}
+            Err(i) => {
+                let msg = if i == 0 {
+                    format!("cannot find `{}` in this scope", path[0])
+                } else {
+                    format!("cannot find `{}` in `{}`", path[i], path[..i].join("::"))
+                };
+                self.diags.push(
+                    Diagnostic::error(diagnostics::UNDEFINED_NAME, span, msg)
+                        .with_label("not found"),
+                );
+            }
+        }
+    }
+
+    // Namespaces and enums can only be used as the prefix of a path. Structs
+    // are values when called as constructors.
+    fn expect_value(&mut self, sym: Symbol, name: &str, span: Span) {
+        if matches!(sym.kind, SymbolKind::Namespace(_) | SymbolKind::Enum(_)) {
+            self.diags.push(
+                Diagnostic::error(
+                    diagnostics::NOT_A_VALUE,
+                    span,
+                    format!("expected a value, found {} `{}`", sym.describe(), name),
+                )
+                .with_label("not a value"),
+            );
+        }
+    }
+
+    fn resolve_type(&mut self, ty: &Type) {
+        match &ty.kind {
+            TypeKind::Primitive(_) => {}
+            TypeKind::Named(name) => {
+                if !BUILTIN_TYPES.contains(&name.as_str())
+                    && self.table.lookup_type(self.ns, name).is_none()
+                {
+                    self.undefined_type(name, ty.span);
+                }
+            }
+            TypeKind::Pointer(t) | TypeKind::Ref(t) => self.resolve_type(t),
+            TypeKind::Array(t, size) => {
+                self.resolve_type(t);
+                self.resolve_expr(size);
+            }
+        }
+    }
+
+    fn undefined_type(&mut self, name: &str, span: Span) {
+        self.diags.push(
+            Diagnostic::error(
+                diagnostics::UNDEFINED_TYPE,
+                span,
+                format!("cannot find type `{}` in this scope", name),
+            )
+            .with_label("not a struct or enum"),
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use crate::lexer::Span;
+use std::collections::HashMap;
+
+pub type NsId = usize;
+
+pub const GLOBAL: NsId = 0;
+
+// Named types that exist without a declaration
+pub const BUILTIN_TYPES: &[&str] = &["String"];
+
+#[derive(Debug, Clone)]
+pub enum SymbolKind {
+    Namespace(NsId),
+    Struct,
+    Enum(Vec<String>),
+    Const,
+    Function,
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub kind: SymbolKind,
+    pub name: String,
+    pub path: String, // Fully qualified, `X::Y::f`. Global items are just `f`
+    pub ns: NsId,     // Namespace the symbol is declared in
+    pub span: Span,
+}
+
+impl Symbol {
+    pub fn describe(&self) -> &'static str {
+        match self.kind {
+            SymbolKind::Namespace(_) => "namespace",
+            SymbolKind::Struct => "struct",
+            SymbolKind::Enum(_) => "enum",
+            SymbolKind::Const => "const",
+            SymbolKind::Function => "function",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Scope {
+    pub name: String,
+    pub parent: Option<NsId>,
+    pub items: HashMap<String, Symbol>,
+}
+
+// What a (possibly qualified) path refers to
+#[derive(Debug, Clone, Copy)]
+pub enum Res<'a> {
+    Symbol(&'a Symbol),
+    Variant(&'a Symbol, usize), // Enum and variant index
+    Method(&'a Symbol, Span),   // Struct and method declaration
+}
+
+#[derive(Debug, Clone)]
+pub struct SymbolTable {
+    pub scopes: Vec<Scope>,
+    // Keyed by the struct's qualified path, then method name
+    pub methods: HashMap<String, HashMap<String, Span>>,
+    // Functions sharing a struct's name, `Entity(...)`
+    pub ctors: HashMap<String, Span>,
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![Scope {
+                name: "Global".to_string(),
+                parent: None,
+                items: HashMap::new(),
+            }],
+            methods: HashMap::new(),
+            ctors: HashMap::new(),
+        }
+    }
+
+    pub fn add_scope(&mut self, name: &str, parent: NsId) -> NsId {
+        self.scopes.push(Scope {
+            name: name.to_string(),
+            parent: Some(parent),
+            items: HashMap::new(),
+        });
+        self.scopes.len() - 1
+    }
+
+    // Qualified path of a new item named `name` inside `ns`
+    pub fn qualify(&self, ns: NsId, name: &str) -> String {
+        let mut parts = vec![name.to_string()];
+        let mut cur = ns;
+        while let Some(parent) = self.scopes[cur].parent {
+            parts.push(self.scopes[cur].name.clone());
+            cur = parent;
+        }
+        parts.reverse();
+        parts.join("::")
+    }
+
+    // Looks `name` up in `ns` and then each enclosing namespace
+    pub fn lookup(&self, ns: NsId, name: &str) -> Option<&Symbol> {
+        let mut cur = Some(ns);
+        while let Some(id) = cur {
+            if let Some(sym) = self.scopes[id].items.get(name) {
+                return Some(sym);
+            }
+            cur = self.scopes[id].parent;
+        }
+        None
+    }
+
+    // Resolves `X::Y::f` starting from `ns`. Only the first segment is
+    // looked up through enclosing namespaces, the rest must be members of
+    // what came before. On failure returns the index of the bad segment.
+    pub fn lookup_path(&self, ns: NsId, path: &[String]) -> Result<Res<'_>, usize> {
+        let mut sym = self.lookup(ns, &path[0]).ok_or(0usize)?;
+        for (i, seg) in path.iter().enumerate().skip(1) {
+            let last = i == path.len() - 1;
+            match &sym.kind {
+                SymbolKind::Namespace(id) => {
+                    sym = self.scopes[*id].items.get(seg).ok_or(i)?;
+                }
+                SymbolKind::Enum(variants) if last => {
+                    let idx = variants.iter().position(|v| v == seg).ok_or(i)?;
+                    return Ok(Res::Variant(sym, idx));
+                }
+                SymbolKind::Struct if last => {
+                    let span = self
+                        .methods
+                        .get(&sym.path)
+                        .and_then(|m| m.get(seg))
+                        .ok_or(i)?;
+                    return Ok(Res::Method(sym, *span));
+                }
+                _ => return Err(i),
+            }
+        }
+        Ok(Res::Symbol(sym))
+    }
+
+    // Resolves a type name as written, `Entity` or `X::Entity`
+    pub fn lookup_type(&self, ns: NsId, name: &str) -> Option<&Symbol> {
+        let path: Vec<String> = name.split("::").map(str::to_string).collect();
+        match self.lookup_path(ns, &path) {
+            Ok(Res::Symbol(sym))
+                if matches!(sym.kind, SymbolKind::Struct | SymbolKind::Enum(_)) =>
+            {
+                Some(sym)
+            }
+            _ => None,
+        }
+    }
+}
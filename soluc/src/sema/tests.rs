@@ -0,0 +1,664 @@
+use super::*;
+use crate::diagnostics::Diagnostic;
+use crate::lexer::Lexer;
+use crate::parser::{Parser, Primitive};
+
+// Diagnostics from the semantic passes, which like in the driver stop at the
+// first one reporting an error
+fn diagnose(src: &str) -> Vec<Diagnostic> {
+    let tokens = Lexer::new(src).parse().expect("lexer errors");
+    let ast = Parser::new(tokens).parse().expect("parser errors");
+    let mut all = vec![];
+    let (symbols, diags) = resolve(&ast);
+    all.extend(diags);
+    if all.iter().any(|d| d.is_error()) {
+        return all;
+    }
+    let (types, diags) = check(&ast, &symbols);
+    all.extend(diags);
+    if all.iter().any(|d| d.is_error()) {
+        return all;
+    }
+    for pass in [
+        check_mutability(&ast, &symbols, &types),
+        check_flow(&ast, &types),
+        check_init(&ast, &types),
+    ] {
+        all.extend(pass);
+        if all.iter().any(|d| d.is_error()) {
+            break;
+        }
+    }
+    all
+}
+
+// Code, line and message of each diagnostic
+fn diags(src: &str) -> Vec<(&'static str, usize, String)> {
+    diagnose(src)
+        .into_iter()
+        .map(|d| (d.code, d.span.line, d.msg))
+        .collect()
+}
+
+fn accepts(src: &str) {
+    assert_eq!(diags(src), [], "{}", src);
+}
+
+// `src` gets exactly this one diagnostic
+fn reports(src: &str, code: &'static str, line: usize, msg: &str) {
+    assert_eq!(diags(src), [(code, line, msg.to_string())], "{}", src);
+}
+
+// What the checker found out about a program it accepts
+fn types(src: &str) -> TypeInfo {
+    let tokens = Lexer::new(src).parse().expect("lexer errors");
+    let ast = Parser::new(tokens).parse().expect("parser errors");
+    let (symbols, diags) = resolve(&ast);
+    assert_eq!(diags.len(), 0, "{:?}", diags);
+    let (types, diags) = check(&ast, &symbols);
+    assert_eq!(diags.len(), 0, "{:?}", diags);
+    types
+}
+
+// Wraps `body` in a `main` returning i32
+fn main(body: &str) -> String {
+    format!("main(): i32 do\n{}\nend\n", body)
+}
+
+#[test]
+fn char_casts() {
+    accepts(&main(
+        "\tc: char = [char] 97\n\tn: i32 = 5\n\td: char = [char] n\n\treturn [i32] c",
+    ));
+    reports(
+        &main("\tc: char = [char] 1114112\n\treturn 0"),
+        "E0701",
+        2,
+        "1114112 is not a valid `char`",
+    );
+    reports(
+        &main("\tc: char = [char] 0xD800\n\treturn 0"),
+        "E0701",
+        2,
+        "55296 is not a valid `char`",
+    );
+    reports(
+        &main("\tc: char = [char] -1\n\treturn 0"),
+        "E0701",
+        2,
+        "-1 is not a valid `char`",
+    );
+}
+
+#[test]
+fn mismatched_types() {
+    reports(
+        &main("\tx: i32 = true\n\treturn 0"),
+        "E0300",
+        2,
+        "mismatched types",
+    );
+    reports(
+        &main("\tx: i32 = 1 + true\n\treturn 0"),
+        "E0301",
+        2,
+        "cannot apply `+` to `i32` and `bool`",
+    );
+    reports(
+        "f(a: i32): i32 do return a end\nmain(): i32 do\n\treturn f(1, 2)\nend",
+        "E0302",
+        3,
+        "expected 1 argument(s), found 2",
+    );
+    reports(
+        &main("\tx: u8 = 300\n\treturn 0"),
+        "E0309",
+        2,
+        "literal out of range for `u8`",
+    );
+}
+
+#[test]
+fn implicit_conversions() {
+    accepts(&main(
+        "\ta: i8 = 1\n\tb: i32 = a\n\tc: u16 = 2\n\td: i32 = c\n\te: f64 = b\n\treturn b + d",
+    ));
+    // Changing signedness needs a cast
+    reports(
+        &main("\ta: i8 = 1\n\tc: u32 = a\n\treturn 0"),
+        "E0300",
+        3,
+        "mismatched types",
+    );
+    accepts(&main("\ta: i8 = 1\n\tc: u32 = [u32] a\n\treturn 0"));
+    reports(
+        &main("\tb: i32 = 1\n\te: f32 = b\n\treturn b"),
+        "W0300",
+        3,
+        "implicit conversion from `i32` to `f32` may lose precision",
+    );
+}
+
+const POINT: &str = "struct P is\n\tx: i32\nend\n";
+
+#[test]
+fn immutable_bindings() {
+    accepts(&main(
+        "\tmut y: i32 = 1\n\ty += 1\n\tz: i32\n\tz = 3\n\treturn y + z",
+    ));
+    reports(
+        &main("\tx: i32 = 1\n\tx = 2\n\treturn x"),
+        "E0400",
+        3,
+        "cannot assign to immutable binding `x`",
+    );
+    reports(
+        "f(a: i32): i32 do\n\ta = 2\n\treturn a\nend\nmain(): i32 do return f(1) end",
+        "E0400",
+        2,
+        "cannot assign to immutable binding `a`",
+    );
+    reports(
+        &main("\tfor i in 0..3 do\n\t\ti = 1\n\tend\n\treturn 0"),
+        "E0400",
+        3,
+        "cannot assign to immutable binding `i`",
+    );
+    reports(
+        "const N: i32 = 1\nmain(): i32 do\n\tN = 2\n\treturn 0\nend",
+        "E0403",
+        3,
+        "cannot assign to `N`",
+    );
+}
+
+#[test]
+fn writes_through_places() {
+    // Fields of an immutable struct are immutable too
+    reports(
+        &format!(
+            "{}main(): i32 do\n\tp: P = P with x = 1 end\n\tp.x = 2\n\treturn 0\nend",
+            POINT
+        ),
+        "E0400",
+        6,
+        "cannot assign to immutable binding `p`",
+    );
+    accepts(&format!(
+        "{}f(p: *P) do\n\tp.x = 1\nend\nmain(): i32 do return 0 end",
+        POINT
+    ));
+    reports(
+        &format!(
+            "{}f(p: &P) do\n\tp.x = 1\nend\nmain(): i32 do return 0 end",
+            POINT
+        ),
+        "E0401",
+        5,
+        "cannot assign to a place behind a `&` reference",
+    );
+}
+
+#[test]
+fn mutating_methods() {
+    let bump = format!("{}P::bump() do\n\tthis.x += 1\nend\n", POINT);
+    accepts(&format!(
+        "{}main(): i32 do\n\tmut q: P = P with x = 1 end\n\tq.bump()\n\treturn q.x\nend",
+        bump
+    ));
+    reports(
+        &format!(
+            "{}main(): i32 do\n\tp: P = P with x = 1 end\n\tp.bump()\n\treturn p.x\nend",
+            bump
+        ),
+        "E0402",
+        9,
+        "cannot call mutating method `bump` on immutable binding `p`",
+    );
+    // Calling a mutating method on `this` makes the caller mutating too
+    reports(
+        &format!(
+            "{}P::set(v: i32) do this.x = v end\nP::reset() do this.set(0) end\nmain(): i32 do\n\tp: P = P with x = 1 end\n\tp.reset()\n\treturn 0\nend",
+            POINT
+        ),
+        "E0402",
+        8,
+        "cannot call mutating method `reset` on immutable binding `p`",
+    );
+    // Reading through `this` doesn't
+    accepts(&format!(
+        "{}P::get(): i32 do return this.x end\nf(p: &P): i32 do return p.get() end\nmain(): i32 do\n\tp: P = P with x = 1 end\n\treturn p.get()\nend",
+        POINT
+    ));
+}
+
+#[test]
+fn missing_return() {
+    reports(
+        "sign(x: i32): i32 do\n\tif x < 0 then\n\t\treturn -1\n\telif x > 0 then\n\t\treturn 1\n\tend\nend\nmain(): i32 do return sign(1) end",
+        "E0500",
+        1,
+        "function `sign` may end without returning a value",
+    );
+    accepts(
+        "sign(x: i32): i32 do\n\tif x < 0 then\n\t\treturn -1\n\telse\n\t\treturn 1\n\tend\nend\nmain(): i32 do return sign(1) end",
+    );
+    // `while true` without a `break` never ends
+    accepts(&main("\twhile true do\n\tend"));
+    reports(
+        &main(
+            "\tmut i: i32 = 0\n\twhile true do\n\t\ti += 1\n\t\tif i > 3 then\n\t\t\tbreak\n\t\tend\n\tend",
+        ),
+        "E0500",
+        1,
+        "function `main` may end without returning a value",
+    );
+}
+
+#[test]
+fn jumps_outside_loops() {
+    reports(
+        &main("\tbreak\n\treturn 0"),
+        "E0501",
+        2,
+        "`break` outside of a loop",
+    );
+    reports(
+        &main("\tcontinue\n\treturn 0"),
+        "E0502",
+        2,
+        "`continue` outside of a loop",
+    );
+    accepts(&main(
+        "\tfor i in 0..3 do\n\t\tif i == 1 then\n\t\t\tcontinue\n\t\tend\n\t\tbreak\n\tend\n\treturn 0",
+    ));
+}
+
+// Only the first statement of an unreachable run is reported
+#[test]
+fn unreachable_code() {
+    reports(
+        &main("\treturn 0\n\tx: i32 = 1\n\treturn x"),
+        "W0500",
+        3,
+        "unreachable statement",
+    );
+    reports(
+        "f(c: bool): i32 do\n\tif c then\n\t\treturn 1\n\telse\n\t\treturn 2\n\tend\n\treturn 3\nend\nmain(): i32 do return f(true) end",
+        "W0500",
+        7,
+        "unreachable statement",
+    );
+    reports(
+        &main("\tfor i in 0..3 do\n\t\tcontinue\n\t\ti + 1\n\tend\n\treturn 0"),
+        "W0500",
+        4,
+        "unreachable statement",
+    );
+}
+
+#[test]
+fn assigned_on_every_path() {
+    reports(
+        "pick(c: bool): i32 do\n\tx: i32\n\tif c then\n\t\tx = 1\n\tend\n\treturn x\nend\nmain(): i32 do return pick(true) end",
+        "E0600",
+        6,
+        "`x` is used before it is assigned",
+    );
+    accepts(
+        "pick(c: bool): i32 do\n\tx: i32\n\tif c then\n\t\tx = 1\n\telse\n\t\tx = 2\n\tend\n\treturn x\nend\nmain(): i32 do return pick(true) end",
+    );
+    // The edge a literal condition never takes doesn't count
+    accepts(&main(
+        "\tx: i32\n\tif true then\n\t\tx = 1\n\tend\n\treturn x",
+    ));
+    accepts(&main(
+        "\tx: i32\n\twhile true do\n\t\tx = 7\n\t\tbreak\n\tend\n\treturn x",
+    ));
+}
+
+#[test]
+fn assigned_in_loops() {
+    // The body may not run at all
+    reports(
+        &main("\tx: i32\n\tfor i in 0..3 do\n\t\tx = i\n\tend\n\treturn x"),
+        "E0600",
+        6,
+        "`x` is used before it is assigned",
+    );
+    // A local declared in the body starts out unassigned on every iteration
+    reports(
+        &main(
+            "\tfor i in 0..3 do\n\t\tx: i32\n\t\tif i > 0 then\n\t\t\tx = 1\n\t\tend\n\t\tx + 1\n\tend\n\treturn 0",
+        ),
+        "E0600",
+        7,
+        "`x` is used before it is assigned",
+    );
+}
+
+#[test]
+fn assigned_fields() {
+    let pair = "struct P is\n\tx: i32\n\ty: i32\nend\n";
+    accepts(&format!(
+        "{}main(): i32 do\n\tmut p: P\n\tp.x = 1\n\treturn p.x\nend",
+        pair
+    ));
+    // Using the whole struct needs every field
+    reports(
+        &format!(
+            "{}main(): i32 do\n\tmut p: P\n\tp.x = 1\n\tr: P = p\n\treturn 0\nend",
+            pair
+        ),
+        "E0600",
+        8,
+        "`p` is used before it is assigned",
+    );
+    reports(
+        &format!(
+            "{}main(): i32 do\n\tp: P = P with x = 1 end\n\treturn p.x\nend",
+            pair
+        ),
+        "E0305",
+        6,
+        "missing field(s) in `P` literal",
+    );
+    // An element write assigns the whole array
+    accepts(&main("\tmut a: i32[3]\n\ta[0] = 1\n\treturn a[1]"));
+}
+
+// Value of each const, printed, by name
+fn consts(src: &str) -> Vec<(String, String)> {
+    let mut values: Vec<(String, String)> = types(src)
+        .const_values
+        .iter()
+        .map(|(name, v)| (name.clone(), v.to_string()))
+        .collect();
+    values.sort();
+    values
+}
+
+#[test]
+fn constant_values() {
+    assert_eq!(
+        consts(
+            "const SIDE: i32 = 10\nconst TILES: i32 = SIDE * SIDE * SIDE\nconst BIG: bool = TILES > 999 ? true : false\nconst WRAPPED: u8 = [u8] 300\nconst HALF: f64 = [f64] SIDE / 4.0\nmain(): i32 do return 0 end"
+        ),
+        [
+            ("BIG".to_string(), "true".to_string()),
+            ("HALF".to_string(), "2.5".to_string()),
+            ("SIDE".to_string(), "10".to_string()),
+            ("TILES".to_string(), "1000".to_string()),
+            ("WRAPPED".to_string(), "44".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn constant_errors() {
+    for (src, code, msg) in [
+        (
+            "const A: i32 = 2147483647 + 1",
+            "E0701",
+            "`(2147483647 + 1)` overflows `i32`",
+        ),
+        ("const A: i32 = 1 / 0", "E0702", "`(1 / 0)` divides by zero"),
+        (
+            "const A: i32 = 1 << 32",
+            "E0701",
+            "shift by 32 overflows `i32`",
+        ),
+        (
+            "const A: i32 = [i32] 2.9e10",
+            "E0701",
+            "`([i32] 29000000000.0)` overflows `i32`",
+        ),
+        (
+            "f(): i32 do return 1 end\nconst A: i32 = f()",
+            "E0700",
+            "calls can't be evaluated at compile time",
+        ),
+    ] {
+        let src = format!("{}\nmain(): i32 do return 0 end", src);
+        let line = src.lines().count() - 1;
+        reports(&src, code, line, msg);
+    }
+    reports(
+        "const A: i32 = B\nconst B: i32 = A + 1\nmain(): i32 do return 0 end",
+        "E0703",
+        1,
+        "constant `A` depends on itself",
+    );
+}
+
+#[test]
+fn array_lengths() {
+    accepts("const N: i32 = 4\nstruct S is\n\ta: i32[N * 2]\nend\nmain(): i32 do return 0 end");
+    reports(
+        "const N: i32 = -1\nstruct S is\n\ta: i32[N]\nend\nmain(): i32 do return 0 end",
+        "E0704",
+        3,
+        "array length can't be negative, `N` is -1",
+    );
+    reports(
+        "struct S is\n\ta: i32[1.5]\nend\nmain(): i32 do return 0 end",
+        "E0300",
+        2,
+        "array length must be an integer, found `f64`",
+    );
+    reports(
+        &main("\tn: i32 = 3\n\ta: i32[n]\n\treturn 0"),
+        "E0700",
+        3,
+        "local `n` can't be used in a constant expression",
+    );
+}
+
+#[test]
+fn case_labels() {
+    reports(
+        &main(
+            "\tn: i32 = 3\n\tswitch n then\n\tcase 1:\n\t\treturn 1\n\tcase 2 - 1:\n\t\treturn 2\n\tdefault:\n\t\treturn 0\n\tend",
+        ),
+        "E0705",
+        6,
+        "duplicate case `(2 - 1)`",
+    );
+}
+
+#[test]
+fn struct_layout() {
+    let types = types(
+        "struct Vec2 is\n\tx: f32\n\ty: f64\nend\nstruct Entity is\n\tid: u8\n\tpos: Vec2\n\tflags: u16\n\tnext: *Entity\nend\nmain(): i32 do return 0 end",
+    );
+    let entity = &types.layouts["Entity"];
+    let offsets: Vec<(&str, u64)> = entity
+        .fields
+        .iter()
+        .map(|f| (f.name.as_str(), f.offset))
+        .collect();
+    assert_eq!(
+        offsets,
+        [("id", 0), ("pos", 8), ("flags", 24), ("next", 32)]
+    );
+    assert_eq!(entity.layout, Layout { size: 40, align: 8 });
+    assert_eq!(types.layouts["Vec2"].layout, Layout { size: 16, align: 8 });
+}
+
+#[test]
+fn sizeof_alignof() {
+    assert_eq!(
+        consts(
+            "struct A is\n\tx: u8\n\ty: u16\n\tz: u8\nend\nconst S: u64 = sizeof(A)\nconst N: u64 = alignof(A) + sizeof(i64[3])\nmain(): i32 do return 0 end"
+        ),
+        [
+            ("N".to_string(), "26".to_string()),
+            ("S".to_string(), "6".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn unsized_structs() {
+    accepts("struct A is\n\tnext: *A\nend\nmain(): i32 do return 0 end");
+    reports(
+        "struct A is\n\tb: B\nend\nstruct B is\n\ta: A\nend\nmain(): i32 do return 0 end",
+        "E0800",
+        1,
+        "recursive struct `A` has infinite size",
+    );
+    reports(
+        "struct A is\n\tx: u64[4611686018427387904]\nend\nmain(): i32 do return 0 end",
+        "E0801",
+        2,
+        "`u64[4611686018427387904]` is too large",
+    );
+}
+
+#[test]
+fn discriminants() {
+    let types = types(
+        "enum Key: u8 is\n\tUp = 1\n\tDown\n\tQuit = 10\n\tLast = [u8] Key::Quit + 1\nend\nmain(): i32 do return 0 end",
+    );
+    let key = &types.enums["Key"];
+    assert_eq!(key.repr, Primitive::U8);
+    let variants: Vec<(&str, i128)> = key.variants.iter().map(|(n, v)| (n.as_str(), *v)).collect();
+    assert_eq!(
+        variants,
+        [("Up", 1), ("Down", 2), ("Quit", 10), ("Last", 11)]
+    );
+
+    reports(
+        "enum E is\n\tA = 1\n\tB = 1\nend\nmain(): i32 do return 0 end",
+        "E0706",
+        3,
+        "`B` has the same value as `A`",
+    );
+    reports(
+        "enum E: u8 is\n\tA = 255\n\tB\nend\nmain(): i32 do return 0 end",
+        "E0701",
+        3,
+        "discriminant of `B` overflows `u8`",
+    );
+    reports(
+        "enum E: f32 is\n\tA\nend\nmain(): i32 do return 0 end",
+        "E0310",
+        1,
+        "`f32` can't be the type of an enum",
+    );
+}
+
+#[test]
+fn exhaustive_switch() {
+    accepts(
+        "enum E is\n\tA\n\tB\nend\nf(e: E): i32 do\n\tswitch e then\n\tcase E::A:\n\t\treturn 1\n\tcase E::B:\n\t\treturn 2\n\tend\nend\nmain(): i32 do return f(E::A) end",
+    );
+    reports(
+        "enum E is\n\tA\n\tB\nend\nf(e: E): i32 do\n\tswitch e then\n\tcase E::A:\n\t\treturn 1\n\tend\n\treturn 0\nend\nmain(): i32 do return f(E::A) end",
+        "E0311",
+        6,
+        "switch over `E` doesn't handle every variant",
+    );
+}
+
+#[test]
+fn enum_conversions() {
+    reports(
+        "enum E is\n\tA\n\tB = 5\nend\nmain(): i32 do\n\te: E = [E] 3\n\treturn 0\nend",
+        "E0707",
+        6,
+        "no variant of `E` has the value 3",
+    );
+    reports(
+        "enum E is\n\tA\n\tB = 300\nend\nmain(): i32 do\n\tx: u8 = [u8] E::A\n\treturn 0\nend",
+        "E0306",
+        6,
+        "cannot cast `E` to `u8`",
+    );
+    reports(
+        "enum E is\n\tA\nend\nmain(): i32 do\n\tx: i32 = E::A\n\treturn 0\nend",
+        "E0300",
+        5,
+        "mismatched types",
+    );
+}
+
+#[test]
+fn method_receivers() {
+    reports(
+        "P::f() do end\nmain(): i32 do return 0 end",
+        "E0205",
+        1,
+        "cannot find type `P` for method `P::f`",
+    );
+    reports(
+        "enum E is\n\tA\nend\nE::f() do end\nmain(): i32 do return 0 end",
+        "E0205",
+        4,
+        "method receiver `E` is not a struct",
+    );
+    reports(
+        &main("return this"),
+        "E0200",
+        2,
+        "cannot find `this` in this scope",
+    );
+}
+
+#[test]
+fn method_calls() {
+    accepts(&format!(
+        "{}P::get(): i32 do return this.x end\nP::zero(): i32 do return 0 end\nf(p: &P): i32 do return p.get() end\n{}",
+        POINT,
+        main("p: P = P with x = 1 end\n\treturn p.get() + P::zero()")
+    ));
+    reports(
+        &format!(
+            "{}{}",
+            POINT,
+            main("p: P = P with x = 1 end\n\treturn p.nope()")
+        ),
+        "E0312",
+        6,
+        "no method `nope` on `P`",
+    );
+    reports(
+        &format!(
+            "{}P::zero(): i32 do return 0 end\n{}",
+            POINT,
+            main("p: P = P with x = 1 end\n\treturn p.zero()")
+        ),
+        "E0313",
+        7,
+        "`zero` is a static method of `P`",
+    );
+    reports(
+        &format!(
+            "{}P::get(): i32 do return this.x end\n{}",
+            POINT,
+            main("return P::get()")
+        ),
+        "E0313",
+        6,
+        "method `get` uses `this` and needs a receiver",
+    );
+}
+
+#[test]
+fn constructors() {
+    accepts(&format!(
+        "{}P(x: i32): P do return P with x = x end end\n{}",
+        POINT,
+        main("p: P = P(1)\n\treturn p.x")
+    ));
+    reports(
+        &format!(
+            "{}P(x: i32): i32 do return x end\n{}",
+            POINT,
+            main("return 0")
+        ),
+        "E0300",
+        4,
+        "constructor of `P` must return `P`",
+    );
+}
@@ -0,0 +1,118 @@
+use crate::parser::Primitive;
+use std::fmt;
+
+// An implicit conversion, and whether it can lose precision
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coercion {
+    Exact,
+    Lossy,
+}
+
+// Semantic type of a value. Named types are kept by their qualified path.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Ty {
+    Prim(Primitive),
+    Struct(String),
+    Enum(String),
+    Pointer(Box<Ty>),
+    Ref(Box<Ty>),
+    Array(Box<Ty>, Option<u64>), // Length is None when it couldn't be evaluated
+    Str,
+    Nil,   // Type of `nil`, assignable to any pointer or reference
+    Void,  // Result of calling a function without a return type
+    Error, // Already reported, accepted everywhere to avoid cascading errors
+}
+
+impl Ty {
+    pub fn is_error(&self) -> bool {
+        *self == Ty::Error
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Ty::Prim(p) if p.is_integer())
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, Ty::Prim(Primitive::F32 | Primitive::F64))
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+
+    pub fn is_bool(&self) -> bool {
+        *self == Ty::Prim(Primitive::Bool)
+    }
+
+    pub fn is_pointer_like(&self) -> bool {
+        matches!(self, Ty::Pointer(_) | Ty::Ref(_))
+    }
+
+    // Type behind a pointer or reference
+    pub fn pointee(&self) -> Option<&Ty> {
+        match self {
+            Ty::Pointer(t) | Ty::Ref(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    // Whether a value of type `self` can be stored where `to` is expected
+    pub fn assignable_to(&self, to: &Ty) -> bool {
+        match (self, to) {
+            (Ty::Error, _) | (_, Ty::Error) => true,
+            (Ty::Nil, t) => t.is_pointer_like(),
+            (Ty::Pointer(a), Ty::Pointer(b)) | (Ty::Ref(a), Ty::Ref(b)) => a.assignable_to(b),
+            (Ty::Array(a, n), Ty::Array(b, m)) => {
+                a.assignable_to(b) && (n.is_none() || m.is_none() || n == m)
+            }
+            (a, b) => a == b,
+        }
+    }
+}
+
+impl Ty {
+    // Numeric conversions done without a cast where a value is stored:
+    // integers widen to a type that holds all of their values, integers
+    // become floats and f32 becomes f64. Everything else needs a cast.
+    pub fn coerces_to(&self, to: &Ty) -> Option<Coercion> {
+        let (Ty::Prim(from), Ty::Prim(to)) = (self, to) else {
+            return None;
+        };
+        if from.is_integer() && to.is_integer() {
+            let wider = to.size() >= from.size();
+            let ok = (from.is_signed() == to.is_signed() && wider)
+                || (!from.is_signed() && to.is_signed() && to.size() > from.size());
+            return ok.then_some(Coercion::Exact);
+        }
+        match (from, to) {
+            (Primitive::F32, Primitive::F64) => Some(Coercion::Exact),
+            (p, Primitive::F32 | Primitive::F64) if p.is_integer() => {
+                let mantissa = if *to == Primitive::F32 { 24 } else { 53 };
+                let bits = p.size() * 8 - p.is_signed() as u64;
+                if bits <= mantissa {
+                    Some(Coercion::Exact)
+                } else {
+                    Some(Coercion::Lossy)
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ty::Prim(p) => write!(f, "{}", p),
+            Ty::Struct(s) | Ty::Enum(s) => write!(f, "{}", s),
+            Ty::Pointer(t) => write!(f, "*{}", t),
+            Ty::Ref(t) => write!(f, "&{}", t),
+            Ty::Array(t, Some(n)) => write!(f, "{}[{}]", t, n),
+            Ty::Array(t, None) => write!(f, "{}[_]", t),
+            Ty::Str => write!(f, "String"),
+            Ty::Nil => write!(f, "nil"),
+            Ty::Void => write!(f, "void"),
+            Ty::Error => write!(f, "{{error}}"),
+        }
+    }
+}
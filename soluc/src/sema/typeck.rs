@@ -0,0 +1,1334 @@
+use super::consteval::{ConstEval, Value, int_fits, not_constant};
+use super::enums::{EnumInfo, enum_repr};
+use super::layout::StructLayout;
+use super::mutability::{walk_block, walk_expr};
+use super::symbols::*;
+use super::ty::{Coercion, Ty};
+use crate::diagnostics::{self, Diagnostic};
+use crate::lexer::Span;
+use crate::parser::*;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone)]
+pub struct FnSig {
+    pub params: Vec<Ty>,
+    pub ret: Ty,
+    // Type of `this` for methods called on a value, None for functions,
+    // constructors and static methods
+    pub this: Option<Ty>,
+    pub span: Span,
+}
+
+// Everything later passes need to know about types. Named items are keyed by
+// their qualified path, expressions by their span.
+#[derive(Debug, Clone, Default)]
+pub struct TypeInfo {
+    pub structs: HashMap<String, Vec<(String, Ty)>>,
+    pub funcs: HashMap<String, FnSig>,
+    pub methods: HashMap<String, HashMap<String, FnSig>>,
+    pub ctors: HashMap<String, FnSig>,
+    pub consts: HashMap<String, Ty>,
+    pub const_values: HashMap<String, Value>,
+    pub layouts: HashMap<String, StructLayout>,
+    pub enums: HashMap<String, EnumInfo>,
+    // Switches over an enum without a `default` that handle every variant,
+    // by the span of the statement
+    pub exhaustive: HashSet<(usize, usize)>,
+    // Expressions implicitly converted to another numeric type, by span
+    pub coercions: HashMap<(usize, usize), Ty>,
+    // Type of each local declared with `x: T` or as a `for` loop variable,
+    // by the span of the declaring statement
+    pub locals: HashMap<(usize, usize), Ty>,
+    pub exprs: HashMap<(usize, usize), Ty>,
+}
+
+impl TypeInfo {
+    pub fn type_of(&self, expr: &Expr) -> Option<&Ty> {
+        self.exprs.get(&(expr.span.start, expr.span.end))
+    }
+}
+
+// Type checks a resolved program
+pub fn check(ast: &Namespace, table: &SymbolTable) -> (TypeInfo, Vec<Diagnostic>) {
+    let mut c = Checker {
+        table,
+        info: TypeInfo::default(),
+        diags: vec![],
+        ns: GLOBAL,
+        locals: vec![],
+        ret: Ty::Void,
+        eval: ConstEval::new(ast, table),
+        lens: HashMap::new(),
+    };
+    c.collect(&ast.nodes, GLOBAL);
+    c.check_decls(&ast.nodes, GLOBAL);
+    c.eval.layout_all();
+    c.diags.append(&mut c.eval.diags);
+    c.info.layouts = c
+        .eval
+        .layouts
+        .iter()
+        .filter_map(|(path, l)| l.clone().map(|l| (path.clone(), l)))
+        .collect();
+    c.info.enums = c
+        .eval
+        .discriminants
+        .iter()
+        .filter_map(|(path, e)| e.clone().map(|e| (path.clone(), e)))
+        .collect();
+    c.info.const_values = c
+        .eval
+        .values()
+        .map(|(path, v)| (path.clone(), v.clone()))
+        .collect();
+    (c.info, c.diags)
+}
+
+struct Checker<'a> {
+    table: &'a SymbolTable,
+    info: TypeInfo,
+    diags: Vec<Diagnostic>,
+    ns: NsId,
+    locals: Vec<HashMap<String, Ty>>,
+    ret: Ty, // Return type of the function being checked
+    eval: ConstEval<'a>,
+    // Array lengths by the span of their size expression, so each one is
+    // evaluated and reported once
+    lens: HashMap<(usize, usize), Option<u64>>,
+}
+
+// ---------------------------------------
+// Declarations
+// ---------------------------------------
+impl Checker<'_> {
+    // Records struct fields, const types and function signatures so bodies
+    // can refer to items declared after them
+    fn collect(&mut self, nodes: &[Decl], ns: NsId) {
+        self.ns = ns;
+        for decl in nodes {
+            match decl {
+                Decl::Namespace(n) => {
+                    let id = self.namespace(ns, &n.name);
+                    self.collect(&n.nodes, id);
+                    self.ns = ns;
+                }
+                Decl::Struct(s) => {
+                    let fields = s
+                        .members
+                        .iter()
+                        .map(|m| (m.name.clone(), self.lower_type(&m.ty)))
+                        .collect();
+                    self.info
+                        .structs
+                        .insert(self.table.qualify(ns, &s.name), fields);
+                }
+                Decl::Const(c) => {
+                    let ty = self.lower_type(&c.ty);
+                    self.info.consts.insert(self.table.qualify(ns, &c.name), ty);
+                }
+                Decl::Function(f) => {
+                    let owner = self.owner(f);
+                    let this = match &owner {
+                        Some((path, true)) if uses_this(&f.body) => {
+                            Some(Ty::Pointer(Box::new(Ty::Struct(path.clone()))))
+                        }
+                        _ => None,
+                    };
+                    let sig = FnSig {
+                        params: f.params.iter().map(|p| self.lower_type(&p.ty)).collect(),
+                        ret: f.ret.as_ref().map_or(Ty::Void, |t| self.lower_type(t)),
+                        this,
+                        span: f.span,
+                    };
+                    match owner {
+                        Some((path, true)) => {
+                            self.info
+                                .methods
+                                .entry(path)
+                                .or_default()
+                                .insert(f.name.clone(), sig);
+                        }
+                        Some((path, false)) => {
+                            self.check_ctor(f, &path, &sig.ret);
+                            self.info.ctors.insert(path, sig);
+                        }
+                        // Reported by the resolver
+                        None if f.receiver.is_some() => {}
+                        None => {
+                            self.info.funcs.insert(self.table.qualify(ns, &f.name), sig);
+                        }
+                    }
+                }
+                Decl::Enum(_) => {}
+            }
+        }
+    }
+
+    fn check_decls(&mut self, nodes: &[Decl], ns: NsId) {
+        for decl in nodes {
+            self.ns = ns;
+            match decl {
+                Decl::Namespace(n) => {
+                    let id = self.namespace(ns, &n.name);
+                    self.check_decls(&n.nodes, id);
+                }
+                Decl::Const(c) => {
+                    let ty = self.lower_type(&c.ty);
+                    let errors = self.diags.len();
+                    let found = self.check_expr(&c.value, Some(&ty));
+                    self.expect_assignable(&found, &ty, c.value.span);
+                    if self.diags.len() == errors {
+                        self.eval.value_of(&self.table.qualify(ns, &c.name));
+                        self.diags.append(&mut self.eval.diags);
+                    }
+                }
+                Decl::Function(f) => self.check_function(f),
+                Decl::Enum(e) => self.check_enum(e, ns),
+                Decl::Struct(_) => {}
+            }
+        }
+    }
+
+    // The underlying type must be an integer and explicit values must fit
+    // it. Discriminants are numbered once they all type check.
+    fn check_enum(&mut self, e: &Enum, ns: NsId) {
+        if let Some(ty) = &e.ty {
+            let repr = self.lower_type(ty);
+            if !repr.is_error() && !repr.is_integer() {
+                self.diags.push(
+                    Diagnostic::error(
+                        diagnostics::INVALID_ENUM_TYPE,
+                        ty.span,
+                        format!("`{}` can't be the type of an enum", repr),
+                    )
+                    .with_label("expected an integer type"),
+                );
+            }
+        }
+        let repr = Ty::Prim(enum_repr(e));
+        let errors = self.diags.len();
+        for v in e.variants.iter() {
+            if let Some(value) = &v.value {
+                let found = self.check_expr(value, Some(&repr));
+                self.expect_assignable(&found, &repr, value.span);
+            }
+        }
+        if self.diags.len() == errors {
+            self.eval.enum_info(&self.table.qualify(ns, &e.name));
+            self.diags.append(&mut self.eval.diags);
+        }
+    }
+
+    // A function named after a struct constructs it
+    fn check_ctor(&mut self, f: &Function, path: &str, ret: &Ty) {
+        let expected = Ty::Struct(path.to_string());
+        if *ret == expected || ret.is_error() {
+            return;
+        }
+        let span = f.ret.as_ref().map_or(f.span, |t| t.span);
+        self.diags.push(
+            Diagnostic::error(
+                diagnostics::MISMATCHED_TYPES,
+                span,
+                format!("constructor of `{}` must return `{}`", path, expected),
+            )
+            .with_label(format!("found `{}`", ret))
+            .with_help(format!("declare it as `{}(...): {}`", f.name, f.name)),
+        );
+    }
+
+    fn check_function(&mut self, f: &Function) {
+        let mut scope = HashMap::new();
+        if f.receiver.is_some() {
+            // `this` points at the value the method is called on
+            let this = match self.owner(f) {
+                Some((path, _)) => Ty::Pointer(Box::new(Ty::Struct(path))),
+                None => Ty::Error,
+            };
+            scope.insert("this".to_string(), this);
+        }
+        for p in f.params.iter() {
+            scope.insert(p.name.clone(), self.lower_type(&p.ty));
+        }
+        self.locals = vec![scope];
+        self.ret = f.ret.as_ref().map_or(Ty::Void, |t| self.lower_type(t));
+        self.check_block(&f.body);
+        self.locals.clear();
+    }
+
+    // The struct a function belongs to, and whether it's a method (true) or
+    // the struct's constructor (false)
+    fn owner(&self, f: &Function) -> Option<(String, bool)> {
+        match &f.receiver {
+            Some(r) => match self.table.lookup(self.ns, r) {
+                Some(sym) if matches!(sym.kind, SymbolKind::Struct) => {
+                    Some((sym.path.clone(), true))
+                }
+                _ => None,
+            },
+            None => match self.table.scopes[self.ns].items.get(&f.name) {
+                Some(sym) if matches!(sym.kind, SymbolKind::Struct) => {
+                    Some((sym.path.clone(), false))
+                }
+                _ => None,
+            },
+        }
+    }
+
+    fn namespace(&self, ns: NsId, name: &str) -> NsId {
+        match self.table.scopes[ns].items.get(name).map(|s| &s.kind) {
+            Some(SymbolKind::Namespace(id)) => *id,
+            _ => ns,
+        }
+    }
+
+    pub fn lower_type(&mut self, ty: &Type) -> Ty {
+        match &ty.kind {
+            TypeKind::Primitive(p) => Ty::Prim(*p),
+            TypeKind::Named(name) if BUILTIN_TYPES.contains(&name.as_str()) => Ty::Str,
+            TypeKind::Named(name) => match self.table.lookup_type(self.ns, name) {
+                Some(sym) if matches!(sym.kind, SymbolKind::Struct) => Ty::Struct(sym.path.clone()),
+                Some(sym) => Ty::Enum(sym.path.clone()),
+                // Reported by the resolver
+                None => Ty::Error,
+            },
+            TypeKind::Pointer(t) => Ty::Pointer(Box::new(self.lower_type(t))),
+            TypeKind::Ref(t) => Ty::Ref(Box::new(self.lower_type(t))),
+            TypeKind::Array(t, size) => {
+                let len = self.array_len(size);
+                Ty::Array(Box::new(self.lower_type(t)), len)
+            }
+        }
+    }
+
+    fn array_len(&mut self, size: &Expr) -> Option<u64> {
+        let key = (size.span.start, size.span.end);
+        if let Some(len) = self.lens.get(&key) {
+            return *len;
+        }
+        let errors = self.diags.len();
+        let ty = self.check_expr(size, Some(&Ty::Prim(Primitive::U64)));
+        let len = if !ty.is_integer() {
+            if !ty.is_error() {
+                self.diags.push(
+                    Diagnostic::error(
+                        diagnostics::MISMATCHED_TYPES,
+                        size.span,
+                        format!("array length must be an integer, found `{}`", ty),
+                    )
+                    .with_label("expected an integer"),
+                );
+            }
+            None
+        } else if self.diags.len() > errors || self.reject_locals(size) {
+            None
+        } else {
+            let n = self.eval.array_len(size, self.ns);
+            self.diags.append(&mut self.eval.diags);
+            match n {
+                Some(n) if n >= 0 => Some(n as u64),
+                Some(n) => {
+                    self.diags.push(
+                        Diagnostic::error(
+                            diagnostics::INVALID_ARRAY_LENGTH,
+                            size.span,
+                            format!("array length can't be negative, `{}` is {}", size, n),
+                        )
+                        .with_label("negative length"),
+                    );
+                    None
+                }
+                _ => None,
+            }
+        };
+        self.lens.insert(key, len);
+        len
+    }
+
+    // Evaluates an expression that must be constant. Locals can't appear in
+    // one, the evaluator only knows about global items.
+    fn eval_const(&mut self, expr: &Expr, expected: Option<&Ty>) -> Option<Value> {
+        if self.reject_locals(expr) {
+            return None;
+        }
+        let expected = match expected {
+            Some(Ty::Prim(p)) => Some(*p),
+            _ => None,
+        };
+        let v = self.eval.eval(expr, self.ns, expected);
+        self.diags.append(&mut self.eval.diags);
+        v
+    }
+
+    // Reports the first local used in a constant expression
+    fn reject_locals(&mut self, expr: &Expr) -> bool {
+        let mut local = None;
+        walk_expr(expr, &mut |e| {
+            if local.is_none()
+                && let ExprKind::Ident(name) = &e.kind
+                && self.local(name).is_some()
+            {
+                local = Some((name.clone(), e.span));
+            }
+        });
+        if let Some((name, span)) = local {
+            self.diags.push(not_constant(
+                span,
+                format!("local `{}` can't be used in a constant expression", name),
+            ));
+            return true;
+        }
+        false
+    }
+}
+
+// ---------------------------------------
+// Statements
+// ---------------------------------------
+impl Checker<'_> {
+    fn check_block(&mut self, block: &Block) {
+        self.locals.push(HashMap::new());
+        for stmt in block.stmts.iter() {
+            self.check_stmt(stmt);
+        }
+        self.locals.pop();
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Var {
+                name, ty, value, ..
+            } => {
+                let ty = self.lower_type(ty);
+                if let Some(v) = value {
+                    let found = self.check_expr(v, Some(&ty));
+                    self.expect_assignable(&found, &ty, v.span);
+                }
+                self.info
+                    .locals
+                    .insert((stmt.span.start, stmt.span.end), ty.clone());
+                self.declare(name, ty);
+            }
+            StmtKind::Assign { target, op, value } => {
+                let target_ty = self.check_expr(target, None);
+                let found = self.check_expr(value, Some(&target_ty));
+                match op.binary() {
+                    None => self.expect_assignable(&found, &target_ty, value.span),
+                    Some(bin) => {
+                        let result = self.binary_result(bin, &target_ty, &found, stmt.span);
+                        self.expect_assignable(&result, &target_ty, stmt.span);
+                    }
+                }
+            }
+            StmtKind::Expr(e) => {
+                self.check_expr(e, None);
+            }
+            StmtKind::Return(value) => {
+                let ret = self.ret.clone();
+                match value {
+                    Some(v) if ret == Ty::Void => {
+                        self.check_expr(v, None);
+                        self.diags.push(
+                            Diagnostic::error(
+                                diagnostics::MISMATCHED_TYPES,
+                                v.span,
+                                "function has no return type but returns a value",
+                            )
+                            .with_help("declare a return type with `name(...): type`"),
+                        );
+                    }
+                    Some(v) => {
+                        let found = self.check_expr(v, Some(&ret));
+                        self.expect_assignable(&found, &ret, v.span);
+                    }
+                    None if ret != Ty::Void && !ret.is_error() => {
+                        self.diags.push(
+                            Diagnostic::error(
+                                diagnostics::MISSING_RETURN_VALUE,
+                                stmt.span,
+                                format!("missing return value of type `{}`", ret),
+                            )
+                            .with_label("returns nothing"),
+                        );
+                    }
+                    None => {}
+                }
+            }
+            StmtKind::Break | StmtKind::Continue => {}
+            StmtKind::If {
+                branches,
+                else_body,
+            } => {
+                for (cond, body) in branches.iter() {
+                    self.check_cond(cond);
+                    self.check_block(body);
+                }
+                if let Some(body) = else_body {
+                    self.check_block(body);
+                }
+            }
+            StmtKind::While { cond, body } => {
+                self.check_cond(cond);
+                self.check_block(body);
+            }
+            StmtKind::For {
+                var,
+                start,
+                end,
+                body,
+            } => {
+                let var_ty = match end {
+                    Some(end) => {
+                        let (ty, end_ty) = self.check_operands(start, end, None);
+                        if !ty.is_error() && !ty.is_integer() {
+                            self.invalid_operand(start.span, "range bound", &ty);
+                        } else if !end_ty.assignable_to(&ty) {
+                            self.mismatch(end.span, &ty, &end_ty);
+                        }
+                        ty
+                    }
+                    None => match self.check_expr(start, None) {
+                        Ty::Array(elem, _) => *elem,
+                        Ty::Error => Ty::Error,
+                        t => {
+                            self.diags.push(
+                                Diagnostic::error(
+                                    diagnostics::MISMATCHED_TYPES,
+                                    start.span,
+                                    format!("cannot iterate over `{}`", t),
+                                )
+                                .with_help("loop over an array or a range `start..end`"),
+                            );
+                            Ty::Error
+                        }
+                    },
+                };
+                self.info
+                    .locals
+                    .insert((stmt.span.start, stmt.span.end), var_ty.clone());
+                self.locals.push(HashMap::new());
+                self.declare(var, var_ty);
+                self.check_block(body);
+                self.locals.pop();
+            }
+            StmtKind::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                let ty = self.check_expr(value, None);
+                let mut seen: Vec<(Value, Span)> = vec![];
+                let mut complete = true;
+                for case in cases.iter() {
+                    let errors = self.diags.len();
+                    let found = self.check_expr(&case.value, Some(&ty));
+                    // No conversions, a case has to match the value exactly
+                    if !found.assignable_to(&ty) {
+                        self.mismatch(case.value.span, &ty, &found);
+                    }
+                    // Case labels are constants, each value can only appear once
+                    let v = if self.diags.len() == errors {
+                        self.eval_const(&case.value, Some(&ty))
+                    } else {
+                        None
+                    };
+                    complete &= v.is_some();
+                    if let Some(v) = v {
+                        match seen.iter().find(|(w, _)| *w == v) {
+                            Some((_, first)) => self.diags.push(
+                                Diagnostic::error(
+                                    diagnostics::DUPLICATE_CASE,
+                                    case.value.span,
+                                    format!("duplicate case `{}`", case.value),
+                                )
+                                .with_label(format!("`{}` is already handled", v))
+                                .with_secondary(*first, "first handled here"),
+                            ),
+                            None => seen.push((v, case.value.span)),
+                        }
+                    }
+                    self.check_block(&case.body);
+                }
+                if let Some(body) = default {
+                    self.check_block(body);
+                } else if let Ty::Enum(path) = &ty
+                    && complete
+                {
+                    self.check_exhaustive(stmt, value, path, &seen);
+                }
+            }
+        }
+    }
+
+    // A switch over an enum without a `default` has to handle every variant
+    fn check_exhaustive(&mut self, stmt: &Stmt, value: &Expr, path: &str, seen: &[(Value, Span)]) {
+        let Some(info) = self.eval.enum_info(path) else {
+            return;
+        };
+        self.diags.append(&mut self.eval.diags);
+        let missing: Vec<String> = info
+            .variants
+            .iter()
+            .filter(|(name, _)| {
+                !seen
+                    .iter()
+                    .any(|(v, _)| matches!(v, Value::Variant(_, n, _) if n == name))
+            })
+            .map(|(name, _)| format!("`{}`", name))
+            .collect();
+        if missing.is_empty() {
+            self.info
+                .exhaustive
+                .insert((stmt.span.start, stmt.span.end));
+            return;
+        }
+        self.diags.push(
+            Diagnostic::error(
+                diagnostics::NON_EXHAUSTIVE_SWITCH,
+                value.span,
+                format!("switch over `{}` doesn't handle every variant", path),
+            )
+            .with_label(format!("{} not handled", missing.join(", ")))
+            .with_help("add a `case` for each of them, or a `default`"),
+        );
+    }
+
+    fn check_cond(&mut self, cond: &Expr) {
+        let ty = self.check_expr(cond, Some(&Ty::Prim(Primitive::Bool)));
+        self.expect_assignable(&ty, &Ty::Prim(Primitive::Bool), cond.span);
+    }
+
+    fn declare(&mut self, name: &str, ty: Ty) {
+        self.locals.last_mut().unwrap().insert(name.to_string(), ty);
+    }
+
+    fn local(&self, name: &str) -> Option<&Ty> {
+        self.locals.iter().rev().find_map(|scope| scope.get(name))
+    }
+}
+
+// ---------------------------------------
+// Expressions
+// ---------------------------------------
+impl Checker<'_> {
+    // `expected` lets literals take the type of their context, so `x: u8 = 1`
+    // types the 1 as a u8
+    fn check_expr(&mut self, expr: &Expr, expected: Option<&Ty>) -> Ty {
+        let ty = self.infer_expr(expr, expected);
+        self.info
+            .exprs
+            .insert((expr.span.start, expr.span.end), ty.clone());
+        ty
+    }
+
+    fn infer_expr(&mut self, expr: &Expr, expected: Option<&Ty>) -> Ty {
+        match &expr.kind {
+            ExprKind::Int(n) => self.int_literal(*n, false, expected, expr.span),
+            ExprKind::Float(_) => match expected {
+                Some(t) if t.is_float() => t.clone(),
+                _ => Ty::Prim(Primitive::F64),
+            },
+            ExprKind::Bool(_) => Ty::Prim(Primitive::Bool),
+            ExprKind::Char(_) => Ty::Prim(Primitive::Char),
+            ExprKind::Str(_) => Ty::Str,
+            ExprKind::Nil => Ty::Nil,
+            // Typed like an integer literal
+            ExprKind::SizeOf(ty) | ExprKind::AlignOf(ty) => {
+                self.lower_type(ty);
+                self.eval.type_layout(ty, self.ns);
+                self.diags.append(&mut self.eval.diags);
+                match expected {
+                    Some(Ty::Prim(p)) if p.is_integer() => Ty::Prim(*p),
+                    _ => Ty::Prim(Primitive::U64),
+                }
+            }
+            ExprKind::Ident(name) => self.ident_type(name, expr.span),
+            ExprKind::Path(path) => self.path_type(path, expr.span),
+            ExprKind::Unary {
+                op: UnaryOp::Neg,
+                expr: inner,
+            } if matches!(inner.kind, ExprKind::Int(_)) => {
+                let ExprKind::Int(n) = inner.kind else {
+                    unreachable!()
+                };
+                let ty = self.int_literal(n, true, expected, expr.span);
+                self.info
+                    .exprs
+                    .insert((inner.span.start, inner.span.end), ty.clone());
+                ty
+            }
+            ExprKind::Unary { op, expr: inner } => {
+                let ty = self.check_expr(inner, expected);
+                self.unary_result(*op, &ty, expr.span)
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                let expected = match op {
+                    BinaryOp::Add
+                    | BinaryOp::Sub
+                    | BinaryOp::Mul
+                    | BinaryOp::Div
+                    | BinaryOp::Mod
+                    | BinaryOp::BitAnd
+                    | BinaryOp::BitOr
+                    | BinaryOp::BitXor => expected,
+                    _ => None,
+                };
+                let (lt, rt) = self.check_operands(lhs, rhs, expected);
+                self.binary_result(*op, &lt, &rt, expr.span)
+            }
+            ExprKind::Ternary {
+                cond,
+                then,
+                otherwise,
+            } => {
+                self.check_cond(cond);
+                let (then_ty, else_ty) = self.check_operands(then, otherwise, expected);
+                if then_ty.assignable_to(&else_ty) {
+                    if then_ty == Ty::Nil { else_ty } else { then_ty }
+                } else if else_ty.assignable_to(&then_ty) {
+                    then_ty
+                } else {
+                    self.mismatch(otherwise.span, &then_ty, &else_ty);
+                    Ty::Error
+                }
+            }
+            ExprKind::Cast { ty, expr: inner } => {
+                let to = self.lower_type(ty);
+                let from = self.check_expr(inner, None);
+                if !castable(&from, &to) {
+                    self.diags.push(
+                        Diagnostic::error(
+                            diagnostics::INVALID_CAST,
+                            expr.span,
+                            format!("cannot cast `{}` to `{}`", from, to),
+                        )
+                        .with_label("invalid cast"),
+                    );
+                } else {
+                    self.check_cast_range(expr, inner, &from, &to);
+                }
+                to
+            }
+            ExprKind::Call { callee, args } => self.check_call(callee, args, expr.span),
+            ExprKind::Member { base, field } => {
+                let base_ty = self.check_expr(base, None);
+                self.field_type(&base_ty, field, expr.span)
+            }
+            ExprKind::Index { base, index } => {
+                let base_ty = self.check_expr(base, None);
+                let index_ty = self.check_expr(index, None);
+                if !index_ty.is_error() && !index_ty.is_integer() {
+                    self.invalid_operand(index.span, "index", &index_ty);
+                }
+                match base_ty {
+                    Ty::Array(elem, _) | Ty::Pointer(elem) => *elem,
+                    Ty::Error => Ty::Error,
+                    t => {
+                        self.diags.push(
+                            Diagnostic::error(
+                                diagnostics::NOT_INDEXABLE,
+                                base.span,
+                                format!("cannot index into `{}`", t),
+                            )
+                            .with_label("not an array or pointer"),
+                        );
+                        Ty::Error
+                    }
+                }
+            }
+            ExprKind::StructLit { name, fields } => self.check_struct_lit(name, fields, expr.span),
+        }
+    }
+
+    fn int_literal(&mut self, n: u64, neg: bool, expected: Option<&Ty>, span: Span) -> Ty {
+        let ty = match expected {
+            Some(Ty::Prim(p)) if p.is_integer() || matches!(p, Primitive::F32 | Primitive::F64) => {
+                *p
+            }
+            _ => Primitive::I32,
+        };
+        if ty.is_integer() && !ty.fits(n, neg) {
+            self.diags.push(
+                Diagnostic::error(
+                    diagnostics::LITERAL_OUT_OF_RANGE,
+                    span,
+                    format!("literal out of range for `{}`", ty),
+                )
+                .with_label(format!("doesn't fit in a `{}`", ty)),
+            );
+        }
+        Ty::Prim(ty)
+    }
+
+    // Checks both sides of a binary operator. A literal on the left takes its
+    // type from the right so `1 + x` works for any integer `x`.
+    fn check_operands(&mut self, lhs: &Expr, rhs: &Expr, expected: Option<&Ty>) -> (Ty, Ty) {
+        if is_literal(lhs) && !is_literal(rhs) {
+            let rt = self.check_expr(rhs, expected);
+            let lt = self.check_expr(lhs, Some(&rt));
+            (lt, rt)
+        } else {
+            let lt = self.check_expr(lhs, expected);
+            let rt = self.check_expr(rhs, Some(&lt));
+            (lt, rt)
+        }
+    }
+
+    fn ident_type(&mut self, name: &str, span: Span) -> Ty {
+        if let Some(ty) = self.local(name) {
+            return ty.clone();
+        }
+        match self.table.lookup(self.ns, name) {
+            Some(sym) => self.symbol_type(sym, name, span),
+            None => Ty::Error,
+        }
+    }
+
+    fn path_type(&mut self, path: &[String], span: Span) -> Ty {
+        match self.table.lookup_path(self.ns, path) {
+            Ok(Res::Symbol(sym)) => self.symbol_type(sym, &path.join("::"), span),
+            Ok(Res::Variant(sym, _)) => Ty::Enum(sym.path.clone()),
+            Ok(Res::Method(..)) => {
+                self.not_a_value("method", &path.join("::"), span);
+                Ty::Error
+            }
+            Err(_) => Ty::Error,
+        }
+    }
+
+    fn symbol_type(&mut self, sym: &Symbol, name: &str, span: Span) -> Ty {
+        match sym.kind {
+            SymbolKind::Const => self
+                .info
+                .consts
+                .get(&sym.path)
+                .cloned()
+                .unwrap_or(Ty::Error),
+            SymbolKind::Function | SymbolKind::Struct => {
+                self.not_a_value(sym.describe(), name, span);
+                Ty::Error
+            }
+            // Reported by the resolver
+            SymbolKind::Namespace(_) | SymbolKind::Enum(_) => Ty::Error,
+        }
+    }
+
+    fn check_call(&mut self, callee: &Expr, args: &[Expr], span: Span) -> Ty {
+        let sig = match &callee.kind {
+            ExprKind::Ident(name) if self.local(name).is_none() => self
+                .table
+                .lookup(self.ns, name)
+                .map(|sym| (sym.clone(), None)),
+            ExprKind::Path(path) => match self.table.lookup_path(self.ns, path) {
+                Ok(Res::Symbol(sym)) => Some((sym.clone(), None)),
+                Ok(Res::Method(sym, _)) => Some((sym.clone(), path.last().cloned())),
+                _ => None,
+            },
+            _ => None,
+        };
+        let sig = match sig {
+            Some((sym, Some(method))) => {
+                let sig = self
+                    .info
+                    .methods
+                    .get(&sym.path)
+                    .and_then(|m| m.get(&method))
+                    .cloned();
+                if let Some(sig) = &sig
+                    && sig.this.is_some()
+                {
+                    self.diags.push(
+                        Diagnostic::error(
+                            diagnostics::RECEIVER_MISMATCH,
+                            callee.span,
+                            format!("method `{}` uses `this` and needs a receiver", method),
+                        )
+                        .with_label("called without a value")
+                        .with_help(format!(
+                            "call it on a value of type `{}`, e.g. `value.{}(...)`",
+                            sym.name, method
+                        )),
+                    );
+                }
+                sig
+            }
+            Some((sym, None)) => match sym.kind {
+                SymbolKind::Function => self.info.funcs.get(&sym.path).cloned(),
+                SymbolKind::Struct => match self.info.ctors.get(&sym.path) {
+                    Some(sig) => Some(sig.clone()),
+                    None => {
+                        self.diags.push(
+                            Diagnostic::error(
+                                diagnostics::NOT_CALLABLE,
+                                callee.span,
+                                format!("struct `{}` has no constructor", sym.path),
+                            )
+                            .with_help(format!(
+                                "build it with `{} with ... end` or declare `{}(...)`",
+                                sym.name, sym.name
+                            )),
+                        );
+                        self.check_args_unknown(args);
+                        return Ty::Struct(sym.path.clone());
+                    }
+                },
+                _ => None,
+            },
+            None if matches!(callee.kind, ExprKind::Member { .. }) => {
+                let ExprKind::Member { base, field } = &callee.kind else {
+                    unreachable!()
+                };
+                return self.check_method_call(base, field, callee.span, args, span);
+            }
+            None => None,
+        };
+        let Some(sig) = sig else {
+            let ty = self.check_expr(callee, None);
+            if !ty.is_error() {
+                self.diags.push(
+                    Diagnostic::error(
+                        diagnostics::NOT_CALLABLE,
+                        callee.span,
+                        format!("`{}` is not callable", ty),
+                    )
+                    .with_label("not a function"),
+                );
+            }
+            self.check_args_unknown(args);
+            return Ty::Error;
+        };
+        self.check_args(&sig, args, span);
+        sig.ret
+    }
+
+    // `value.method(...)` finds the method on the value's struct, through one
+    // level of pointer or reference like fields do
+    fn check_method_call(
+        &mut self,
+        base: &Expr,
+        method: &str,
+        callee: Span,
+        args: &[Expr],
+        span: Span,
+    ) -> Ty {
+        let base_ty = self.check_expr(base, None);
+        let strukt = match &base_ty {
+            Ty::Struct(s) => Some(s),
+            Ty::Pointer(t) | Ty::Ref(t) => match &**t {
+                Ty::Struct(s) => Some(s),
+                _ => None,
+            },
+            _ => None,
+        };
+        let sig = strukt.and_then(|s| self.info.methods.get(s)?.get(method).cloned());
+        let Some(sig) = sig else {
+            if !base_ty.is_error() {
+                let mut d = Diagnostic::error(
+                    diagnostics::UNKNOWN_METHOD,
+                    callee,
+                    format!("no method `{}` on `{}`", method, base_ty),
+                )
+                .with_label("unknown method");
+                if let Some(s) = strukt
+                    && self
+                        .info
+                        .structs
+                        .get(s)
+                        .is_some_and(|fields| fields.iter().any(|(f, _)| f == method))
+                {
+                    d = d.with_note(format!("`{}` is a field, not a method", method));
+                }
+                self.diags.push(d);
+            }
+            self.check_args_unknown(args);
+            return Ty::Error;
+        };
+        if sig.this.is_none() {
+            let owner = strukt.map_or(String::new(), |s| s.clone());
+            self.diags.push(
+                Diagnostic::error(
+                    diagnostics::RECEIVER_MISMATCH,
+                    callee,
+                    format!("`{}` is a static method of `{}`", method, owner),
+                )
+                .with_label("doesn't use `this`")
+                .with_help(format!("call it as `{}::{}(...)`", owner, method)),
+            );
+        }
+        self.check_args(&sig, args, span);
+        sig.ret
+    }
+
+    fn check_args(&mut self, sig: &FnSig, args: &[Expr], span: Span) {
+        if sig.params.len() != args.len() {
+            self.diags.push(
+                Diagnostic::error(
+                    diagnostics::WRONG_ARG_COUNT,
+                    span,
+                    format!(
+                        "expected {} argument(s), found {}",
+                        sig.params.len(),
+                        args.len()
+                    ),
+                )
+                .with_secondary(sig.span, "declared here"),
+            );
+        }
+        for (i, arg) in args.iter().enumerate() {
+            match sig.params.get(i) {
+                Some(param) => {
+                    let found = self.check_expr(arg, Some(param));
+                    self.expect_assignable(&found, param, arg.span);
+                }
+                None => {
+                    self.check_expr(arg, None);
+                }
+            }
+        }
+    }
+
+    fn check_args_unknown(&mut self, args: &[Expr]) {
+        for arg in args.iter() {
+            self.check_expr(arg, None);
+        }
+    }
+
+    // Fields are reached through one level of pointer or reference
+    fn field_type(&mut self, base: &Ty, field: &str, span: Span) -> Ty {
+        let strukt = match base {
+            Ty::Struct(s) => s,
+            Ty::Pointer(t) | Ty::Ref(t) if matches!(**t, Ty::Struct(_)) => match &**t {
+                Ty::Struct(s) => s,
+                _ => unreachable!(),
+            },
+            Ty::Error => return Ty::Error,
+            t => {
+                self.diags.push(
+                    Diagnostic::error(
+                        diagnostics::UNKNOWN_FIELD,
+                        span,
+                        format!("`{}` has no fields", t),
+                    )
+                    .with_label(format!("`{}` is not a struct", t)),
+                );
+                return Ty::Error;
+            }
+        };
+        let found = self
+            .info
+            .structs
+            .get(strukt)
+            .and_then(|fields| fields.iter().find(|(n, _)| n == field));
+        match found {
+            Some((_, ty)) => ty.clone(),
+            None => {
+                self.diags.push(
+                    Diagnostic::error(
+                        diagnostics::UNKNOWN_FIELD,
+                        span,
+                        format!("struct `{}` has no field `{}`", strukt, field),
+                    )
+                    .with_label("unknown field"),
+                );
+                Ty::Error
+            }
+        }
+    }
+
+    fn check_struct_lit(&mut self, name: &str, inits: &[FieldInit], span: Span) -> Ty {
+        let path = match self.table.lookup_type(self.ns, name) {
+            Some(sym) if matches!(sym.kind, SymbolKind::Struct) => sym.path.clone(),
+            // Reported by the resolver
+            _ => {
+                for init in inits.iter() {
+                    self.check_expr(&init.value, None);
+                }
+                return Ty::Error;
+            }
+        };
+        let fields = self.info.structs.get(&path).cloned().unwrap_or_default();
+        let mut seen: HashMap<&str, Span> = HashMap::new();
+        for init in inits.iter() {
+            match fields.iter().find(|(n, _)| *n == init.name) {
+                Some((_, ty)) => {
+                    let found = self.check_expr(&init.value, Some(ty));
+                    self.expect_assignable(&found, ty, init.value.span);
+                }
+                None => {
+                    self.check_expr(&init.value, None);
+                    self.diags.push(
+                        Diagnostic::error(
+                            diagnostics::UNKNOWN_FIELD,
+                            init.span,
+                            format!("struct `{}` has no field `{}`", path, init.name),
+                        )
+                        .with_label("unknown field"),
+                    );
+                }
+            }
+            if let Some(prev) = seen.get(init.name.as_str()) {
+                self.diags.push(
+                    Diagnostic::error(
+                        diagnostics::DUPLICATE_DEFINITION,
+                        init.span,
+                        format!("field `{}` is initialized more than once", init.name),
+                    )
+                    .with_label("initialized again here")
+                    .with_secondary(*prev, "first initialized here"),
+                );
+            } else {
+                seen.insert(&init.name, init.span);
+            }
+        }
+        let missing: Vec<&str> = fields
+            .iter()
+            .map(|(n, _)| n.as_str())
+            .filter(|n| !seen.contains_key(n))
+            .collect();
+        if !missing.is_empty() {
+            self.diags.push(
+                Diagnostic::error(
+                    diagnostics::MISSING_FIELDS,
+                    span,
+                    format!("missing field(s) in `{}` literal", path),
+                )
+                .with_label(format!("missing `{}`", missing.join("`, `"))),
+            );
+        }
+        Ty::Struct(path)
+    }
+
+    fn unary_result(&mut self, op: UnaryOp, ty: &Ty, span: Span) -> Ty {
+        let ok = match op {
+            UnaryOp::Neg => matches!(ty, Ty::Prim(p) if p.is_signed() || ty.is_float()),
+            UnaryOp::Not => ty.is_bool(),
+            UnaryOp::BitNot => ty.is_integer(),
+            UnaryOp::Deref => return self.deref(ty, span),
+        };
+        if ty.is_error() {
+            return Ty::Error;
+        }
+        if !ok {
+            self.diags.push(
+                Diagnostic::error(
+                    diagnostics::INVALID_OPERAND,
+                    span,
+                    format!("cannot apply `{}` to `{}`", op, ty),
+                )
+                .with_label(format!("`{}` operand", ty)),
+            );
+            return Ty::Error;
+        }
+        ty.clone()
+    }
+
+    fn deref(&mut self, ty: &Ty, span: Span) -> Ty {
+        match ty.pointee() {
+            Some(t) => t.clone(),
+            None if ty.is_error() => Ty::Error,
+            None => {
+                self.invalid_operand(span, "dereference", ty);
+                Ty::Error
+            }
+        }
+    }
+
+    fn binary_result(&mut self, op: BinaryOp, lt: &Ty, rt: &Ty, span: Span) -> Ty {
+        let bool_ty = Ty::Prim(Primitive::Bool);
+        if lt.is_error() || rt.is_error() {
+            return match op {
+                BinaryOp::Eq
+                | BinaryOp::NotEq
+                | BinaryOp::Lt
+                | BinaryOp::Gt
+                | BinaryOp::LtEq
+                | BinaryOp::GtEq
+                | BinaryOp::And
+                | BinaryOp::Or => bool_ty,
+                _ => Ty::Error,
+            };
+        }
+        let same = lt == rt;
+        let result = match op {
+            BinaryOp::Add | BinaryOp::Sub if matches!(lt, Ty::Pointer(_)) && rt.is_integer() => {
+                Some(lt.clone())
+            }
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div => {
+                (same && lt.is_numeric()).then(|| lt.clone())
+            }
+            BinaryOp::Mod | BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor => {
+                (same && lt.is_integer()).then(|| lt.clone())
+            }
+            BinaryOp::Shl | BinaryOp::Shr => {
+                (lt.is_integer() && rt.is_integer()).then(|| lt.clone())
+            }
+            // Pointers and references also compare against nil
+            BinaryOp::Eq | BinaryOp::NotEq => {
+                let comparable =
+                    matches!(lt, Ty::Prim(_) | Ty::Enum(_) | Ty::Pointer(_) | Ty::Ref(_));
+                let nil = (*rt == Ty::Nil && lt.is_pointer_like())
+                    || (*lt == Ty::Nil && rt.is_pointer_like());
+                ((same && comparable) || nil).then_some(bool_ty)
+            }
+            BinaryOp::Lt | BinaryOp::Gt | BinaryOp::LtEq | BinaryOp::GtEq => {
+                (same && (lt.is_numeric() || *lt == Ty::Prim(Primitive::Char))).then_some(bool_ty)
+            }
+            BinaryOp::And | BinaryOp::Or => (lt.is_bool() && rt.is_bool()).then_some(bool_ty),
+        };
+        match result {
+            Some(t) => t,
+            None => {
+                self.diags.push(
+                    Diagnostic::error(
+                        diagnostics::INVALID_OPERAND,
+                        span,
+                        format!("cannot apply `{}` to `{}` and `{}`", op, lt, rt),
+                    )
+                    .with_label("invalid operands"),
+                );
+                Ty::Error
+            }
+        }
+    }
+
+    fn expect_assignable(&mut self, found: &Ty, expected: &Ty, span: Span) {
+        if found.assignable_to(expected) {
+            return;
+        }
+        match found.coerces_to(expected) {
+            Some(c) => {
+                self.info
+                    .coercions
+                    .insert((span.start, span.end), expected.clone());
+                if c == Coercion::Lossy {
+                    self.diags.push(
+                        Diagnostic::warning(
+                            diagnostics::LOSSY_CONVERSION,
+                            span,
+                            format!(
+                                "implicit conversion from `{}` to `{}` may lose precision",
+                                found, expected
+                            ),
+                        )
+                        .with_label(format!("converted to `{}`", expected))
+                        .with_help(format!("cast it with `[{}]` if this is intended", expected)),
+                    );
+                }
+            }
+            None if found.is_numeric() && expected.is_numeric() => self.diags.push(
+                Diagnostic::error(diagnostics::MISMATCHED_TYPES, span, "mismatched types")
+                    .with_label(format!("expected `{}`, found `{}`", expected, found))
+                    .with_help(format!(
+                        "`{}` doesn't convert to `{}` implicitly, cast it with `[{}]`",
+                        found, expected, expected
+                    )),
+            ),
+            None => self.mismatch(span, expected, found),
+        }
+    }
+
+    fn mismatch(&mut self, span: Span, expected: &Ty, found: &Ty) {
+        self.diags.push(
+            Diagnostic::error(diagnostics::MISMATCHED_TYPES, span, "mismatched types")
+                .with_label(format!("expected `{}`, found `{}`", expected, found)),
+        );
+    }
+
+    fn invalid_operand(&mut self, span: Span, what: &str, ty: &Ty) {
+        self.diags.push(
+            Diagnostic::error(
+                diagnostics::INVALID_OPERAND,
+                span,
+                format!("invalid {} of type `{}`", what, ty),
+            )
+            .with_label(format!("`{}` here", ty)),
+        );
+    }
+
+    fn not_a_value(&mut self, desc: &str, name: &str, span: Span) {
+        self.diags.push(
+            Diagnostic::error(
+                diagnostics::NOT_A_VALUE,
+                span,
+                format!("expected a value, found {} `{}`", desc, name),
+            )
+            .with_help(format!("call it with `{}(...)`", name)),
+        );
+    }
+}
+
+pub(super) fn is_literal(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Int(_) | ExprKind::Float(_) => true,
+        ExprKind::Unary {
+            op: UnaryOp::Neg,
+            expr,
+        } => is_literal(expr),
+        _ => false,
+    }
+}
+
+// Methods that never mention `this` are static
+fn uses_this(body: &Block) -> bool {
+    let mut found = false;
+    walk_block(body, &mut |_| {}, &mut |e| {
+        found |= matches!(&e.kind, ExprKind::Ident(n) if n == "this");
+    });
+    found
+}
+
+impl Checker<'_> {
+    // Casts to enums and chars are checked: a literal cast to an enum has to
+    // be one of its discriminants, one cast to `char` a valid code point, and
+    // an enum can only be cast to an integer type that holds all of them
+    fn check_cast_range(&mut self, expr: &Expr, inner: &Expr, from: &Ty, to: &Ty) {
+        match (from, to) {
+            (_, Ty::Enum(_) | Ty::Prim(Primitive::Char)) if is_literal(inner) => {
+                self.eval_const(expr, None);
+            }
+            (Ty::Enum(path), Ty::Prim(p)) => {
+                let Some(info) = self.eval.enum_info(path) else {
+                    return;
+                };
+                self.diags.append(&mut self.eval.diags);
+                let wide: Vec<String> = info
+                    .variants
+                    .iter()
+                    .filter(|(_, n)| !int_fits(*n, *p))
+                    .map(|(name, n)| format!("`{}` = {}", name, n))
+                    .collect();
+                if !wide.is_empty() {
+                    self.diags.push(
+                        Diagnostic::error(
+                            diagnostics::INVALID_CAST,
+                            expr.span,
+                            format!("cannot cast `{}` to `{}`", from, to),
+                        )
+                        .with_label(format!("not every variant fits in `{}`", p))
+                        .with_note(format!("doesn't fit: {}", wide.join(", "))),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Explicit `[type] expr` conversions: between any numeric types, chars and
+// integers, bools to integers, enums and integers, and between pointers or a
+// pointer and its `u64` address
+fn castable(from: &Ty, to: &Ty) -> bool {
+    let char_ty = Ty::Prim(Primitive::Char);
+    let address = Ty::Prim(Primitive::U64);
+    match (from, to) {
+        (Ty::Error, _) | (_, Ty::Error) => true,
+        (a, b) if a == b => true,
+        (a, b) if a.is_numeric() && b.is_numeric() => true,
+        (a, b) if *a == char_ty || *b == char_ty => a.is_integer() || b.is_integer(),
+        (a, b) if a.is_bool() => b.is_integer(),
+        (Ty::Enum(_), b) | (b, Ty::Enum(_)) => b.is_integer(),
+        (Ty::Pointer(_) | Ty::Nil, Ty::Pointer(_)) => true,
+        (Ty::Pointer(_), b) | (b, Ty::Pointer(_)) => *b == address,
+        _ => false,
+    }
+}
@@ -0,0 +1,85 @@
+// Helpers for the tests that run the dvmc binary and the VM
+#![allow(dead_code)]
+
+use dunevm::vm::bytecode::ValueType;
+use dunevm::vm::cpu::Vm;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Every program is checked without -O, with all of it, and with each pass
+// left out in turn
+pub const CONFIGS: &[&[&str]] = &[
+    &[],
+    &["-O"],
+    &["-O", "--skip=fold"],
+    &["-O", "--skip=copy-prop"],
+    &["-O", "--skip=dce"],
+    &["-O", "--skip=jump-threading"],
+    &["-O", "--skip=inline"],
+    &["-O", "--skip=tail-calls"],
+];
+
+pub fn path(dir: &str, file: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join(dir)
+        .join(file)
+}
+
+fn dvmc(src: &Path, args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_dvmc"))
+        .arg(src)
+        .args(args)
+        .output()
+        .expect("couldn't run dvmc")
+}
+
+// The image dvmc writes for `src`, or what it printed to stderr
+pub fn compile(src: &Path, args: &[&str]) -> Result<Vec<u8>, String> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let out = std::env::temp_dir().join(format!(
+        "dvmc-test-{}-{}.dvm",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut all = args.to_vec();
+    all.extend(["-o", out.to_str().unwrap()]);
+    let output = dvmc(src, &all);
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).into_owned());
+    }
+    let image = std::fs::read(&out).expect("dvmc wrote no image");
+    let _ = std::fs::remove_file(&out);
+    Ok(image)
+}
+
+// What `main` returned, printed like `vm` does, or why the VM stopped
+pub fn run(image: Vec<u8>) -> Result<String, String> {
+    let mut vm = Vm::new(image).map_err(|e| format!("invalid bytecode: {}", e))?;
+    vm.start().map_err(|trap| trap.to_string())?;
+    let ret = vm.fn_table[vm.entry].ret_type;
+    let bits = vm.exit.expect("main didn't return").bits;
+    Ok(match ret {
+        Some(ValueType::I32) => (bits as i32).to_string(),
+        Some(ValueType::I64) => (bits as i64).to_string(),
+        Some(ValueType::F32) => f32::from_bits(bits as u32).to_string(),
+        Some(ValueType::F64) => f64::from_bits(bits).to_string(),
+        Some(ValueType::Ptr) => format!("{:#x}", bits),
+        None => String::new(),
+    })
+}
+
+// `--emit=ir` output for `src`
+pub fn emit_ir(src: &Path, args: &[&str]) -> String {
+    let mut all = vec!["--emit=ir"];
+    all.extend(args);
+    let output = dvmc(src, &all);
+    assert!(
+        output.status.success(),
+        "dvmc failed on {}: {}",
+        src.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
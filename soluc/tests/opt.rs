@@ -0,0 +1,70 @@
+// Checks what each optimizer pass does on its own. tests/opt/<pass>.sl is
+// compiled to IR without -O and with only that pass, and both are compared
+// with <pass>.before.ir and <pass>.after.ir. Run with UPDATE_GOLDEN=1 to
+// write the files again after a change to the IR or a pass.
+mod common;
+
+use common::*;
+
+const PASSES: [&str; 6] = [
+    "fold",
+    "copy-prop",
+    "dce",
+    "jump-threading",
+    "inline",
+    "tail-calls",
+];
+
+fn golden(file: &str, got: &str) {
+    let path = path("opt", file);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, got).unwrap();
+        return;
+    }
+    let want = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("couldn't read {}: {}", path.display(), e));
+    assert_eq!(got, want, "{} changed", file);
+}
+
+fn check(pass: &str) {
+    let src = path("opt", &format!("{}.sl", pass));
+    let others: Vec<&str> = PASSES.into_iter().filter(|p| *p != pass).collect();
+    let skip = format!("--skip={}", others.join(","));
+    let only = ["-O", skip.as_str()];
+    golden(&format!("{}.before.ir", pass), &emit_ir(&src, &[]));
+    golden(&format!("{}.after.ir", pass), &emit_ir(&src, &only));
+
+    // The pass mustn't change what the program does
+    let result = |args: &[&str]| run(compile(&src, args).unwrap());
+    assert_eq!(result(&only), result(&[]), "{} changed the result", pass);
+}
+
+#[test]
+fn fold() {
+    check("fold");
+}
+
+#[test]
+fn copy_prop() {
+    check("copy-prop");
+}
+
+#[test]
+fn dce() {
+    check("dce");
+}
+
+#[test]
+fn jump_threading() {
+    check("jump-threading");
+}
+
+#[test]
+fn inline() {
+    check("inline");
+}
+
+#[test]
+fn tail_calls() {
+    check("tail-calls");
+}
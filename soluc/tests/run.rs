@@ -0,0 +1,133 @@
+// Compiles the programs in tests/programs and checks what they return when
+// run, without -O and under each optimizer setting in CONFIGS
+mod common;
+
+use common::*;
+
+// `want` is what main returns, or "trap: ..." when the VM should stop early
+fn check(name: &str, want: &str) {
+    let src = path("programs", &format!("{}.sl", name));
+    for args in CONFIGS {
+        let image = compile(&src, args)
+            .unwrap_or_else(|e| panic!("{} didn't compile with {:?}:\n{}", name, args, e));
+        assert_eq!(
+            run(image).unwrap_or_else(|trap| format!("trap: {}", trap)),
+            want,
+            "{} returned something else with {:?}",
+            name,
+            args
+        );
+    }
+}
+
+#[test]
+fn fib() {
+    check("fib", "6765");
+}
+
+#[test]
+fn loops() {
+    check("loops", "867111055");
+}
+
+#[test]
+fn switch() {
+    check("switch", "561");
+}
+
+#[test]
+fn namespaces() {
+    check("namespaces", "92");
+}
+
+#[test]
+fn logic() {
+    check("logic", "10110");
+}
+
+#[test]
+fn void() {
+    check("void", "5");
+}
+
+#[test]
+fn conversions() {
+    check("conversions", "1205399383");
+}
+
+#[test]
+fn wrap() {
+    check("wrap", "108");
+}
+
+#[test]
+fn mixed() {
+    check("mixed", "355");
+}
+
+#[test]
+fn division_by_zero() {
+    check("div_zero", "trap: division by zero");
+}
+
+#[test]
+fn branches() {
+    check("branches", "-7");
+}
+
+#[test]
+fn calls() {
+    check("calls", "644");
+}
+
+#[test]
+fn for_bound() {
+    check("for_bound", "306");
+}
+
+#[test]
+fn const_conditions() {
+    check("const_conditions", "173");
+}
+
+// More values live at once than any other program, across calls and a loop
+#[test]
+fn pressure() {
+    check("pressure", "153167");
+}
+
+#[test]
+fn structs() {
+    check("structs", "80612382");
+}
+
+#[test]
+fn arrays() {
+    check("arrays", "-59474972");
+}
+
+#[test]
+fn index_out_of_bounds() {
+    check("bounds", "trap: index out of bounds");
+}
+
+#[test]
+fn pointers() {
+    check("pointers", "794134");
+}
+
+#[test]
+fn nil_pointer() {
+    check("nil", "trap: nil pointer dereference");
+}
+
+// Strings need a heap, which the VM doesn't have yet
+#[test]
+fn strings_unsupported() {
+    let err = compile(&path("programs", "strings.sl"), &[]).unwrap_err();
+    assert!(
+        err.contains("error[E0902]: values of type `String`"),
+        "{}",
+        err
+    );
+}
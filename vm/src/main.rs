@@ -1,54 +1,107 @@
-mod vm;
-use std::{alloc::System, time::SystemTime};
-use vm::bytecode::{Instruction, Opcode};
-
-/*
-* 0x0000000000000000 0x0000000000000000 0x0000000000000001
-* 0x0000000000000000 0x0000000000000001 0x00000000000003E8
-* 0x0000000000000000 0x0000000000000002 0x0000000000000000
-* 0x0000000000000019 0x0000000000000000
-* 0x0000000000000019 0x0000000000000001
-* 0x0000000000000019 0x0000000000000002
-* 0x0000000000000006 0x0000000000000002 0x0000000000000002 0x0000000000000000
-* 0x000000000000000A 0x0000000000000001 0x0000000000000001 0x0000000000000000
-* 0x0000000000000019 0x0000000000000000
-* 0x0000000000000019 0x0000000000000001
-* 0x0000000000000019 0x0000000000000002
-* 0x0000000000000018 0x0000000000000001 x0000000000000006 0xFFFFFFFFFFFFFFFA
-* 0x000000000000001D
-*/
-
-use crate::vm::bytecode::Operand;
-fn main() {
-    let prog: Vec<vm::bytecode::Instruction> = vec![
-        Instruction::new(Opcode::LoadI32, &[Operand::Reg(0), Operand::Int(1)]),
-        Instruction::new(Opcode::LoadI32, &[Operand::Reg(1), Operand::Int(1_000)]),
-        Instruction::new(Opcode::LoadI32, &[Operand::Reg(2), Operand::Int(0)]),
-        Instruction::new(Opcode::Print, &[Operand::Reg(0)]),
-        Instruction::new(Opcode::Print, &[Operand::Reg(1)]),
-        Instruction::new(Opcode::Print, &[Operand::Reg(2)]),
-        Instruction::new(
-            Opcode::AddI32,
-            &[Operand::Reg(2), Operand::Reg(2), Operand::Reg(0)],
-        ),
-        Instruction::new(
-            Opcode::SubI32,
-            &[Operand::Reg(1), Operand::Reg(1), Operand::Reg(0)],
-        ),
-        Instruction::new(Opcode::Print, &[Operand::Reg(0)]),
-        Instruction::new(Opcode::Print, &[Operand::Reg(1)]),
-        Instruction::new(Opcode::Print, &[Operand::Reg(2)]),
-        Instruction::new(Opcode::Jmpnif, &[Operand::Reg(1), Operand::Int(-6)]),
-        Instruction::new(Opcode::Halt, &[]),
-    ];
-    let mut vm = vm::cpu::Vm::new(prog);
+use dunevm::vm;
+use std::process::ExitCode;
+use std::time::SystemTime;
+use vm::bytecode::{Opcode, ValueType};
+
+// Counts r1 down from 1000 to 0, adding r0 to r2 on every iteration
+#[rustfmt::skip]
+fn benchmark() -> Vec<u64> {
+    let op = |o: Opcode| o as u64;
+    vec![
+        op(Opcode::LoadI32), 0, 1,
+        op(Opcode::LoadI32), 1, 1_000,
+        op(Opcode::LoadI32), 2, 0,
+        op(Opcode::Print), 0,
+        op(Opcode::Print), 1,
+        op(Opcode::Print), 2,
+        // loop: word 15
+        op(Opcode::AddI32), 2, 2, 0,
+        op(Opcode::SubI32), 1, 1, 0,
+        op(Opcode::Print), 0,
+        op(Opcode::Print), 1,
+        op(Opcode::Print), 2,
+        // Offsets are relative to the word after the opcode
+        op(Opcode::Jmpnif), (-15i64) as u64, 1,
+        op(Opcode::Halt),
+    ]
+}
+
+// Wraps code in a DVM1 file with a single entry function
+fn image(code: &[u64], reg_count: u16) -> Vec<u8> {
+    let mut out = vec![];
+    out.extend_from_slice(&0x44564D31u32.to_le_bytes()); // Magic
+    out.extend_from_slice(&vm::cpu::BYTECODE_VERSION.to_le_bytes());
+    out.extend_from_slice(&1u32.to_le_bytes()); // Function table count
+    out.extend_from_slice(&0u32.to_le_bytes()); // Entry point index
+    out.extend_from_slice(&0u32.to_le_bytes()); // Const table count
+    out.extend_from_slice(&(code.len() as u64).to_le_bytes());
+
+    out.extend_from_slice(&0u64.to_le_bytes()); // Entry ip
+    out.extend_from_slice(&reg_count.to_le_bytes());
+    out.extend_from_slice(&0u64.to_le_bytes()); // Memory
+    out.extend_from_slice(&0u16.to_le_bytes()); // Arg count
+    out.push(0xFF); // No return value
+    out.extend_from_slice(&0u32.to_le_bytes()); // Flags
+
+    for word in code {
+        out.extend_from_slice(&word.to_le_bytes());
+    }
+    out
+}
+
+// Runs a DVM1 file and prints what its entry function returned
+fn run(path: &str) -> ExitCode {
+    let prog = match std::fs::read(path) {
+        Ok(prog) => prog,
+        Err(e) => {
+            eprintln!("could not read {}: {}", path, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut vm = match vm::cpu::Vm::new(prog) {
+        Ok(vm) => vm,
+        Err(e) => {
+            eprintln!("invalid bytecode: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(trap) = vm.start() {
+        eprintln!("error: {}", trap);
+        return ExitCode::FAILURE;
+    }
+    if let Some(kind) = vm.fn_table[vm.entry].ret_type
+        && let Some(reg) = vm.exit
+    {
+        match kind {
+            ValueType::I32 => println!("{}", reg.bits as i32),
+            ValueType::I64 => println!("{}", reg.bits as i64),
+            ValueType::F32 => println!("{}", f32::from_bits(reg.bits as u32)),
+            ValueType::F64 => println!("{}", f64::from_bits(reg.bits)),
+            ValueType::Ptr => println!("{:#x}", reg.bits),
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn main() -> ExitCode {
+    if let Some(path) = std::env::args().nth(1) {
+        return run(&path);
+    }
+
+    let mut vm = match vm::cpu::Vm::new(image(&benchmark(), 3)) {
+        Ok(vm) => vm,
+        Err(e) => {
+            eprintln!("invalid bytecode: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
     let vm_start = SystemTime::now();
     let _ = vm.start();
     let vm_end = SystemTime::now();
 
     let core_start = SystemTime::now();
     let mut x = 1_000_000;
-    for i in 0..1_000_000 {
+    for _ in 0..1_000_000 {
         x -= 1;
     }
     let core_end = SystemTime::now();
@@ -58,4 +111,5 @@ fn main() {
         "Core Time {:?}",
         core_end.duration_since(core_start).unwrap()
     );
+    ExitCode::SUCCESS
 }
@@ -34,22 +34,119 @@ pub enum Opcode {
     DivI64,
     DivF32,
     DivF64,
+    // Converts r2 -> r1. 8 and 16 bit integers live in I32 registers, the
+    // Trunc opcodes wrap r2 to their width and sign or zero extend it back.
+    // Float to integer conversions saturate, NaN becomes 0.
+    I32ToI64,
+    U32ToI64,
+    I64ToI32,
+    I32ToF32,
+    I32ToF64,
+    I64ToF32,
+    I64ToF64,
+    U64ToF32,
+    U64ToF64,
+    F32ToI32,
+    F32ToI64,
+    F64ToI32,
+    F64ToI64,
+    F32ToU64,
+    F64ToU64,
+    F32ToF64,
+    F64ToF32,
+    TruncI8,
+    TruncU8,
+    TruncI16,
+    TruncU16,
+    // Remainder r2 % r3, unsigned division and remainder
+    ModI32,
+    ModI64,
+    DivU32,
+    DivU64,
+    ModU32,
+    ModU64,
+    // Negates r2 -> r1
+    NegI32,
+    NegI64,
+    NegF32,
+    NegF64,
+    // Bitwise r2 op r3, shifts by r3 modulo the width of the register
+    And,
+    Or,
+    Xor,
+    ShlI32,
+    ShlI64,
+    ShrI32,
+    ShrI64,
+    ShrU32,
+    ShrU64,
+    // Compares r2 with r3, r1 becomes an I32 1 or 0
+    Eq,
+    EqF32,
+    EqF64,
+    LtI32,
+    LtI64,
+    LtU32,
+    LtU64,
+    LtF32,
+    LtF64,
+    LeI32,
+    LeI64,
+    LeU32,
+    LeU64,
+    LeF32,
+    LeF64,
+    // 1 if r2 is zero, 0 otherwise
+    Not,
     // Conditional Jumps
     Equal,
     GreaterThan,
     LessThan,
+    // Calls function r1, copying its arguments from r3.. into its first
+    // locals. Its local 0 is copied into r2 when it returns, unless r2 is
+    // NO_RETURN.
     Call,
-    // Move Instruction Pointer by r1
+    // Calls function r1 in place of the current one, copying its arguments
+    // from r2.. into the first locals of this frame. It returns to where
+    // the current function would have.
+    TailCall,
+    // Move Instruction Pointer by r1 words, counted from the word after
+    // the opcode
     Jmp,
-    // Move Instruction Pointer by r1 in r2 direction if r3 == 0
+    // Move Instruction Pointer by r1 if r2 == 0
     Jmpif,
-    // Move Instruction Pointer by r1 in r2 direction if r3 != 0
+    // Move Instruction Pointer by r1 if r2 != 0
     Jmpnif,
     // Print (Debugging)
     Print,
     // Pointer into Arena
     AddI32Ptr,
     AddI64Ptr,
+    // Reads the memory at r2 + imm into r1, narrow integers are sign or zero
+    // extended. Addresses are Ptr registers, reading through nil traps.
+    ReadI8,
+    ReadU8,
+    ReadI16,
+    ReadU16,
+    ReadI32,
+    ReadI64,
+    ReadF32,
+    ReadF64,
+    ReadPtr,
+    // Writes the low bytes of r2 to the memory at r1 + imm
+    Write8,
+    Write16,
+    Write32,
+    Write64,
+    // Copies imm bytes from r2 to r1
+    MemCopy,
+    // r1 = address imm in the current frame's memory
+    FrameAddr,
+    // r1 = r2 + imm
+    Offset,
+    // r1 = r2 + r3 * imm1, trapping unless r3 < imm2. An imm2 of u64::MAX
+    // leaves the index unchecked.
+    Index,
     // return value from frame
     Ret,
     // Halt the VM
@@ -58,48 +155,7 @@ pub enum Opcode {
 
 impl Into<u16> for Opcode {
     fn into(self) -> u16 {
-        match self {
-            Opcode::LoadI32 => Opcode::LoadI32 as u16,
-            Opcode::LoadI64 => Opcode::LoadI64 as u16,
-            Opcode::LoadF32 => Opcode::LoadF32 as u16,
-            Opcode::LoadF64 => Opcode::LoadF64 as u16,
-
-            Opcode::Mov => Opcode::Mov as u16,
-
-            Opcode::AddI32 => Opcode::AddI32 as u16,
-            Opcode::AddI64 => Opcode::AddI64 as u16,
-            Opcode::AddF32 => Opcode::AddF32 as u16,
-            Opcode::AddF64 => Opcode::AddF64 as u16,
-
-            Opcode::SubI32 => Opcode::SubI32 as u16,
-            Opcode::SubI64 => Opcode::SubI64 as u16,
-            Opcode::SubF32 => Opcode::SubF32 as u16,
-            Opcode::SubF64 => Opcode::SubF64 as u16,
-
-            Opcode::MulI32 => Opcode::LoadI32 as u16,
-            Opcode::MulI64 => Opcode::LoadI32 as u16,
-            Opcode::MulF32 => Opcode::LoadI32 as u16,
-            Opcode::MulF64 => Opcode::LoadI32 as u16,
-
-            Opcode::DivI32 => Opcode::LoadI32 as u16,
-            Opcode::DivI64 => Opcode::LoadI32 as u16,
-            Opcode::DivF32 => Opcode::LoadI32 as u16,
-            Opcode::DivF64 => Opcode::LoadI32 as u16,
-
-            Opcode::Equal => Opcode::Equal as u16,
-            Opcode::GreaterThan => Opcode::GreaterThan as u16,
-            Opcode::LessThan => Opcode::LessThan as u16,
-
-            Opcode::Call => Opcode::Call as u16,
-            Opcode::Jmp => Opcode::Jmp as u16,
-            Opcode::Jmpif => Opcode::Jmpif as u16,
-            Opcode::Jmpnif => Opcode::Jmpnif as u16,
-            Opcode::Print => Opcode::Print as u16,
-            Opcode::AddI32Ptr => Opcode::AddI32Ptr as u16,
-            Opcode::AddI64Ptr => Opcode::AddI64Ptr as u16,
-            Opcode::Ret => Opcode::Ret as u16,
-            Opcode::Halt => Opcode::Halt as u16,
-        }
+        self as u16
     }
 }
 
@@ -174,10 +230,15 @@ impl Instruction {
 }
 
 pub const FRAME_LOCALS_SIZE: usize = 300;
+pub const NO_RETURN: u64 = u64::MAX;
 pub struct Frame {
     pub locals: [Register; FRAME_LOCALS_SIZE],
     pub ret_ip: u64,
     pub ret_dst: Option<u64>,
+    // Where the frame's memory starts, and where the memory in use ended
+    // before the call
+    pub mem_base: u64,
+    pub mem_mark: u64,
 }
 
 impl Frame {
@@ -189,6 +250,8 @@ impl Frame {
             }; FRAME_LOCALS_SIZE],
             ret_ip: ret_point,
             ret_dst: ret_dst,
+            mem_base: 0,
+            mem_mark: 0,
         }
     }
 }
@@ -196,6 +259,7 @@ impl Frame {
 pub struct FunctionEntry {
     pub entry: u64,
     pub reg_count: u16,
+    pub memory: u64, // Bytes of memory for each call
     pub arg_count: u16,
     pub arg_types: Vec<ValueType>,
     pub ret_type: Option<ValueType>,
@@ -3,11 +3,36 @@
 use crate::vm::bytecode::FunctionEntry;
 use crate::vm::bytecode::Instruction;
 use crate::vm::bytecode::ValueType;
-use crate::vm::bytecode::{Frame, Opcode};
-use crate::vm::memory::{Heap, Stack};
+use crate::vm::bytecode::{Frame, Opcode, Register};
+use crate::vm::memory::{Heap, Memory, Stack};
 use crate::vm::op_functions::{DISPATCH_TABLE, DispatchOpcodeTable};
 
 const BYTECODE_MAGIC_VALUE: u32 = 0x44564D31;
+// Bumped whenever opcodes are renumbered or the layout changes
+pub const BYTECODE_VERSION: u32 = 3;
+
+// Why the VM stopped before the program finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trap {
+    DivisionByZero,
+    // Memory read or written through nil
+    NilPointer,
+    // Memory outside of any live frame's
+    InvalidAddress,
+    // An array index not below its length
+    IndexOutOfBounds,
+}
+
+impl std::fmt::Display for Trap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Trap::DivisionByZero => write!(f, "division by zero"),
+            Trap::NilPointer => write!(f, "nil pointer dereference"),
+            Trap::InvalidAddress => write!(f, "invalid memory access"),
+            Trap::IndexOutOfBounds => write!(f, "index out of bounds"),
+        }
+    }
+}
 
 pub struct Vm {
     pub prog: Vec<u64>,
@@ -15,10 +40,15 @@ pub struct Vm {
     pub const_table: Vec<u32>, // not Implmemented right now
     pub halted: bool,
     pub h: Heap,
+    pub mem: Memory,
     pub active_frame: usize,
     pub cs: Vec<Frame>, // Call Stack
     pub ip: u64,
     pub wc: u64,
+    pub entry: usize,
+    // Local 0 of the entry function once it has returned
+    pub exit: Option<Register>,
+    pub trap: Option<Trap>,
 }
 
 impl Vm {
@@ -33,6 +63,9 @@ impl Vm {
         cursor += 4;
 
         let version = u32::from_le_bytes(prog[cursor..cursor + 4].try_into().unwrap());
+        if version != BYTECODE_VERSION {
+            return Err(2);
+        }
         cursor += 4;
 
         let fn_table_count = u32::from_le_bytes(prog[cursor..cursor + 4].try_into().unwrap());
@@ -48,12 +81,14 @@ impl Vm {
         cursor += 8;
 
         // Function Table
-        let fn_table: Vec<FunctionEntry> = Vec::with_capacity(fn_table_count as usize);
+        let mut fn_table: Vec<FunctionEntry> = Vec::with_capacity(fn_table_count as usize);
         for i in 0..fn_table_count {
             let entry_ip = u64::from_le_bytes(prog[cursor..cursor + 8].try_into().unwrap());
             cursor += 8;
             let reg_count = u16::from_le_bytes(prog[cursor..cursor + 2].try_into().unwrap());
             cursor += 2;
+            let memory = u64::from_le_bytes(prog[cursor..cursor + 8].try_into().unwrap());
+            cursor += 8;
             let arg_count = u16::from_le_bytes(prog[cursor..cursor + 2].try_into().unwrap());
             cursor += 2;
             let mut arg_types: Vec<ValueType> = Vec::with_capacity(arg_count as usize);
@@ -62,8 +97,9 @@ impl Vm {
             }
             cursor += arg_count as usize;
 
+            // Anything past the last ValueType means no return value
             let ret_type: Option<ValueType>;
-            if prog[cursor] > 4 {
+            if prog[cursor] <= 4 {
                 ret_type = Some(prog[cursor].into());
             } else {
                 ret_type = None;
@@ -75,6 +111,7 @@ impl Vm {
             fn_table.push(FunctionEntry {
                 entry: entry_ip,
                 reg_count,
+                memory,
                 arg_count,
                 arg_types,
                 ret_type,
@@ -93,7 +130,11 @@ impl Vm {
             cursor += 8;
         }
 
-        let entry_index = fn_table[entry_index as usize].entry;
+        let entry_ip = fn_table[entry_index as usize].entry;
+        let mut mem = Memory::new();
+        let mut frame = Frame::new(u64::MAX, None);
+        frame.mem_mark = mem.data.len() as u64;
+        frame.mem_base = mem.push(fn_table[entry_index as usize].memory);
 
         Ok(Self {
             prog: code,
@@ -101,18 +142,31 @@ impl Vm {
             const_table: vec![],
             halted: false,
             h: Heap::new(1000),
-            cs: vec![Frame::new(u64::MAX, None)],
+            mem,
+            cs: vec![frame],
             active_frame: 0,
-            ip: entry_index,
+            ip: entry_ip,
             wc: word_count,
+            entry: entry_index as usize,
+            exit: None,
+            trap: None,
         })
     }
 
-    pub fn start(&mut self) -> Result<usize, ()> {
+    pub fn start(&mut self) -> Result<usize, Trap> {
         while !self.halted && self.ip < self.wc {
             self.step();
         }
-        Ok(0)
+        match self.trap {
+            Some(trap) => Err(trap),
+            None => Ok(0),
+        }
+    }
+
+    // Stops the program, `start` returns the trap
+    pub fn trap(&mut self, trap: Trap) {
+        self.trap = Some(trap);
+        self.halted = true;
     }
 
     fn step(&mut self) {
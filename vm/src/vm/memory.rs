@@ -1,4 +1,5 @@
 use crate::vm::bytecode::Register;
+use crate::vm::cpu::Trap;
 #[allow(unused)]
 /*
 #[derive(Copy, Clone, Debug)]
@@ -62,3 +63,63 @@ impl Stack {
         self.data.pop().unwrap()
     }
 }
+
+// Memory for values that don't fit a register, like structs and arrays.
+// Every call gets the bytes its function asks for on top of its caller's,
+// freed again when it returns. Addresses are offsets into `data`. The first
+// word is never handed out, so 0 can be nil.
+pub struct Memory {
+    pub data: Vec<u8>,
+}
+
+impl Memory {
+    pub fn new() -> Self {
+        Self { data: vec![0; 8] }
+    }
+
+    // Reserves `size` bytes at the next 8 byte boundary, returning their
+    // address
+    pub fn push(&mut self, size: u64) -> u64 {
+        let base = self.data.len().next_multiple_of(8);
+        self.data.resize(base + size as usize, 0);
+        base as u64
+    }
+
+    // Frees everything from `mark` on
+    pub fn truncate(&mut self, mark: u64) {
+        self.data.truncate(mark as usize);
+    }
+
+    // `len` bytes at `addr`, which must all be in use
+    fn range(&self, addr: u64, len: u64) -> Result<std::ops::Range<usize>, Trap> {
+        match addr.checked_add(len) {
+            Some(end) if addr >= 8 && end <= self.data.len() as u64 => {
+                Ok(addr as usize..end as usize)
+            }
+            _ => Err(Trap::InvalidAddress),
+        }
+    }
+
+    // Little endian value of `len` bytes, at most 8
+    pub fn read(&self, addr: u64, len: u64) -> Result<u64, Trap> {
+        let range = self.range(addr, len)?;
+        let mut bytes = [0u8; 8];
+        bytes[..len as usize].copy_from_slice(&self.data[range]);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    // Writes the low `len` bytes of `bits`
+    pub fn write(&mut self, addr: u64, len: u64, bits: u64) -> Result<(), Trap> {
+        let range = self.range(addr, len)?;
+        self.data[range].copy_from_slice(&bits.to_le_bytes()[..len as usize]);
+        Ok(())
+    }
+
+    // The two ranges may overlap
+    pub fn copy(&mut self, dst: u64, src: u64, len: u64) -> Result<(), Trap> {
+        let from = self.range(src, len)?;
+        let to = self.range(dst, len)?;
+        self.data.copy_within(from, to.start);
+        Ok(())
+    }
+}
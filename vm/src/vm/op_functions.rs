@@ -1,9 +1,7 @@
-use crate::vm::cpu::VmState;
-
 use super::bytecode::*;
-use super::cpu::Vm;
+use super::cpu::{Trap, Vm};
 
-pub type DispatchOpcodeTable = [OpFn; 33];
+pub type DispatchOpcodeTable = [OpFn; 107];
 pub const DISPATCH_TABLE: DispatchOpcodeTable = [
     load_i32,
     load_i64,
@@ -26,16 +24,90 @@ pub const DISPATCH_TABLE: DispatchOpcodeTable = [
     div_i64,
     div_f32,
     div_f64,
+    i32_to_i64,
+    u32_to_i64,
+    i64_to_i32,
+    i32_to_f32,
+    i32_to_f64,
+    i64_to_f32,
+    i64_to_f64,
+    u64_to_f32,
+    u64_to_f64,
+    f32_to_i32,
+    f32_to_i64,
+    f64_to_i32,
+    f64_to_i64,
+    f32_to_u64,
+    f64_to_u64,
+    f32_to_f64,
+    f64_to_f32,
+    trunc_i8,
+    trunc_u8,
+    trunc_i16,
+    trunc_u16,
+    mod_i32,
+    mod_i64,
+    div_u32,
+    div_u64,
+    mod_u32,
+    mod_u64,
+    neg_i32,
+    neg_i64,
+    neg_f32,
+    neg_f64,
+    and,
+    or,
+    xor,
+    shl_i32,
+    shl_i64,
+    shr_i32,
+    shr_i64,
+    shr_u32,
+    shr_u64,
+    eq,
+    eq_f32,
+    eq_f64,
+    lt_i32,
+    lt_i64,
+    lt_u32,
+    lt_u64,
+    lt_f32,
+    lt_f64,
+    le_i32,
+    le_i64,
+    le_u32,
+    le_u64,
+    le_f32,
+    le_f64,
+    not,
     equal,
     greater_than,
     less_than,
     call,
+    tail_call,
     jmp,
     jmpif,
     jmpnif,
     print,
     addi32_ptr,
     addi64_ptr,
+    read_i8,
+    read_u8,
+    read_i16,
+    read_u16,
+    read_i32,
+    read_i64,
+    read_f32,
+    read_f64,
+    read_ptr,
+    write_8,
+    write_16,
+    write_32,
+    write_64,
+    mem_copy,
+    frame_addr,
+    offset,
+    index,
     ret,
     halt,
 ];
@@ -143,9 +215,9 @@ pub fn add_f64(v: &mut Vm) {
     let a = &v.cs[v.active_frame].locals[ar];
     let b = &v.cs[v.active_frame].locals[br];
 
-    let output = f64::from_bits(a.bits as u64) + f64::from_bits(b.bits as u64);
+    let output = f64::from_bits(a.bits) + f64::from_bits(b.bits);
 
-    v.cs[v.active_frame].locals[dst] = Register::new(output.to_bits() as u64, ValueType::F64);
+    v.cs[v.active_frame].locals[dst] = Register::new(output.to_bits(), ValueType::F64);
     v.ip += 3;
 }
 
@@ -204,9 +276,9 @@ pub fn sub_f64(v: &mut Vm) {
     let a = &v.cs[v.active_frame].locals[ar];
     let b = &v.cs[v.active_frame].locals[br];
 
-    let output = f64::from_bits(a.bits as u64) - f64::from_bits(b.bits as u64);
+    let output = f64::from_bits(a.bits) - f64::from_bits(b.bits);
 
-    v.cs[v.active_frame].locals[dst] = Register::new(output.to_bits() as u64, ValueType::F64);
+    v.cs[v.active_frame].locals[dst] = Register::new(output.to_bits(), ValueType::F64);
     v.ip += 3;
 }
 
@@ -265,9 +337,9 @@ pub fn mul_f64(v: &mut Vm) {
     let a = &v.cs[v.active_frame].locals[ar];
     let b = &v.cs[v.active_frame].locals[br];
 
-    let output = f64::from_bits(a.bits as u64) * f64::from_bits(b.bits as u64);
+    let output = f64::from_bits(a.bits) * f64::from_bits(b.bits);
 
-    v.cs[v.active_frame].locals[dst] = Register::new(output.to_bits() as u64, ValueType::F64);
+    v.cs[v.active_frame].locals[dst] = Register::new(output.to_bits(), ValueType::F64);
     v.ip += 3;
 }
 
@@ -282,10 +354,13 @@ pub fn div_i32(v: &mut Vm) {
     let a = &v.cs[v.active_frame].locals[ar];
     let b = &v.cs[v.active_frame].locals[br];
 
-    v.cs[v.active_frame].locals[dst] = Register::new(
-        (a.bits as i32).wrapping_div(b.bits as i32) as u64,
-        ValueType::I32,
-    );
+    if b.bits as i32 == 0 {
+        v.trap(Trap::DivisionByZero);
+        return;
+    }
+    // MIN / -1 wraps like the other arithmetic instead of trapping
+    let out = (a.bits as i32).wrapping_div(b.bits as i32);
+    v.cs[v.active_frame].locals[dst] = Register::new(out as u64, ValueType::I32);
     v.ip += 3;
 }
 
@@ -297,10 +372,13 @@ pub fn div_i64(v: &mut Vm) {
     let a = &v.cs[v.active_frame].locals[ar];
     let b = &v.cs[v.active_frame].locals[br];
 
-    v.cs[v.active_frame].locals[dst] = Register::new(
-        (a.bits as i64).wrapping_div(b.bits as i64) as u64,
-        ValueType::I64,
-    );
+    if b.bits as i64 == 0 {
+        v.trap(Trap::DivisionByZero);
+        return;
+    }
+    // MIN / -1 wraps like the other arithmetic instead of trapping
+    let out = (a.bits as i64).wrapping_div(b.bits as i64);
+    v.cs[v.active_frame].locals[dst] = Register::new(out as u64, ValueType::I64);
     v.ip += 3;
 }
 
@@ -326,131 +404,1047 @@ pub fn div_f64(v: &mut Vm) {
     let a = &v.cs[v.active_frame].locals[ar];
     let b = &v.cs[v.active_frame].locals[br];
 
-    let output = f64::from_bits(a.bits as u64) / f64::from_bits(b.bits as u64);
+    let output = f64::from_bits(a.bits) / f64::from_bits(b.bits);
 
-    v.cs[v.active_frame].locals[dst] = Register::new(output.to_bits() as u64, ValueType::F64);
+    v.cs[v.active_frame].locals[dst] = Register::new(output.to_bits(), ValueType::F64);
     v.ip += 3;
 }
 
-/////////////////////////
-// Conditional Opcodes //
-/////////////////////////
+////////////////////////
+// Conversion OpCodes //
+////////////////////////
+#[inline(always)]
+pub fn i32_to_i64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = bits as i32 as i64;
+    v.cs[v.active_frame].locals[dst] = Register::new(out as u64, ValueType::I64);
+    v.ip += 2;
+}
 
 #[inline(always)]
-pub fn equal(v: &mut Vm) {
-    let jump_to = v.prog[v.ip as usize];
-    let ra = v.prog[(v.ip + 1) as usize] as usize;
-    let rb = v.prog[(v.ip + 2) as usize] as usize;
-    let a = &v.cs[v.active_frame].locals[ra];
-    let b = &v.cs[v.active_frame].locals[rb];
-    if a.bits == b.bits {
-        v.ip = (v.ip as i64 + jump_to as i64) as u64;
-    } else {
-        v.ip += 3;
-    }
+pub fn u32_to_i64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = bits as u32 as i64;
+    v.cs[v.active_frame].locals[dst] = Register::new(out as u64, ValueType::I64);
+    v.ip += 2;
 }
 
 #[inline(always)]
-pub fn greater_than(v: &mut Vm) {
-    let jump_to = v.prog[v.ip as usize];
-    let ra = v.prog[(v.ip + 1) as usize] as usize;
-    let rb = v.prog[(v.ip + 2) as usize] as usize;
-    let a = &v.cs[v.active_frame].locals[ra];
-    let b = &v.cs[v.active_frame].locals[rb];
-    if a.bits >= b.bits {
-        v.ip = (v.ip as i64 + jump_to as i64) as u64;
-    } else {
-        v.ip += 3;
-    }
+pub fn i64_to_i32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = bits as i64 as i32;
+    v.cs[v.active_frame].locals[dst] = Register::new(out as u64, ValueType::I32);
+    v.ip += 2;
 }
 
 #[inline(always)]
-pub fn less_than(v: &mut Vm) {
-    let jump_to = v.prog[v.ip as usize];
-    let ra = v.prog[(v.ip + 1) as usize] as usize;
-    let rb = v.prog[(v.ip + 2) as usize] as usize;
-    let a = &v.cs[v.active_frame].locals[ra];
-    let b = &v.cs[v.active_frame].locals[rb];
-    if a.bits <= b.bits {
-        v.ip = (v.ip as i64 + jump_to as i64) as u64;
-    } else {
-        v.ip += 3;
-    }
+pub fn i32_to_f32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = bits as i32 as f32;
+    v.cs[v.active_frame].locals[dst] = Register::new(out.to_bits() as u64, ValueType::F32);
+    v.ip += 2;
 }
 
-/////////////////////
-// Control OpCodes //
-/////////////////////
+#[inline(always)]
+pub fn i32_to_f64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = bits as i32 as f64;
+    v.cs[v.active_frame].locals[dst] = Register::new(out.to_bits(), ValueType::F64);
+    v.ip += 2;
+}
 
 #[inline(always)]
-pub fn call(v: &mut Vm) {
-    let target_fn = v.prog[v.ip as usize] as usize;
-    let ret_dst = v.prog[v.ip as usize];
-    let fn_def = &v.fn_table[target_fn];
-    let frame = Frame::new(v.ip + 2, Some(ret_dst));
-    v.active_frame += 1;
-    v.ip = v.fn_table[target_fn].entry;
+pub fn i64_to_f32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = bits as i64 as f32;
+    v.cs[v.active_frame].locals[dst] = Register::new(out.to_bits() as u64, ValueType::F32);
+    v.ip += 2;
 }
 
 #[inline(always)]
-pub fn jmp(v: &mut Vm) {
-    let offset = v.prog[v.ip as usize];
-    v.ip = (v.ip as isize + offset as isize) as u64;
+pub fn i64_to_f64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = bits as i64 as f64;
+    v.cs[v.active_frame].locals[dst] = Register::new(out.to_bits(), ValueType::F64);
+    v.ip += 2;
 }
 
 #[inline(always)]
-pub fn jmpif(v: &mut Vm) {
-    let offset = v.prog[v.ip as usize] as isize;
-    let peek = v.prog[(v.ip + 1) as usize] as usize;
-    if v.cs[v.active_frame].locals[peek].zero() {
-        v.ip = (v.ip as isize + offset) as u64;
-    } else {
-        v.ip += 2;
+pub fn u64_to_f32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = bits as f32;
+    v.cs[v.active_frame].locals[dst] = Register::new(out.to_bits() as u64, ValueType::F32);
+    v.ip += 2;
+}
+
+#[inline(always)]
+pub fn u64_to_f64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = bits as f64;
+    v.cs[v.active_frame].locals[dst] = Register::new(out.to_bits(), ValueType::F64);
+    v.ip += 2;
+}
+
+#[inline(always)]
+pub fn f32_to_i32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = f32::from_bits(bits as u32) as i32;
+    v.cs[v.active_frame].locals[dst] = Register::new(out as u64, ValueType::I32);
+    v.ip += 2;
+}
+
+#[inline(always)]
+pub fn f32_to_i64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = f32::from_bits(bits as u32) as i64;
+    v.cs[v.active_frame].locals[dst] = Register::new(out as u64, ValueType::I64);
+    v.ip += 2;
+}
+
+#[inline(always)]
+pub fn f64_to_i32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = f64::from_bits(bits) as i32;
+    v.cs[v.active_frame].locals[dst] = Register::new(out as u64, ValueType::I32);
+    v.ip += 2;
+}
+
+#[inline(always)]
+pub fn f64_to_i64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = f64::from_bits(bits) as i64;
+    v.cs[v.active_frame].locals[dst] = Register::new(out as u64, ValueType::I64);
+    v.ip += 2;
+}
+
+#[inline(always)]
+pub fn f32_to_u64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = f32::from_bits(bits as u32) as u64;
+    v.cs[v.active_frame].locals[dst] = Register::new(out, ValueType::I64);
+    v.ip += 2;
+}
+
+#[inline(always)]
+pub fn f64_to_u64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = f64::from_bits(bits) as u64;
+    v.cs[v.active_frame].locals[dst] = Register::new(out, ValueType::I64);
+    v.ip += 2;
+}
+
+#[inline(always)]
+pub fn f32_to_f64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = f32::from_bits(bits as u32) as f64;
+    v.cs[v.active_frame].locals[dst] = Register::new(out.to_bits(), ValueType::F64);
+    v.ip += 2;
+}
+
+#[inline(always)]
+pub fn f64_to_f32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = f64::from_bits(bits) as f32;
+    v.cs[v.active_frame].locals[dst] = Register::new(out.to_bits() as u64, ValueType::F32);
+    v.ip += 2;
+}
+
+#[inline(always)]
+pub fn trunc_i8(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = bits as i8 as i32;
+    v.cs[v.active_frame].locals[dst] = Register::new(out as u64, ValueType::I32);
+    v.ip += 2;
+}
+
+#[inline(always)]
+pub fn trunc_u8(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = bits as u8 as i32;
+    v.cs[v.active_frame].locals[dst] = Register::new(out as u64, ValueType::I32);
+    v.ip += 2;
+}
+
+#[inline(always)]
+pub fn trunc_i16(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = bits as i16 as i32;
+    v.cs[v.active_frame].locals[dst] = Register::new(out as u64, ValueType::I32);
+    v.ip += 2;
+}
+
+#[inline(always)]
+pub fn trunc_u16(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = bits as u16 as i32;
+    v.cs[v.active_frame].locals[dst] = Register::new(out as u64, ValueType::I32);
+    v.ip += 2;
+}
+
+/////////////////
+// Mod OpCodes //
+/////////////////
+#[inline(always)]
+pub fn mod_i32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    if b.bits as i32 == 0 {
+        v.trap(Trap::DivisionByZero);
+        return;
     }
+    // MIN / -1 wraps like the other arithmetic instead of trapping
+    let out = (a.bits as i32).wrapping_rem(b.bits as i32);
+    v.cs[v.active_frame].locals[dst] = Register::new(out as u64, ValueType::I32);
+    v.ip += 3;
 }
 
 #[inline(always)]
-pub fn jmpnif(v: &mut Vm) {
-    let offset = v.prog[v.ip as usize] as isize;
-    let peek = v.prog[(v.ip + 1) as usize] as usize;
-    if !v.cs[v.active_frame].locals[peek].zero() {
-        v.ip = (v.ip as isize + offset) as u64;
-    } else {
-        v.ip += 2;
+pub fn mod_i64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    if b.bits as i64 == 0 {
+        v.trap(Trap::DivisionByZero);
+        return;
     }
+    // MIN / -1 wraps like the other arithmetic instead of trapping
+    let out = (a.bits as i64).wrapping_rem(b.bits as i64);
+    v.cs[v.active_frame].locals[dst] = Register::new(out as u64, ValueType::I64);
+    v.ip += 3;
 }
 
+// Unsigned 32 bit results are stored sign extended like every other I32
+// value
 #[inline(always)]
-pub fn print(v: &mut Vm) {
-    let src = v.prog[v.ip as usize] as usize;
-    let reg = &v.cs[v.active_frame].locals[src];
-    println!("Reg {}: Val: {}, Type: {:?}", src, reg.bits, reg.kind);
-    v.ip += 1;
+pub fn div_u32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    let Some(out) = (a.bits as u32).checked_div(b.bits as u32) else {
+        v.trap(Trap::DivisionByZero);
+        return;
+    };
+    v.cs[v.active_frame].locals[dst] = Register::new(out as i32 as u64, ValueType::I32);
+    v.ip += 3;
 }
 
 #[inline(always)]
-pub fn addi32_ptr(v: &mut Vm) {
-    let src = v.prog[v.ip as usize] as usize;
-    let d = v.prog[(v.ip + 1) as usize] as u32;
-    let ws = v.prog[(v.ip + 2) as usize] as u32;
-    let reg = &mut v.cs[v.active_frame].locals[src];
-    reg.bits = ((reg.bits as u32) + (d * ws)) as u64;
+pub fn div_u64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    let Some(out) = a.bits.checked_div(b.bits) else {
+        v.trap(Trap::DivisionByZero);
+        return;
+    };
+    v.cs[v.active_frame].locals[dst] = Register::new(out, ValueType::I64);
     v.ip += 3;
 }
 
 #[inline(always)]
-pub fn addi64_ptr(v: &mut Vm) {
-    let src = v.prog[v.ip as usize] as usize;
-    let d = v.prog[(v.ip + 1) as usize] as u64;
-    let ws = v.prog[(v.ip + 2) as usize] as u64;
-    let reg = &mut v.cs[v.active_frame].locals[src];
-    reg.bits = reg.bits + d * ws;
+pub fn mod_u32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    let Some(out) = (a.bits as u32).checked_rem(b.bits as u32) else {
+        v.trap(Trap::DivisionByZero);
+        return;
+    };
+    v.cs[v.active_frame].locals[dst] = Register::new(out as i32 as u64, ValueType::I32);
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn mod_u64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    let Some(out) = a.bits.checked_rem(b.bits) else {
+        v.trap(Trap::DivisionByZero);
+        return;
+    };
+    v.cs[v.active_frame].locals[dst] = Register::new(out, ValueType::I64);
     v.ip += 3;
 }
 
+/////////////////
+// Neg OpCodes //
+/////////////////
+#[inline(always)]
+pub fn neg_i32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = (bits as i32).wrapping_neg() as u64;
+    v.cs[v.active_frame].locals[dst] = Register::new(out, ValueType::I32);
+    v.ip += 2;
+}
+
+#[inline(always)]
+pub fn neg_i64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = (bits as i64).wrapping_neg() as u64;
+    v.cs[v.active_frame].locals[dst] = Register::new(out, ValueType::I64);
+    v.ip += 2;
+}
+
+#[inline(always)]
+pub fn neg_f32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = (-f32::from_bits(bits as u32)).to_bits() as u64;
+    v.cs[v.active_frame].locals[dst] = Register::new(out, ValueType::F32);
+    v.ip += 2;
+}
+
+#[inline(always)]
+pub fn neg_f64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = (-f64::from_bits(bits)).to_bits();
+    v.cs[v.active_frame].locals[dst] = Register::new(out, ValueType::F64);
+    v.ip += 2;
+}
+
+/////////////////////
+// Bitwise OpCodes //
+/////////////////////
+#[inline(always)]
+pub fn and(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] = Register::new(a.bits & b.bits, a.kind);
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn or(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] = Register::new(a.bits | b.bits, a.kind);
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn xor(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] = Register::new(a.bits ^ b.bits, a.kind);
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn shl_i32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] = Register::new(
+        (a.bits as i32).wrapping_shl(b.bits as u32) as u64,
+        ValueType::I32,
+    );
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn shl_i64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] = Register::new(
+        (a.bits as i64).wrapping_shl(b.bits as u32) as u64,
+        ValueType::I64,
+    );
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn shr_i32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] = Register::new(
+        (a.bits as i32).wrapping_shr(b.bits as u32) as u64,
+        ValueType::I32,
+    );
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn shr_i64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] = Register::new(
+        (a.bits as i64).wrapping_shr(b.bits as u32) as u64,
+        ValueType::I64,
+    );
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn shr_u32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] = Register::new(
+        (a.bits as u32).wrapping_shr(b.bits as u32) as i32 as u64,
+        ValueType::I32,
+    );
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn shr_u64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] =
+        Register::new(a.bits.wrapping_shr(b.bits as u32), ValueType::I64);
+    v.ip += 3;
+}
+
+/////////////////////
+// Compare OpCodes //
+/////////////////////
+#[inline(always)]
+pub fn eq(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] = Register::new((a.bits == b.bits) as u64, ValueType::I32);
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn eq_f32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] = Register::new(
+        (f32::from_bits(a.bits as u32) == f32::from_bits(b.bits as u32)) as u64,
+        ValueType::I32,
+    );
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn eq_f64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] = Register::new(
+        (f64::from_bits(a.bits) == f64::from_bits(b.bits)) as u64,
+        ValueType::I32,
+    );
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn lt_i32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] =
+        Register::new(((a.bits as i32) < (b.bits as i32)) as u64, ValueType::I32);
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn lt_i64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] =
+        Register::new(((a.bits as i64) < (b.bits as i64)) as u64, ValueType::I32);
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn lt_u32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] =
+        Register::new(((a.bits as u32) < (b.bits as u32)) as u64, ValueType::I32);
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn lt_u64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] = Register::new((a.bits < b.bits) as u64, ValueType::I32);
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn lt_f32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] = Register::new(
+        (f32::from_bits(a.bits as u32) < f32::from_bits(b.bits as u32)) as u64,
+        ValueType::I32,
+    );
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn lt_f64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] = Register::new(
+        (f64::from_bits(a.bits) < f64::from_bits(b.bits)) as u64,
+        ValueType::I32,
+    );
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn le_i32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] =
+        Register::new(((a.bits as i32) <= (b.bits as i32)) as u64, ValueType::I32);
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn le_i64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] =
+        Register::new(((a.bits as i64) <= (b.bits as i64)) as u64, ValueType::I32);
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn le_u32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] =
+        Register::new(((a.bits as u32) <= (b.bits as u32)) as u64, ValueType::I32);
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn le_u64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] = Register::new((a.bits <= b.bits) as u64, ValueType::I32);
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn le_f32(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] = Register::new(
+        (f32::from_bits(a.bits as u32) <= f32::from_bits(b.bits as u32)) as u64,
+        ValueType::I32,
+    );
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn le_f64(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let ar = v.prog[(v.ip + 1) as usize] as usize;
+    let br = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ar];
+    let b = &v.cs[v.active_frame].locals[br];
+
+    v.cs[v.active_frame].locals[dst] = Register::new(
+        (f64::from_bits(a.bits) <= f64::from_bits(b.bits)) as u64,
+        ValueType::I32,
+    );
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn not(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    let out = (bits == 0) as u64;
+    v.cs[v.active_frame].locals[dst] = Register::new(out, ValueType::I32);
+    v.ip += 2;
+}
+
+/////////////////////////
+// Conditional Opcodes //
+/////////////////////////
+
+#[inline(always)]
+pub fn equal(v: &mut Vm) {
+    let jump_to = v.prog[v.ip as usize];
+    let ra = v.prog[(v.ip + 1) as usize] as usize;
+    let rb = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ra];
+    let b = &v.cs[v.active_frame].locals[rb];
+    if a.bits == b.bits {
+        v.ip = (v.ip as i64 + jump_to as i64) as u64;
+    } else {
+        v.ip += 3;
+    }
+}
+
+#[inline(always)]
+pub fn greater_than(v: &mut Vm) {
+    let jump_to = v.prog[v.ip as usize];
+    let ra = v.prog[(v.ip + 1) as usize] as usize;
+    let rb = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ra];
+    let b = &v.cs[v.active_frame].locals[rb];
+    if a.bits >= b.bits {
+        v.ip = (v.ip as i64 + jump_to as i64) as u64;
+    } else {
+        v.ip += 3;
+    }
+}
+
+#[inline(always)]
+pub fn less_than(v: &mut Vm) {
+    let jump_to = v.prog[v.ip as usize];
+    let ra = v.prog[(v.ip + 1) as usize] as usize;
+    let rb = v.prog[(v.ip + 2) as usize] as usize;
+    let a = &v.cs[v.active_frame].locals[ra];
+    let b = &v.cs[v.active_frame].locals[rb];
+    if a.bits <= b.bits {
+        v.ip = (v.ip as i64 + jump_to as i64) as u64;
+    } else {
+        v.ip += 3;
+    }
+}
+
+/////////////////////
+// Control OpCodes //
+/////////////////////
+
+#[inline(always)]
+pub fn call(v: &mut Vm) {
+    let target_fn = v.prog[v.ip as usize] as usize;
+    let ret_dst = v.prog[(v.ip + 1) as usize];
+    let args = v.prog[(v.ip + 2) as usize] as usize;
+    let fn_def = &v.fn_table[target_fn];
+
+    let ret_dst = (ret_dst != NO_RETURN).then_some(ret_dst);
+    let mut frame = Frame::new(v.ip + 3, ret_dst);
+    frame.mem_mark = v.mem.data.len() as u64;
+    frame.mem_base = v.mem.push(fn_def.memory);
+    let argc = fn_def.arg_count as usize;
+    frame.locals[..argc].copy_from_slice(&v.cs[v.active_frame].locals[args..args + argc]);
+
+    v.ip = fn_def.entry;
+    v.cs.push(frame);
+    v.active_frame += 1;
+}
+
+#[inline(always)]
+pub fn tail_call(v: &mut Vm) {
+    let target_fn = v.prog[v.ip as usize] as usize;
+    let args = v.prog[(v.ip + 1) as usize] as usize;
+    let fn_def = &v.fn_table[target_fn];
+
+    let argc = fn_def.arg_count as usize;
+    let frame = &mut v.cs[v.active_frame];
+    frame.locals.copy_within(args..args + argc, 0);
+    // dvmc only makes tail calls from functions without memory, so
+    // nothing the arguments point to is freed
+    v.mem.truncate(frame.mem_mark);
+    frame.mem_base = v.mem.push(fn_def.memory);
+    v.ip = fn_def.entry;
+}
+
+#[inline(always)]
+pub fn jmp(v: &mut Vm) {
+    let offset = v.prog[v.ip as usize];
+    v.ip = (v.ip as isize + offset as isize) as u64;
+}
+
+#[inline(always)]
+pub fn jmpif(v: &mut Vm) {
+    let offset = v.prog[v.ip as usize] as isize;
+    let peek = v.prog[(v.ip + 1) as usize] as usize;
+    if v.cs[v.active_frame].locals[peek].zero() {
+        v.ip = (v.ip as isize + offset) as u64;
+    } else {
+        v.ip += 2;
+    }
+}
+
+#[inline(always)]
+pub fn jmpnif(v: &mut Vm) {
+    let offset = v.prog[v.ip as usize] as isize;
+    let peek = v.prog[(v.ip + 1) as usize] as usize;
+    if !v.cs[v.active_frame].locals[peek].zero() {
+        v.ip = (v.ip as isize + offset) as u64;
+    } else {
+        v.ip += 2;
+    }
+}
+
+#[inline(always)]
+pub fn print(v: &mut Vm) {
+    let src = v.prog[v.ip as usize] as usize;
+    let reg = &v.cs[v.active_frame].locals[src];
+    println!("Reg {}: Val: {}, Type: {:?}", src, reg.bits, reg.kind);
+    v.ip += 1;
+}
+
+#[inline(always)]
+pub fn addi32_ptr(v: &mut Vm) {
+    let src = v.prog[v.ip as usize] as usize;
+    let d = v.prog[(v.ip + 1) as usize] as u32;
+    let ws = v.prog[(v.ip + 2) as usize] as u32;
+    let reg = &mut v.cs[v.active_frame].locals[src];
+    reg.bits = ((reg.bits as u32) + (d * ws)) as u64;
+    v.ip += 3;
+}
+
+#[inline(always)]
+pub fn addi64_ptr(v: &mut Vm) {
+    let src = v.prog[v.ip as usize] as usize;
+    let d = v.prog[(v.ip + 1) as usize];
+    let ws = v.prog[(v.ip + 2) as usize];
+    let reg = &mut v.cs[v.active_frame].locals[src];
+    reg.bits = reg.bits + d * ws;
+    v.ip += 3;
+}
+
+////////////////////
+// Memory OpCodes //
+////////////////////
+
+// Address r + imm of a memory instruction, None after trapping on nil
+#[inline(always)]
+fn address(v: &mut Vm, r: usize, imm: u64) -> Option<u64> {
+    let base = v.cs[v.active_frame].locals[r].bits;
+    if base == 0 {
+        v.trap(Trap::NilPointer);
+        return None;
+    }
+    Some(base.wrapping_add(imm))
+}
+
+// Decodes `Read* r1 r2 imm` and reads `len` bytes, giving r1 and the bits
+#[inline(always)]
+fn read(v: &mut Vm, len: u64) -> Option<(usize, u64)> {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let off = v.prog[(v.ip + 2) as usize];
+    let addr = address(v, src, off)?;
+    match v.mem.read(addr, len) {
+        Ok(bits) => {
+            v.ip += 3;
+            Some((dst, bits))
+        }
+        Err(trap) => {
+            v.trap(trap);
+            None
+        }
+    }
+}
+
+#[inline(always)]
+pub fn read_i8(v: &mut Vm) {
+    if let Some((dst, bits)) = read(v, 1) {
+        v.cs[v.active_frame].locals[dst] = Register::new(bits as i8 as i32 as u64, ValueType::I32);
+    }
+}
+
+#[inline(always)]
+pub fn read_u8(v: &mut Vm) {
+    if let Some((dst, bits)) = read(v, 1) {
+        v.cs[v.active_frame].locals[dst] = Register::new(bits, ValueType::I32);
+    }
+}
+
+#[inline(always)]
+pub fn read_i16(v: &mut Vm) {
+    if let Some((dst, bits)) = read(v, 2) {
+        v.cs[v.active_frame].locals[dst] = Register::new(bits as i16 as i32 as u64, ValueType::I32);
+    }
+}
+
+#[inline(always)]
+pub fn read_u16(v: &mut Vm) {
+    if let Some((dst, bits)) = read(v, 2) {
+        v.cs[v.active_frame].locals[dst] = Register::new(bits, ValueType::I32);
+    }
+}
+
+#[inline(always)]
+pub fn read_i32(v: &mut Vm) {
+    if let Some((dst, bits)) = read(v, 4) {
+        v.cs[v.active_frame].locals[dst] = Register::new(bits as i32 as u64, ValueType::I32);
+    }
+}
+
+#[inline(always)]
+pub fn read_i64(v: &mut Vm) {
+    if let Some((dst, bits)) = read(v, 8) {
+        v.cs[v.active_frame].locals[dst] = Register::new(bits, ValueType::I64);
+    }
+}
+
+#[inline(always)]
+pub fn read_f32(v: &mut Vm) {
+    if let Some((dst, bits)) = read(v, 4) {
+        v.cs[v.active_frame].locals[dst] = Register::new(bits, ValueType::F32);
+    }
+}
+
+#[inline(always)]
+pub fn read_f64(v: &mut Vm) {
+    if let Some((dst, bits)) = read(v, 8) {
+        v.cs[v.active_frame].locals[dst] = Register::new(bits, ValueType::F64);
+    }
+}
+
+#[inline(always)]
+pub fn read_ptr(v: &mut Vm) {
+    if let Some((dst, bits)) = read(v, 8) {
+        v.cs[v.active_frame].locals[dst] = Register::new(bits, ValueType::Ptr);
+    }
+}
+
+// Decodes `Write* r1 r2 imm` and writes the low `len` bytes of r2
+#[inline(always)]
+fn write(v: &mut Vm, len: u64) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let off = v.prog[(v.ip + 2) as usize];
+    let Some(addr) = address(v, dst, off) else {
+        return;
+    };
+    let bits = v.cs[v.active_frame].locals[src].bits;
+    match v.mem.write(addr, len, bits) {
+        Ok(()) => v.ip += 3,
+        Err(trap) => v.trap(trap),
+    }
+}
+
+#[inline(always)]
+pub fn write_8(v: &mut Vm) {
+    write(v, 1);
+}
+
+#[inline(always)]
+pub fn write_16(v: &mut Vm) {
+    write(v, 2);
+}
+
+#[inline(always)]
+pub fn write_32(v: &mut Vm) {
+    write(v, 4);
+}
+
+#[inline(always)]
+pub fn write_64(v: &mut Vm) {
+    write(v, 8);
+}
+
+#[inline(always)]
+pub fn mem_copy(v: &mut Vm) {
+    let dr = v.prog[v.ip as usize] as usize;
+    let sr = v.prog[(v.ip + 1) as usize] as usize;
+    let len = v.prog[(v.ip + 2) as usize];
+    let (Some(dst), Some(src)) = (address(v, dr, 0), address(v, sr, 0)) else {
+        return;
+    };
+    match v.mem.copy(dst, src, len) {
+        Ok(()) => v.ip += 3,
+        Err(trap) => v.trap(trap),
+    }
+}
+
+#[inline(always)]
+pub fn frame_addr(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let off = v.prog[(v.ip + 1) as usize];
+    let frame = &mut v.cs[v.active_frame];
+    frame.locals[dst] = Register::new(frame.mem_base + off, ValueType::Ptr);
+    v.ip += 2;
+}
+
+#[inline(always)]
+pub fn offset(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let src = v.prog[(v.ip + 1) as usize] as usize;
+    let off = v.prog[(v.ip + 2) as usize];
+    if let Some(addr) = address(v, src, off) {
+        v.cs[v.active_frame].locals[dst] = Register::new(addr, ValueType::Ptr);
+        v.ip += 3;
+    }
+}
+
+#[inline(always)]
+pub fn index(v: &mut Vm) {
+    let dst = v.prog[v.ip as usize] as usize;
+    let base = v.prog[(v.ip + 1) as usize] as usize;
+    let ir = v.prog[(v.ip + 2) as usize] as usize;
+    let size = v.prog[(v.ip + 3) as usize];
+    let len = v.prog[(v.ip + 4) as usize];
+    let i = v.cs[v.active_frame].locals[ir].bits;
+    if len != u64::MAX && i >= len {
+        v.trap(Trap::IndexOutOfBounds);
+        return;
+    }
+    if let Some(addr) = address(v, base, i.wrapping_mul(size)) {
+        v.cs[v.active_frame].locals[dst] = Register::new(addr, ValueType::Ptr);
+        v.ip += 5;
+    }
+}
+
 #[inline(always)]
 pub fn ret(v: &mut Vm) {
     let finished = v.cs.pop().unwrap();
+    v.mem.truncate(finished.mem_mark);
     if let Some(caller) = v.cs.last_mut() {
         if let Some(dst) = finished.ret_dst {
             caller.locals[dst as usize] = finished.locals[0];
@@ -459,6 +1453,7 @@ pub fn ret(v: &mut Vm) {
         v.active_frame -= 1;
     } else {
         // returned from main function
+        v.exit = Some(finished.locals[0]);
         v.ip = u64::MAX;
         v.halted = true;
     }